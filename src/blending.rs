@@ -0,0 +1,78 @@
+use crate::tga;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    // Source over destination, both with straight alpha.
+    AlphaOver,
+    // Colours are summed, weighted by their alpha.
+    Additive,
+    // Source colour multiplies the destination where both are present.
+    Multiply,
+    // Source over destination, the source colour being premultiplied by its alpha.
+    Premultiplied,
+}
+
+// Composites `src` onto `dst`. The destination, and the result, use straight alpha.
+pub fn blend(src: tga::Rgba, dst: tga::Rgba, mode: BlendMode) -> tga::Rgba {
+    let (src_colour, src_alpha) = to_unit(src);
+    let (dst_colour, dst_alpha) = to_unit(dst);
+
+    let alpha: f32 = match mode {
+        BlendMode::Additive => (src_alpha + dst_alpha).min(1f32),
+        _ => src_alpha + dst_alpha * (1f32 - src_alpha),
+    };
+    let mut premultiplied = [0f32; 3];
+    for c in 0..3usize {
+        let (s, d) = (src_colour[c], dst_colour[c]);
+        premultiplied[c] = match mode {
+            BlendMode::AlphaOver => s * src_alpha + d * dst_alpha * (1f32 - src_alpha),
+            BlendMode::Additive => s * src_alpha + d * dst_alpha,
+            BlendMode::Multiply => {
+                s * src_alpha * (1f32 - dst_alpha)
+                    + d * dst_alpha * (1f32 - src_alpha)
+                    + s * d * src_alpha * dst_alpha
+            }
+            BlendMode::Premultiplied => s + d * dst_alpha * (1f32 - src_alpha),
+        };
+    }
+
+    if alpha <= 0f32 {
+        return tga::Rgba {
+            b: 0,
+            g: 0,
+            r: 0,
+            a: 0,
+        };
+    }
+    let colour: [u8; 3] = premultiplied.map(|c| to_u8(c / alpha));
+    return tga::Rgba {
+        b: colour[2],
+        g: colour[1],
+        r: colour[0],
+        a: to_u8(alpha),
+    };
+}
+
+// Straight alpha colour with its RGB scaled by its alpha, as `BlendMode::Premultiplied` expects.
+pub fn premultiply(colour: tga::Rgba) -> tga::Rgba {
+    let (rgb, alpha) = to_unit(colour);
+    let [r, g, b] = rgb.map(|c| to_u8(c * alpha));
+    return tga::Rgba {
+        b,
+        g,
+        r,
+        a: colour.a,
+    };
+}
+
+fn to_unit(colour: tga::Rgba) -> ([f32; 3], f32) {
+    let (r, g, b, a) = (colour.r, colour.g, colour.b, colour.a);
+    return (
+        [r as f32 / 255f32, g as f32 / 255f32, b as f32 / 255f32],
+        a as f32 / 255f32,
+    );
+}
+
+fn to_u8(value: f32) -> u8 {
+    return (value * 255f32).round().clamp(0f32, 255f32) as u8;
+}
@@ -0,0 +1,243 @@
+use crate::tga::{self, ColorSpace, PixelChannels};
+use std::io::{self, prelude::*};
+
+// Windows bitmap reading and writing, for 24 bit and 32 bit images. Greyscale and RGB images are
+// written as 24 bit BITMAPINFOHEADER files, RGBA ones as 32 bit BITMAPV5HEADER files with alpha
+// masks. With `vflip`, the first image row is the bottom of the picture, as in the drawers
+// output.
+
+const FILE_HEADER_SIZE: u32 = 14;
+const INFO_HEADER_SIZE: u32 = 40;
+const V5_HEADER_SIZE: u32 = 124;
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+const BI_ALPHABITFIELDS: u32 = 6;
+const PIXELS_PER_METRE: i32 = 2835;
+const LCS_SRGB: u32 = 0x73524742;
+const LCS_GM_IMAGES: u32 = 4;
+
+impl<T: PixelChannels> tga::Image<T> {
+    pub fn write_bmp<W: io::Write>(
+        &self,
+        writer: &mut io::BufWriter<W>,
+        vflip: bool,
+    ) -> io::Result<()> {
+        let has_alpha: bool = T::BPP == tga::Rgba::BPP;
+        let bytes_per_pixel: u32 = if has_alpha { 4 } else { 3 };
+        let stride: u64 = (self.width as u64 * bytes_per_pixel as u64).div_ceil(4) * 4;
+        let image_size: u64 = stride * self.height as u64;
+        // Sizes and offsets are 32 bit fields.
+        if self.width > i32::MAX as u32
+            || self.height > i32::MAX as u32
+            || image_size + (FILE_HEADER_SIZE + V5_HEADER_SIZE) as u64 > u32::MAX as u64
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                tga::Error::InvalidDimensions.to_string(),
+            ));
+        }
+        let image_size: u32 = image_size as u32;
+        let header_size: u32 = if has_alpha {
+            V5_HEADER_SIZE
+        } else {
+            INFO_HEADER_SIZE
+        };
+        let pixel_offset: u32 = FILE_HEADER_SIZE + header_size;
+
+        writer.write_all(b"BM")?;
+        writer.write_all(&(pixel_offset + image_size).to_le_bytes())?;
+        writer.write_all(&[0u8; 4])?;
+        writer.write_all(&pixel_offset.to_le_bytes())?;
+
+        writer.write_all(&header_size.to_le_bytes())?;
+        writer.write_all(&(self.width as i32).to_le_bytes())?;
+        // A positive height stores the rows bottom-up.
+        writer.write_all(&(self.height as i32).to_le_bytes())?;
+        writer.write_all(&1u16.to_le_bytes())?;
+        writer.write_all(&(bytes_per_pixel as u16 * 8).to_le_bytes())?;
+        let compression: u32 = if has_alpha { BI_BITFIELDS } else { BI_RGB };
+        writer.write_all(&compression.to_le_bytes())?;
+        writer.write_all(&image_size.to_le_bytes())?;
+        writer.write_all(&PIXELS_PER_METRE.to_le_bytes())?;
+        writer.write_all(&PIXELS_PER_METRE.to_le_bytes())?;
+        writer.write_all(&[0u8; 8])?;
+        if has_alpha {
+            for mask in [0x00ff0000u32, 0x0000ff00, 0x000000ff, 0xff000000] {
+                writer.write_all(&mask.to_le_bytes())?;
+            }
+            writer.write_all(&LCS_SRGB.to_le_bytes())?;
+            // Colour space endpoints and gamma, unused with sRGB.
+            writer.write_all(&[0u8; 48])?;
+            writer.write_all(&LCS_GM_IMAGES.to_le_bytes())?;
+            // Profile data, profile size and reserved.
+            writer.write_all(&[0u8; 12])?;
+        }
+
+        let width: usize = self.width as usize;
+        let mut channels: Vec<u8> = Vec::with_capacity(width * T::BPP as usize);
+        let mut row_bytes: Vec<u8> = Vec::with_capacity(stride as usize);
+        for y in 0..self.height as usize {
+            let row: usize = if vflip {
+                y
+            } else {
+                self.height as usize - 1 - y
+            };
+            channels.clear();
+            for pixel in &self.data[row * width..(row + 1) * width] {
+                pixel.push_channels(&mut channels);
+            }
+            row_bytes.clear();
+            for pixel in channels.chunks_exact(T::BPP as usize) {
+                match pixel {
+                    [grey] => row_bytes.extend_from_slice(&[*grey, *grey, *grey]),
+                    [r, g, b] => row_bytes.extend_from_slice(&[*b, *g, *r]),
+                    [r, g, b, a] => row_bytes.extend_from_slice(&[*b, *g, *r, *a]),
+                    _ => (),
+                }
+            }
+            row_bytes.resize(stride as usize, 0);
+            writer.write_all(&row_bytes)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+// Uncompressed 24 and 32 bit bitmaps with any header version up to V5. 32 bit images with an
+// alpha mask decode to `Rgba`, the others to `Rgb`.
+pub fn read<R: io::Read>(reader: &mut R, vflip: bool) -> io::Result<tga::DynamicImage> {
+    let mut file_header = [0u8; FILE_HEADER_SIZE as usize];
+    reader.read_exact(&mut file_header)?;
+    if &file_header[0..2] != b"BM" {
+        return Err(invalid_data("missing BMP signature"));
+    }
+    let pixel_offset: usize = u32_at(&file_header, 10) as usize;
+
+    let mut header_size = [0u8; 4];
+    reader.read_exact(&mut header_size)?;
+    let header_size: usize = u32::from_le_bytes(header_size) as usize;
+    if header_size < INFO_HEADER_SIZE as usize {
+        return Err(invalid_data("unsupported BMP header"));
+    }
+    // Fields past the V5 header are skipped.
+    let mut header: Vec<u8> = vec![0u8; header_size.min(V5_HEADER_SIZE as usize)];
+    reader.read_exact(&mut header[4..])?;
+    io::copy(
+        &mut reader.by_ref().take((header_size - header.len()) as u64),
+        &mut io::sink(),
+    )?;
+
+    let width: i32 = u32_at(&header, 4) as i32;
+    let height: i32 = u32_at(&header, 8) as i32;
+    let bits_per_pixel: u16 = u16::from_le_bytes([header[14], header[15]]);
+    let compression: u32 = u32_at(&header, 16);
+    let mut consumed: usize = FILE_HEADER_SIZE as usize + header_size;
+
+    let mut masks: [u32; 4] = [0x00ff0000, 0x0000ff00, 0x000000ff, 0];
+    match compression {
+        BI_RGB => (),
+        BI_BITFIELDS | BI_ALPHABITFIELDS if header_size >= 52 => {
+            let mask_count: usize = if header_size >= 56 { 4 } else { 3 };
+            for (i, mask) in masks.iter_mut().enumerate().take(mask_count) {
+                *mask = u32_at(&header, 40 + 4 * i);
+            }
+        }
+        BI_BITFIELDS | BI_ALPHABITFIELDS => {
+            let mask_count: usize = if compression == BI_ALPHABITFIELDS {
+                4
+            } else {
+                3
+            };
+            let mut mask_bytes: Vec<u8> = vec![0u8; 4 * mask_count];
+            reader.read_exact(&mut mask_bytes)?;
+            consumed += mask_bytes.len();
+            for (i, mask) in masks.iter_mut().enumerate().take(mask_count) {
+                *mask = u32_at(&mask_bytes, 4 * i);
+            }
+        }
+        _ => return Err(invalid_data("compressed bitmaps are not supported")),
+    }
+    if bits_per_pixel != 24 && bits_per_pixel != 32 {
+        return Err(invalid_data("only 24 and 32 bit bitmaps are supported"));
+    }
+    if bits_per_pixel == 24 {
+        masks = [0x00ff0000, 0x0000ff00, 0x000000ff, 0];
+    }
+    if width <= 0
+        || height == 0
+        || tga::decoded_size(width as usize, height.unsigned_abs() as usize, 4).is_err()
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            tga::Error::InvalidDimensions.to_string(),
+        ));
+    }
+
+    if pixel_offset < consumed {
+        return Err(invalid_data("pixel data overlaps the header"));
+    }
+    io::copy(
+        &mut reader.by_ref().take((pixel_offset - consumed) as u64),
+        &mut io::sink(),
+    )?;
+
+    let (width, height, bottom_up) = (width as usize, height.unsigned_abs() as usize, height > 0);
+    let bytes_per_pixel: usize = bits_per_pixel as usize / 8;
+    let stride: usize = (width * bytes_per_pixel).div_ceil(4) * 4;
+    // Rows are appended as they are read, so that a truncated file does not allocate the whole
+    // image.
+    let mut pixels: Vec<[u8; 4]> = Vec::new();
+    let mut row_bytes: Vec<u8> = vec![0u8; stride];
+    for _ in 0..height {
+        reader.read_exact(&mut row_bytes)?;
+        for x in 0..width {
+            let bytes: &[u8] = &row_bytes[x * bytes_per_pixel..(x + 1) * bytes_per_pixel];
+            let value: u32 = bytes
+                .iter()
+                .rev()
+                .fold(0u32, |value, &byte| value << 8 | byte as u32);
+            pixels.push(masks.map(|mask| extract_channel(value, mask)));
+        }
+    }
+    if bottom_up != vflip {
+        pixels = pixels.chunks(width).rev().flatten().copied().collect();
+    }
+
+    return Ok(if masks[3] != 0 {
+        tga::DynamicImage::Rgba(to_image(&pixels, width, height))
+    } else {
+        tga::DynamicImage::Rgb(to_image(&pixels, width, height))
+    });
+}
+
+fn to_image<T: PixelChannels>(pixels: &[[u8; 4]], width: usize, height: usize) -> tga::Image<T> {
+    let mut img: tga::Image<T> = tga::Image::new(width as u32, height as u32);
+    for (pixel, rgba) in img.data.iter_mut().zip(pixels) {
+        *pixel = T::from_channels(&rgba[..T::BPP as usize]);
+    }
+    return img;
+}
+
+// Channel selected by `mask`, rescaled to 8 bits.
+fn extract_channel(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 255;
+    }
+    let shift: u32 = mask.trailing_zeros();
+    let max: u32 = mask >> shift;
+    let channel: u32 = (value & mask) >> shift;
+    return (channel as u64 * 255 / max as u64) as u8;
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    return u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ]);
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+}
@@ -1,3 +1,6 @@
+use crate::linear_algebra::{self, Mat4};
+use crate::mesh::Point3D;
+
 #[derive(Copy, Clone, Debug)]
 pub struct BoundingBox3D {
     pub min_x: f32,
@@ -9,31 +12,200 @@ pub struct BoundingBox3D {
 }
 
 impl BoundingBox3D {
+    // Contains nothing, and leaves any box unchanged in a union.
+    pub fn empty() -> Self {
+        return BoundingBox3D {
+            min_x: f32::INFINITY,
+            min_y: f32::INFINITY,
+            min_z: f32::INFINITY,
+            max_x: f32::NEG_INFINITY,
+            max_y: f32::NEG_INFINITY,
+            max_z: f32::NEG_INFINITY,
+        };
+    }
+
+    pub fn from_points<I: IntoIterator<Item = Point3D<f32>>>(points: I) -> Self {
+        let mut bounding_box: BoundingBox3D = BoundingBox3D::empty();
+        for p in points {
+            bounding_box.add_point(p);
+        }
+        return bounding_box;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return !(self.min_x <= self.max_x && self.min_y <= self.max_y && self.min_z <= self.max_z);
+    }
+
+    pub fn add_point(&mut self, p: Point3D<f32>) {
+        self.min_x = self.min_x.min(p.x);
+        self.min_y = self.min_y.min(p.y);
+        self.min_z = self.min_z.min(p.z);
+        self.max_x = self.max_x.max(p.x);
+        self.max_y = self.max_y.max(p.y);
+        self.max_z = self.max_z.max(p.z);
+    }
+
+    // Grows the box around its center, each extent by `padding_percentage` percent.
     pub fn pad(&mut self, padding_percentage: f32) {
-        let total_percentage: f32 = 1f32 + padding_percentage / 100f32;
-        self.min_x *= total_percentage;
-        self.min_y *= total_percentage;
-        self.min_z *= total_percentage;
-        self.max_x *= total_percentage;
-        self.max_y *= total_percentage;
-        self.max_z *= total_percentage;
+        let center: Point3D<f32> = self.center();
+        let extent: Point3D<f32> = self.extent();
+        let half_factor: f32 = (1f32 + padding_percentage / 100f32) / 2f32;
+        self.min_x = center.x - extent.x * half_factor;
+        self.min_y = center.y - extent.y * half_factor;
+        self.min_z = center.z - extent.z * half_factor;
+        self.max_x = center.x + extent.x * half_factor;
+        self.max_y = center.y + extent.y * half_factor;
+        self.max_z = center.z + extent.z * half_factor;
+    }
+
+    pub fn union(&self, other: &BoundingBox3D) -> BoundingBox3D {
+        return BoundingBox3D {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            min_z: self.min_z.min(other.min_z),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+            max_z: self.max_z.max(other.max_z),
+        };
+    }
+
+    // Empty when the boxes do not overlap.
+    pub fn intersection(&self, other: &BoundingBox3D) -> BoundingBox3D {
+        return BoundingBox3D {
+            min_x: self.min_x.max(other.min_x),
+            min_y: self.min_y.max(other.min_y),
+            min_z: self.min_z.max(other.min_z),
+            max_x: self.max_x.min(other.max_x),
+            max_y: self.max_y.min(other.max_y),
+            max_z: self.max_z.min(other.max_z),
+        };
+    }
+
+    // Points on the faces of the box are inside it.
+    pub fn contains(&self, p: Point3D<f32>) -> bool {
+        return self.min_x <= p.x
+            && p.x <= self.max_x
+            && self.min_y <= p.y
+            && p.y <= self.max_y
+            && self.min_z <= p.z
+            && p.z <= self.max_z;
+    }
+
+    pub fn overlaps(&self, other: &BoundingBox3D) -> bool {
+        return !self.intersection(other).is_empty();
+    }
+
+    pub fn center(&self) -> Point3D<f32> {
+        return Point3D {
+            x: (self.min_x + self.max_x) / 2f32,
+            y: (self.min_y + self.max_y) / 2f32,
+            z: (self.min_z + self.max_z) / 2f32,
+        };
+    }
+
+    // Size along each axis, null for an empty box.
+    pub fn extent(&self) -> Point3D<f32> {
+        if self.is_empty() {
+            return Point3D {
+                x: 0f32,
+                y: 0f32,
+                z: 0f32,
+            };
+        }
+        return Point3D {
+            x: self.max_x - self.min_x,
+            y: self.max_y - self.min_y,
+            z: self.max_z - self.min_z,
+        };
+    }
+
+    pub fn diagonal(&self) -> f32 {
+        return linear_algebra::norm(self.extent());
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let extent: Point3D<f32> = self.extent();
+        return 2f32 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x);
+    }
+
+    // The box around the eight transformed corners, which holds everything the original box
+    // held.
+    pub fn transform(&self, matrix: &Mat4) -> BoundingBox3D {
+        if self.is_empty() {
+            return *self;
+        }
+        let corners = (0..8usize).map(|corner| {
+            matrix.transform_point(Point3D {
+                x: if corner & 1 == 0 {
+                    self.min_x
+                } else {
+                    self.max_x
+                },
+                y: if corner & 2 == 0 {
+                    self.min_y
+                } else {
+                    self.max_y
+                },
+                z: if corner & 4 == 0 {
+                    self.min_z
+                } else {
+                    self.max_z
+                },
+            })
+        });
+        return BoundingBox3D::from_points(corners);
+    }
+
+    // Slab test against the ray `origin + t * direction`, returning the range of t in
+    // [t_min, t_max] inside the box, if any.
+    pub fn intersect_ray(
+        &self,
+        origin: Point3D<f32>,
+        direction: Point3D<f32>,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<(f32, f32)> {
+        if self.is_empty() {
+            return None;
+        }
+        let (mut near, mut far) = (t_min, t_max);
+        for (o, d, min, max) in [
+            (origin.x, direction.x, self.min_x, self.max_x),
+            (origin.y, direction.y, self.min_y, self.max_y),
+            (origin.z, direction.z, self.min_z, self.max_z),
+        ] {
+            if d == 0f32 {
+                // Parallel to the slab, which the origin has to lie in.
+                if o < min || o > max {
+                    return None;
+                }
+                continue;
+            }
+            let (t0, t1) = ((min - o) / d, (max - o) / d);
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+            if near > far {
+                return None;
+            }
+        }
+        return Some((near, far));
     }
 }
 
 #[derive(Copy, Clone, Debug)]
 pub struct BoundingBox2D {
-    pub min_u: u16,
-    pub min_v: u16,
-    pub max_u: u16,
-    pub max_v: u16,
+    pub min_u: u32,
+    pub min_v: u32,
+    pub max_u: u32,
+    pub max_v: u32,
 }
 
 impl BoundingBox2D {
-    pub fn get_bounding_box(triangle_u: &[u16; 3], triangle_v: &[u16; 3]) -> Self {
-        let mut min_u: u16 = u16::MAX;
-        let mut min_v: u16 = u16::MAX;
-        let mut max_u: u16 = 0;
-        let mut max_v: u16 = 0;
+    pub fn get_bounding_box(triangle_u: &[u32; 3], triangle_v: &[u32; 3]) -> Self {
+        let mut min_u: u32 = u32::MAX;
+        let mut min_v: u32 = u32::MAX;
+        let mut max_u: u32 = 0;
+        let mut max_v: u32 = 0;
         for i in 0..3usize {
             let u = triangle_u[i];
             let v = triangle_v[i];
@@ -50,3 +222,132 @@ impl BoundingBox2D {
         };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, SQRT_2};
+
+    fn point(x: f32, y: f32, z: f32) -> Point3D<f32> {
+        return Point3D { x, y, z };
+    }
+
+    fn bounding_box(min: [f32; 3], max: [f32; 3]) -> BoundingBox3D {
+        return BoundingBox3D::from_points([
+            point(min[0], min[1], min[2]),
+            point(max[0], max[1], max[2]),
+        ]);
+    }
+
+    fn assert_bounds(actual: &BoundingBox3D, min: [f32; 3], max: [f32; 3]) {
+        let bounds: [f32; 6] = [
+            actual.min_x,
+            actual.min_y,
+            actual.min_z,
+            actual.max_x,
+            actual.max_y,
+            actual.max_z,
+        ];
+        let expected: [f32; 6] = [min[0], min[1], min[2], max[0], max[1], max[2]];
+        for (a, e) in bounds.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-5f32, "{actual:?} {min:?} {max:?}");
+        }
+    }
+
+    #[test]
+    fn pad_keeps_the_center() {
+        let mut padded: BoundingBox3D = bounding_box([2f32, -3f32, 10f32], [6f32, -1f32, 11f32]);
+        padded.pad(50f32);
+        assert_bounds(&padded, [1f32, -3.5f32, 9.75f32], [7f32, -0.5f32, 11.25f32]);
+        let center: Point3D<f32> = padded.center();
+        assert_eq!([center.x, center.y, center.z], [4f32, -2f32, 10.5f32]);
+    }
+
+    #[test]
+    fn intersection_of_disjoint_boxes_is_empty() {
+        let unit: BoundingBox3D = bounding_box([0f32; 3], [1f32; 3]);
+        let apart: BoundingBox3D = bounding_box([2f32, 0f32, 0f32], [3f32, 1f32, 1f32]);
+        assert!(unit.intersection(&apart).is_empty());
+        assert!(!unit.overlaps(&apart));
+        assert_eq!(unit.intersection(&apart).extent().x, 0f32);
+        assert!(unit.intersection(&BoundingBox3D::empty()).is_empty());
+
+        // Boxes sharing a face overlap on it.
+        let touching: BoundingBox3D = bounding_box([1f32, 0f32, 0f32], [2f32, 1f32, 1f32]);
+        assert!(unit.overlaps(&touching));
+        assert_bounds(&unit.intersection(&touching), [1f32, 0f32, 0f32], [1f32; 3]);
+
+        let shifted: BoundingBox3D = bounding_box([0.5f32; 3], [3f32; 3]);
+        assert_bounds(&unit.intersection(&shifted), [0.5f32; 3], [1f32; 3]);
+        assert_bounds(&unit.union(&shifted), [0f32; 3], [3f32; 3]);
+    }
+
+    #[test]
+    fn rays_hit_miss_and_start_inside() {
+        let slab: BoundingBox3D = bounding_box([1f32, -1f32, -1f32], [2f32, 1f32, 1f32]);
+        let along_x: Point3D<f32> = point(1f32, 0f32, 0f32);
+        let origin: Point3D<f32> = point(0f32, 0f32, 0f32);
+        assert_eq!(
+            slab.intersect_ray(origin, along_x, 0f32, f32::INFINITY),
+            Some((1f32, 2f32))
+        );
+        // Against the direction, with the far side first.
+        assert_eq!(
+            slab.intersect_ray(
+                point(4f32, 0.5f32, 0f32),
+                point(-2f32, 0f32, 0f32),
+                0f32,
+                10f32
+            ),
+            Some((1f32, 1.5f32))
+        );
+
+        // Parallel outside a slab, passing beside the box, behind the origin, or past t_max.
+        let beside: Point3D<f32> = point(0f32, 2f32, 0f32);
+        assert_eq!(
+            slab.intersect_ray(beside, along_x, 0f32, f32::INFINITY),
+            None
+        );
+        let steep: Point3D<f32> = point(1f32, 3f32, 0f32);
+        assert_eq!(slab.intersect_ray(origin, steep, 0f32, f32::INFINITY), None);
+        let past: Point3D<f32> = point(3f32, 0f32, 0f32);
+        assert_eq!(slab.intersect_ray(past, along_x, 0f32, f32::INFINITY), None);
+        assert_eq!(slab.intersect_ray(origin, along_x, 0f32, 0.5f32), None);
+        assert_eq!(
+            BoundingBox3D::empty().intersect_ray(origin, along_x, 0f32, f32::INFINITY),
+            None
+        );
+
+        // From inside, the range starts at t_min.
+        let inside: Point3D<f32> = point(1.5f32, 0f32, 0f32);
+        assert_eq!(
+            slab.intersect_ray(inside, point(-1f32, 0f32, 0f32), 0f32, f32::INFINITY),
+            Some((0f32, 0.5f32))
+        );
+        assert_eq!(
+            slab.intersect_ray(inside, point(0f32, 0f32, 4f32), 0f32, f32::INFINITY),
+            Some((0f32, 0.25f32))
+        );
+    }
+
+    #[test]
+    fn transform_bounds_the_rotated_corners() {
+        let z_axis: Point3D<f32> = point(0f32, 0f32, 1f32);
+        let flat: BoundingBox3D = bounding_box([0f32, 0f32, -1f32], [2f32, 1f32, 1f32]);
+        // A quarter turn takes (x, y) to (-y, x).
+        let quarter: BoundingBox3D = flat.transform(&Mat4::rotation(z_axis, FRAC_PI_2));
+        assert_bounds(&quarter, [-1f32, 0f32, -1f32], [0f32, 2f32, 1f32]);
+
+        // The corners of a cube turned by 45 degrees reach out by the half diagonal.
+        let cube: BoundingBox3D = bounding_box([-1f32; 3], [1f32; 3]);
+        let matrix: Mat4 =
+            Mat4::translation(point(5f32, 0f32, 0f32)).multiply(&Mat4::rotation(z_axis, FRAC_PI_4));
+        assert_bounds(
+            &cube.transform(&matrix),
+            [5f32 - SQRT_2, -SQRT_2, -1f32],
+            [5f32 + SQRT_2, SQRT_2, 1f32],
+        );
+
+        assert!(BoundingBox3D::empty().transform(&matrix).is_empty());
+    }
+}
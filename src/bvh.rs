@@ -0,0 +1,505 @@
+use crate::bounding_box::BoundingBox3D;
+use crate::linear_algebra;
+use crate::mesh::{Mesh, Point3D};
+
+// Bounding volume hierarchy over the triangles of a mesh, for ray casting, picking and culling.
+// Nodes are split along the longest axis of their triangle centroids, where the surface area
+// heuristic, evaluated over a few bins, finds the cheapest split.
+//
+// The hierarchy does not keep the mesh, which every query takes again. After moving vertices,
+// `refit` updates the boxes without changing the tree, which stays valid but gets slower as the
+// triangles drift away from where they were at build time.
+
+const BIN_COUNT: usize = 12;
+// Nodes with more triangles are split even when the heuristic prefers a leaf.
+const MAX_LEAF_TRIANGLES: usize = 8;
+// Cost of visiting a node, relative to testing a triangle.
+const TRAVERSAL_COST: f32 = 1f32;
+
+#[derive(Copy, Clone, Debug)]
+struct Node {
+    bounds: BoundingBox3D,
+    // First triangle of a leaf, or the left child of an inner node, the right child following it.
+    first: usize,
+    // Zero for inner nodes.
+    count: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Bvh {
+    // Parents come before their children.
+    nodes: Vec<Node>,
+    // Triangle indices, the leaves owning consecutive ranges.
+    triangles: Vec<usize>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct RayHit {
+    pub triangle: usize,
+    // Distance along the ray, in units of its direction.
+    pub t: f32,
+    // Weights of the three corners of the triangle at the hit point.
+    pub barycentric: Point3D<f32>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct NearestPoint {
+    pub triangle: usize,
+    pub point: Point3D<f32>,
+    pub distance: f32,
+}
+
+impl Bvh {
+    pub fn new(mesh: &Mesh) -> Self {
+        let bounds: Vec<BoundingBox3D> = triangle_bounds(mesh);
+        let centroids: Vec<Point3D<f32>> = bounds.iter().map(|b| b.center()).collect();
+        let mut bvh: Bvh = Bvh {
+            nodes: Vec::new(),
+            triangles: (0..mesh.triangles.len()).collect(),
+        };
+        if bvh.triangles.is_empty() {
+            return bvh;
+        }
+
+        bvh.nodes.push(Node {
+            bounds: BoundingBox3D::empty(),
+            first: 0,
+            count: bvh.triangles.len(),
+        });
+        let mut pending: Vec<usize> = vec![0];
+        while let Some(n) = pending.pop() {
+            let Node { first, count, .. } = bvh.nodes[n];
+            let range: &mut [usize] = &mut bvh.triangles[first..first + count];
+            bvh.nodes[n].bounds = range
+                .iter()
+                .fold(BoundingBox3D::empty(), |sum, &t| sum.union(&bounds[t]));
+
+            let Some(left_count) = split(range, &bounds, &centroids) else {
+                continue;
+            };
+            let left: usize = bvh.nodes.len();
+            bvh.nodes.push(Node {
+                bounds: BoundingBox3D::empty(),
+                first,
+                count: left_count,
+            });
+            bvh.nodes.push(Node {
+                bounds: BoundingBox3D::empty(),
+                first: first + left_count,
+                count: count - left_count,
+            });
+            bvh.nodes[n].first = left;
+            bvh.nodes[n].count = 0;
+            pending.push(left);
+            pending.push(left + 1);
+        }
+        return bvh;
+    }
+
+    // Recomputes the boxes from the current positions of `mesh`, which must have the triangles
+    // the hierarchy was built with.
+    pub fn refit(&mut self, mesh: &Mesh) {
+        let bounds: Vec<BoundingBox3D> = triangle_bounds(mesh);
+        for n in (0..self.nodes.len()).rev() {
+            let Node { first, count, .. } = self.nodes[n];
+            self.nodes[n].bounds = if count == 0 {
+                self.nodes[first]
+                    .bounds
+                    .union(&self.nodes[first + 1].bounds)
+            } else {
+                self.triangles[first..first + count]
+                    .iter()
+                    .fold(BoundingBox3D::empty(), |sum, &t| sum.union(&bounds[t]))
+            };
+        }
+    }
+
+    // Box around the whole mesh, empty without triangles.
+    pub fn bounds(&self) -> BoundingBox3D {
+        return self
+            .nodes
+            .first()
+            .map_or(BoundingBox3D::empty(), |root| root.bounds);
+    }
+
+    // First triangle hit by the ray `origin + t * direction` with t in [t_min, t_max]. Both sides
+    // of the triangles are hit.
+    pub fn closest_hit(
+        &self,
+        mesh: &Mesh,
+        origin: Point3D<f32>,
+        direction: Point3D<f32>,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<RayHit> {
+        let mut closest: Option<RayHit> = None;
+        let mut far: f32 = t_max;
+        let mut stack: Vec<(usize, f32)> = Vec::new();
+        if let Some(root) = self.nodes.first() {
+            if let Some((near, _)) = root.bounds.intersect_ray(origin, direction, t_min, far) {
+                stack.push((0, near));
+            }
+        }
+        while let Some((n, near)) = stack.pop() {
+            if near > far {
+                continue;
+            }
+            let Node { first, count, .. } = self.nodes[n];
+            if count > 0 {
+                for &t in &self.triangles[first..first + count] {
+                    if let Some(hit) = intersect_triangle(mesh, t, origin, direction, t_min, far) {
+                        far = hit.t;
+                        closest = Some(hit);
+                    }
+                }
+                continue;
+            }
+            // The nearer child goes on top of the stack.
+            let entries: Vec<(usize, f32)> = [first, first + 1]
+                .iter()
+                .filter_map(|&child| {
+                    let entry = self.nodes[child]
+                        .bounds
+                        .intersect_ray(origin, direction, t_min, far);
+                    entry.map(|(near, _)| (child, near))
+                })
+                .collect();
+            if entries.len() == 2 && entries[0].1 < entries[1].1 {
+                stack.push(entries[1]);
+                stack.push(entries[0]);
+            } else {
+                stack.extend(entries);
+            }
+        }
+        return closest;
+    }
+
+    // Whether any triangle is hit in [t_min, t_max], stopping at the first one found, as for
+    // shadow rays.
+    pub fn any_hit(
+        &self,
+        mesh: &Mesh,
+        origin: Point3D<f32>,
+        direction: Point3D<f32>,
+        t_min: f32,
+        t_max: f32,
+    ) -> bool {
+        let mut stack: Vec<usize> = if self.nodes.is_empty() {
+            Vec::new()
+        } else {
+            vec![0]
+        };
+        while let Some(n) = stack.pop() {
+            let Node {
+                bounds,
+                first,
+                count,
+            } = self.nodes[n];
+            if bounds
+                .intersect_ray(origin, direction, t_min, t_max)
+                .is_none()
+            {
+                continue;
+            }
+            if count == 0 {
+                stack.push(first);
+                stack.push(first + 1);
+            } else if self.triangles[first..first + count]
+                .iter()
+                .any(|&t| intersect_triangle(mesh, t, origin, direction, t_min, t_max).is_some())
+            {
+                return true;
+            }
+        }
+        return false;
+    }
+
+    // Triangles whose boxes overlap `query`, in no particular order. They may not touch it
+    // themselves.
+    pub fn overlapping(&self, mesh: &Mesh, query: &BoundingBox3D) -> Vec<usize> {
+        let mut found: Vec<usize> = Vec::new();
+        let mut stack: Vec<usize> = if self.nodes.is_empty() {
+            Vec::new()
+        } else {
+            vec![0]
+        };
+        while let Some(n) = stack.pop() {
+            let Node {
+                bounds,
+                first,
+                count,
+            } = self.nodes[n];
+            if !bounds.overlaps(query) {
+                continue;
+            }
+            if count == 0 {
+                stack.push(first);
+                stack.push(first + 1);
+            } else {
+                found.extend(self.triangles[first..first + count].iter().filter(|&&t| {
+                    let corners = mesh.triangles[t]
+                        .vertices
+                        .map(|v| mesh.v_positions.get_at(v));
+                    BoundingBox3D::from_points(corners).overlaps(query)
+                }));
+            }
+        }
+        return found;
+    }
+
+    // Point of the mesh nearest to `p`, None for a mesh without triangles.
+    pub fn nearest_point(&self, mesh: &Mesh, p: Point3D<f32>) -> Option<NearestPoint> {
+        let mut nearest: Option<NearestPoint> = None;
+        let mut best: f32 = f32::INFINITY;
+        let mut stack: Vec<(usize, f32)> = match self.nodes.first() {
+            Some(root) => vec![(0, distance_to_box(&root.bounds, p))],
+            None => Vec::new(),
+        };
+        while let Some((n, distance)) = stack.pop() {
+            if distance >= best {
+                continue;
+            }
+            let Node { first, count, .. } = self.nodes[n];
+            if count > 0 {
+                for &t in &self.triangles[first..first + count] {
+                    let [a, b, c] = mesh.triangles[t]
+                        .vertices
+                        .map(|v| mesh.v_positions.get_at(v));
+                    let point: Point3D<f32> = closest_point_on_triangle(p, a, b, c);
+                    let distance: f32 = linear_algebra::norm(linear_algebra::subtract(point, p));
+                    if distance < best {
+                        best = distance;
+                        nearest = Some(NearestPoint {
+                            triangle: t,
+                            point,
+                            distance,
+                        });
+                    }
+                }
+                continue;
+            }
+            let (left, right) = (
+                (first, distance_to_box(&self.nodes[first].bounds, p)),
+                (first + 1, distance_to_box(&self.nodes[first + 1].bounds, p)),
+            );
+            if left.1 < right.1 {
+                stack.push(right);
+                stack.push(left);
+            } else {
+                stack.push(left);
+                stack.push(right);
+            }
+        }
+        return nearest;
+    }
+}
+
+fn triangle_bounds(mesh: &Mesh) -> Vec<BoundingBox3D> {
+    return mesh
+        .triangles
+        .iter()
+        .map(|triangle| {
+            BoundingBox3D::from_points(triangle.vertices.map(|v| mesh.v_positions.get_at(v)))
+        })
+        .collect();
+}
+
+// Partitions `triangles` along the cheapest binned split, returning the size of the first part,
+// or None to keep them in a leaf.
+fn split(
+    triangles: &mut [usize],
+    bounds: &[BoundingBox3D],
+    centroids: &[Point3D<f32>],
+) -> Option<usize> {
+    let count: usize = triangles.len();
+    if count <= 1 {
+        return None;
+    }
+    let centroid_bounds: BoundingBox3D =
+        BoundingBox3D::from_points(triangles.iter().map(|&t| centroids[t]));
+    let extent: Point3D<f32> = centroid_bounds.extent();
+    let (axis, axis_min, axis_extent) = if extent.x >= extent.y && extent.x >= extent.z {
+        (0usize, centroid_bounds.min_x, extent.x)
+    } else if extent.y >= extent.z {
+        (1usize, centroid_bounds.min_y, extent.y)
+    } else {
+        (2usize, centroid_bounds.min_z, extent.z)
+    };
+    if axis_extent <= 0f32 {
+        // All centroids coincide, no split separates them.
+        return None;
+    }
+    let bin_of = |t: usize| -> usize {
+        let c: f32 = [centroids[t].x, centroids[t].y, centroids[t].z][axis];
+        return (((c - axis_min) / axis_extent * BIN_COUNT as f32) as usize).min(BIN_COUNT - 1);
+    };
+
+    let mut bin_bounds: [BoundingBox3D; BIN_COUNT] = [BoundingBox3D::empty(); BIN_COUNT];
+    let mut bin_counts: [usize; BIN_COUNT] = [0usize; BIN_COUNT];
+    for &t in triangles.iter() {
+        let bin: usize = bin_of(t);
+        bin_bounds[bin] = bin_bounds[bin].union(&bounds[t]);
+        bin_counts[bin] += 1;
+    }
+
+    // Cost of splitting after each bin, from sweeps in both directions.
+    let mut left_costs: [f32; BIN_COUNT] = [0f32; BIN_COUNT];
+    let mut sweep: (BoundingBox3D, usize) = (BoundingBox3D::empty(), 0);
+    for bin in 0..BIN_COUNT - 1 {
+        sweep = (sweep.0.union(&bin_bounds[bin]), sweep.1 + bin_counts[bin]);
+        left_costs[bin] = sweep.0.surface_area() * sweep.1 as f32;
+    }
+    let mut best: Option<(usize, f32)> = None;
+    sweep = (BoundingBox3D::empty(), 0);
+    for bin in (0..BIN_COUNT - 1).rev() {
+        sweep = (
+            sweep.0.union(&bin_bounds[bin + 1]),
+            sweep.1 + bin_counts[bin + 1],
+        );
+        if sweep.1 == 0 || sweep.1 == count {
+            continue;
+        }
+        let cost: f32 = left_costs[bin] + sweep.0.surface_area() * sweep.1 as f32;
+        if best.is_none_or(|(_, best_cost)| cost < best_cost) {
+            best = Some((bin, cost));
+        }
+    }
+    let (split_bin, cost) = best?;
+
+    let parent_area: f32 = bin_bounds
+        .iter()
+        .fold(BoundingBox3D::empty(), |sum, b| sum.union(b))
+        .surface_area();
+    let split_cost: f32 = TRAVERSAL_COST + cost / parent_area.max(f32::MIN_POSITIVE);
+    if split_cost >= count as f32 && count <= MAX_LEAF_TRIANGLES {
+        return None;
+    }
+
+    let mut left_count: usize = 0;
+    for i in 0..count {
+        if bin_of(triangles[i]) <= split_bin {
+            triangles.swap(i, left_count);
+            left_count += 1;
+        }
+    }
+    return Some(left_count);
+}
+
+// Möller-Trumbore intersection, None when the ray misses or is parallel to the triangle.
+fn intersect_triangle(
+    mesh: &Mesh,
+    triangle: usize,
+    origin: Point3D<f32>,
+    direction: Point3D<f32>,
+    t_min: f32,
+    t_max: f32,
+) -> Option<RayHit> {
+    let [a, b, c] = mesh.triangles[triangle]
+        .vertices
+        .map(|v| mesh.v_positions.get_at(v));
+    let ab: Point3D<f32> = linear_algebra::subtract(b, a);
+    let ac: Point3D<f32> = linear_algebra::subtract(c, a);
+    let p: Point3D<f32> = linear_algebra::cross_product(direction, ac);
+    let determinant: f32 = linear_algebra::dot_product(ab, p);
+    if determinant.abs() < f32::EPSILON * linear_algebra::norm(ab) * linear_algebra::norm(p) {
+        return None;
+    }
+    let inverse: f32 = 1f32 / determinant;
+    let s: Point3D<f32> = linear_algebra::subtract(origin, a);
+    let u: f32 = linear_algebra::dot_product(s, p) * inverse;
+    if !(0f32..=1f32).contains(&u) {
+        return None;
+    }
+    let q: Point3D<f32> = linear_algebra::cross_product(s, ab);
+    let v: f32 = linear_algebra::dot_product(direction, q) * inverse;
+    if v < 0f32 || u + v > 1f32 {
+        return None;
+    }
+    let t: f32 = linear_algebra::dot_product(ac, q) * inverse;
+    if t < t_min || t > t_max {
+        return None;
+    }
+    return Some(RayHit {
+        triangle,
+        t,
+        barycentric: Point3D {
+            x: 1f32 - u - v,
+            y: u,
+            z: v,
+        },
+    });
+}
+
+// Infinite for an empty box, like the one around triangles with NaN corners, so that its node is
+// skipped.
+fn distance_to_box(bounds: &BoundingBox3D, p: Point3D<f32>) -> f32 {
+    if bounds.is_empty() {
+        return f32::INFINITY;
+    }
+    let clamped: Point3D<f32> = Point3D {
+        x: p.x.clamp(bounds.min_x, bounds.max_x),
+        y: p.y.clamp(bounds.min_y, bounds.max_y),
+        z: p.z.clamp(bounds.min_z, bounds.max_z),
+    };
+    return linear_algebra::norm(linear_algebra::subtract(clamped, p));
+}
+
+// From Ericson, Real-Time Collision Detection, 5.1.5: finds the Voronoi region of the triangle
+// holding `p`, and projects `p` on its feature.
+fn closest_point_on_triangle(
+    p: Point3D<f32>,
+    a: Point3D<f32>,
+    b: Point3D<f32>,
+    c: Point3D<f32>,
+) -> Point3D<f32> {
+    let along = |origin: Point3D<f32>, u: Point3D<f32>, t: f32| -> Point3D<f32> {
+        return Point3D {
+            x: origin.x + t * u.x,
+            y: origin.y + t * u.y,
+            z: origin.z + t * u.z,
+        };
+    };
+    let ab: Point3D<f32> = linear_algebra::subtract(b, a);
+    let ac: Point3D<f32> = linear_algebra::subtract(c, a);
+    let ap: Point3D<f32> = linear_algebra::subtract(p, a);
+    let d1: f32 = linear_algebra::dot_product(ab, ap);
+    let d2: f32 = linear_algebra::dot_product(ac, ap);
+    if d1 <= 0f32 && d2 <= 0f32 {
+        return a;
+    }
+    let bp: Point3D<f32> = linear_algebra::subtract(p, b);
+    let d3: f32 = linear_algebra::dot_product(ab, bp);
+    let d4: f32 = linear_algebra::dot_product(ac, bp);
+    if d3 >= 0f32 && d4 <= d3 {
+        return b;
+    }
+    let vc: f32 = d1 * d4 - d3 * d2;
+    if vc <= 0f32 && d1 >= 0f32 && d3 <= 0f32 {
+        return along(a, ab, d1 / (d1 - d3));
+    }
+    let cp: Point3D<f32> = linear_algebra::subtract(p, c);
+    let d5: f32 = linear_algebra::dot_product(ab, cp);
+    let d6: f32 = linear_algebra::dot_product(ac, cp);
+    if d6 >= 0f32 && d5 <= d6 {
+        return c;
+    }
+    let vb: f32 = d5 * d2 - d1 * d6;
+    if vb <= 0f32 && d2 >= 0f32 && d6 <= 0f32 {
+        return along(a, ac, d2 / (d2 - d6));
+    }
+    let va: f32 = d3 * d6 - d5 * d4;
+    if va <= 0f32 && (d4 - d3) >= 0f32 && (d5 - d6) >= 0f32 {
+        return along(
+            b,
+            linear_algebra::subtract(c, b),
+            (d4 - d3) / ((d4 - d3) + (d5 - d6)),
+        );
+    }
+    let denominator: f32 = va + vb + vc;
+    if denominator == 0f32 {
+        // Degenerate triangle.
+        return a;
+    }
+    let (v, w) = (vb / denominator, vc / denominator);
+    return along(along(a, ab, v), ac, w);
+}
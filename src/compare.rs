@@ -0,0 +1,321 @@
+use crate::tga::{self, Grayscale, Image, PixelChannels, Rgb};
+
+// Image comparison for regression tests. A pixel's error is its largest channel difference, in 8
+// bit units. PSNR uses every channel and SSIM the luma over 8x8 windows.
+
+const SSIM_WINDOW: usize = 8;
+const SSIM_C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const SSIM_C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+#[derive(Copy, Clone, Debug)]
+pub struct Comparison {
+    pub max_error: u8,
+    pub mean_error: f64,
+    // Infinite for identical images.
+    pub psnr: f64,
+    pub ssim: f64,
+    pub differing_pixels: usize,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Tolerance {
+    pub max_error: u8,
+    pub mean_error: f64,
+    pub min_psnr: f64,
+    pub min_ssim: f64,
+    pub max_differing_pixels: usize,
+    // Pixels whose eight neighbours all match are compared as if they matched too, in every
+    // measure, which absorbs rasterisation noise along edges.
+    pub ignore_isolated_pixels: bool,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Tolerance {
+            max_error: 0,
+            mean_error: 0f64,
+            min_psnr: f64::INFINITY,
+            min_ssim: 1f64,
+            max_differing_pixels: 0,
+            ignore_isolated_pixels: false,
+        }
+    }
+}
+
+// Largest channel difference of every pixel.
+pub fn difference<T: PixelChannels>(
+    actual: &Image<T>,
+    expected: &Image<T>,
+) -> Result<Image<Grayscale>, tga::Error> {
+    if actual.width != expected.width || actual.height != expected.height {
+        return Err(tga::Error::InvalidDimensions);
+    }
+    let mut diff: Image<Grayscale> = Image::new(actual.width, actual.height);
+    let mut a: Vec<u8> = Vec::with_capacity(4);
+    let mut b: Vec<u8> = Vec::with_capacity(4);
+    for ((pixel, x), y) in diff.pixels_mut().zip(&actual.data).zip(&expected.data) {
+        a.clear();
+        b.clear();
+        x.push_channels(&mut a);
+        y.push_channels(&mut b);
+        pixel.i = a
+            .iter()
+            .zip(&b)
+            .map(|(x, y)| x.abs_diff(*y))
+            .max()
+            .unwrap_or(0);
+    }
+    return Ok(diff);
+}
+
+// Colours a difference image from black through red and yellow to white, scaled so that the
+// largest difference is white.
+pub fn heatmap(diff: &Image<Grayscale>) -> Image<Rgb> {
+    let max: u8 = diff.pixels().map(|pixel| pixel.i).max().unwrap_or(0).max(1);
+    let mut heat: Image<Rgb> = Image::new(diff.width, diff.height);
+    for (colour, pixel) in heat.pixels_mut().zip(diff.pixels()) {
+        let t: f32 = pixel.i as f32 / max as f32 * 3f32;
+        let channel = |start: f32| -> u8 { ((t - start).clamp(0f32, 1f32) * 255f32) as u8 };
+        *colour = Rgb {
+            b: channel(2f32),
+            g: channel(1f32),
+            r: channel(0f32),
+        };
+    }
+    return heat;
+}
+
+pub fn compare<T: PixelChannels>(
+    actual: &Image<T>,
+    expected: &Image<T>,
+) -> Result<Comparison, tga::Error> {
+    let diff: Image<Grayscale> = difference(actual, expected)?;
+    let channels: Vec<(u8, u8)> = channel_pairs(actual, expected);
+    let squared_error: f64 = channels
+        .iter()
+        .map(|&(a, b)| (a as f64 - b as f64).powi(2))
+        .sum::<f64>();
+    let mse: f64 = squared_error / channels.len().max(1) as f64;
+    let psnr: f64 = if mse == 0f64 {
+        f64::INFINITY
+    } else {
+        10f64 * (255f64 * 255f64 / mse).log10()
+    };
+    return Ok(Comparison {
+        max_error: diff.pixels().map(|pixel| pixel.i).max().unwrap_or(0),
+        mean_error: mean_error(&diff),
+        psnr,
+        ssim: ssim(actual, expected)?,
+        differing_pixels: diff.pixels().filter(|pixel| pixel.i > 0).count(),
+    });
+}
+
+// Mean structural similarity of the luma of both images, 1 meaning identical.
+pub fn ssim<T: PixelChannels>(actual: &Image<T>, expected: &Image<T>) -> Result<f64, tga::Error> {
+    if actual.width != expected.width || actual.height != expected.height {
+        return Err(tga::Error::InvalidDimensions);
+    }
+    let width: usize = actual.width as usize;
+    let height: usize = actual.height as usize;
+    let x: Vec<f64> = luma(actual);
+    let y: Vec<f64> = luma(expected);
+    if width < SSIM_WINDOW || height < SSIM_WINDOW {
+        // A single window covering the whole image.
+        let mut sums: [f64; 5] = [0f64; 5];
+        for (a, b) in x.iter().zip(&y) {
+            sums = [
+                sums[0] + a,
+                sums[1] + b,
+                sums[2] + a * a,
+                sums[3] + b * b,
+                sums[4] + a * b,
+            ];
+        }
+        return Ok(ssim_from_sums(sums, x.len().max(1) as f64));
+    }
+
+    // Summed area tables of x, y, x^2, y^2 and xy make every window O(1).
+    let stride: usize = width + 1;
+    let mut tables: Vec<[f64; 5]> = vec![[0f64; 5]; stride * (height + 1)];
+    for row in 0..height {
+        for column in 0..width {
+            let (a, b) = (x[row * width + column], y[row * width + column]);
+            let values: [f64; 5] = [a, b, a * a, b * b, a * b];
+            let above: [f64; 5] = tables[row * stride + column + 1];
+            let left: [f64; 5] = tables[(row + 1) * stride + column];
+            let diagonal: [f64; 5] = tables[row * stride + column];
+            tables[(row + 1) * stride + column + 1] =
+                [0usize, 1, 2, 3, 4].map(|k| values[k] + above[k] + left[k] - diagonal[k]);
+        }
+    }
+
+    let n: f64 = (SSIM_WINDOW * SSIM_WINDOW) as f64;
+    let mut total: f64 = 0f64;
+    let mut windows: usize = 0;
+    for row in 0..=height - SSIM_WINDOW {
+        for column in 0..=width - SSIM_WINDOW {
+            let corner = |r: usize, c: usize| -> [f64; 5] { tables[r * stride + c] };
+            let (top, bottom) = (row, row + SSIM_WINDOW);
+            let (left, right) = (column, column + SSIM_WINDOW);
+            let sums: [f64; 5] = [0usize, 1, 2, 3, 4].map(|k| {
+                corner(bottom, right)[k] - corner(top, right)[k] - corner(bottom, left)[k]
+                    + corner(top, left)[k]
+            });
+            total += ssim_from_sums(sums, n);
+            windows += 1;
+        }
+    }
+    return Ok(total / windows as f64);
+}
+
+// Describes how `actual` falls outside `tolerance` of `expected`, if it does. The comparison
+// returned or reported is the one the tolerance applies to.
+pub fn check_images_similar<T: PixelChannels>(
+    actual: &Image<T>,
+    expected: &Image<T>,
+    tolerance: &Tolerance,
+) -> Result<Comparison, String> {
+    let dimensions_error = |e: tga::Error| -> String {
+        format!(
+            "{}: {}x{} against {}x{}",
+            e, actual.width, actual.height, expected.width, expected.height
+        )
+    };
+    let comparison: Comparison = if tolerance.ignore_isolated_pixels {
+        let filtered: Image<T> =
+            without_isolated_differences(actual, expected).map_err(dimensions_error)?;
+        compare(&filtered, expected)
+    } else {
+        compare(actual, expected)
+    }
+    .map_err(dimensions_error)?;
+    if comparison.max_error > tolerance.max_error
+        || comparison.mean_error > tolerance.mean_error
+        || comparison.psnr < tolerance.min_psnr
+        || comparison.ssim < tolerance.min_ssim
+        || comparison.differing_pixels > tolerance.max_differing_pixels
+    {
+        return Err(format!(
+            "images differ: max error {} (tolerance {}), mean error {:.4} (tolerance {}), PSNR {:.2} dB (min {}), SSIM {:.5} (min {}), {} differing pixels (tolerance {})",
+            comparison.max_error,
+            tolerance.max_error,
+            comparison.mean_error,
+            tolerance.mean_error,
+            comparison.psnr,
+            tolerance.min_psnr,
+            comparison.ssim,
+            tolerance.min_ssim,
+            comparison.differing_pixels,
+            tolerance.max_differing_pixels
+        ));
+    }
+    return Ok(comparison);
+}
+
+// Panics with the measured values when `actual` is not within `tolerance` of `expected`.
+pub fn assert_images_similar<T: PixelChannels>(
+    actual: &Image<T>,
+    expected: &Image<T>,
+    tolerance: &Tolerance,
+) {
+    if let Err(message) = check_images_similar(actual, expected, tolerance) {
+        panic!("{}", message);
+    }
+}
+
+// `actual` with the pixels that differ from `expected` but have no differing neighbour set back
+// to their expected value.
+pub fn without_isolated_differences<T: PixelChannels>(
+    actual: &Image<T>,
+    expected: &Image<T>,
+) -> Result<Image<T>, tga::Error> {
+    let diff: Image<Grayscale> = difference(actual, expected)?;
+    let kept: Image<Grayscale> = without_isolated_pixels(&diff);
+    let mut filtered: Image<T> = actual.clone();
+    for (offset, (all, kept)) in diff.pixels().zip(kept.pixels()).enumerate() {
+        if all.i != kept.i {
+            filtered.data[offset] = expected.data[offset];
+        }
+    }
+    return Ok(filtered);
+}
+
+// Clears the differences that have no differing neighbour.
+pub fn without_isolated_pixels(diff: &Image<Grayscale>) -> Image<Grayscale> {
+    let width: i64 = diff.width as i64;
+    let height: i64 = diff.height as i64;
+    let mut filtered: Image<Grayscale> = diff.clone();
+    for y in 0..height {
+        for x in 0..width {
+            let offset: usize = (y * width + x) as usize;
+            if diff.data[offset].i == 0 {
+                continue;
+            }
+            let mut has_neighbour: bool = false;
+            for (dx, dy) in NEIGHBOURS {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx >= 0 && ny >= 0 && nx < width && ny < height {
+                    has_neighbour |= diff.data[(ny * width + nx) as usize].i > 0;
+                }
+            }
+            if !has_neighbour {
+                filtered.data[offset].i = 0;
+            }
+        }
+    }
+    return filtered;
+}
+
+const NEIGHBOURS: [(i64, i64); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+fn channel_pairs<T: PixelChannels>(actual: &Image<T>, expected: &Image<T>) -> Vec<(u8, u8)> {
+    let mut a: Vec<u8> = Vec::with_capacity(actual.data.len() * T::BPP as usize);
+    let mut b: Vec<u8> = Vec::with_capacity(expected.data.len() * T::BPP as usize);
+    for (x, y) in actual.data.iter().zip(&expected.data) {
+        x.push_channels(&mut a);
+        y.push_channels(&mut b);
+    }
+    return a.into_iter().zip(b).collect();
+}
+
+fn luma<T: PixelChannels>(img: &Image<T>) -> Vec<f64> {
+    let mut channels: Vec<u8> = Vec::with_capacity(4);
+    return img
+        .pixels()
+        .map(|pixel| {
+            channels.clear();
+            pixel.push_channels(&mut channels);
+            if channels.len() < 3 {
+                return channels[0] as f64;
+            }
+            return 0.299 * channels[0] as f64
+                + 0.587 * channels[1] as f64
+                + 0.114 * channels[2] as f64;
+        })
+        .collect();
+}
+
+fn mean_error(diff: &Image<Grayscale>) -> f64 {
+    return diff.pixels().map(|pixel| pixel.i as f64).sum::<f64>() / diff.data.len().max(1) as f64;
+}
+
+fn ssim_from_sums(sums: [f64; 5], n: f64) -> f64 {
+    let mean_x: f64 = sums[0] / n;
+    let mean_y: f64 = sums[1] / n;
+    let variance_x: f64 = (sums[2] / n - mean_x * mean_x).max(0f64);
+    let variance_y: f64 = (sums[3] / n - mean_y * mean_y).max(0f64);
+    // Clamped like the variances, so that identical windows score exactly 1.
+    let bound: f64 = (variance_x * variance_y).sqrt();
+    let covariance: f64 = (sums[4] / n - mean_x * mean_y).clamp(-bound, bound);
+    return ((2f64 * mean_x * mean_y + SSIM_C1) * (2f64 * covariance + SSIM_C2))
+        / ((mean_x * mean_x + mean_y * mean_y + SSIM_C1) * (variance_x + variance_y + SSIM_C2));
+}
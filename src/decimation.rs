@@ -0,0 +1,648 @@
+use crate::half_edge::HalfEdgeMesh;
+use crate::linear_algebra;
+use crate::mesh::{Mesh, Point3D};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+// Garland-Heckbert decimation. Edges are collapsed cheapest first, the cost being the quadric
+// error: the mean squared distance from the merged vertex to the planes of the original
+// triangles around it, weighted by their area. Boundary edges and seams, where the normal or
+// texture indices differ across an edge, add weighted planes orthogonal to their triangles which
+// hold them in place. The error reported for a collapse leaves these out, to stay a distance.
+//
+// An interior vertex with a single normal and texture goes to the position minimising the error,
+// with its normal and UV interpolated along the edge. Other vertices only move onto the other end
+// of an edge running along their boundary or seam, and the corners they leave take the attributes
+// of that end on the same side, so UVs and normals stay continuous.
+//
+// The mesh is expected to be valid, see `Mesh::validate`. Vertices at exactly the same position
+// are merged first, their corners keeping their normals and UVs.
+
+// Collapses turning a triangle by more than this from its normal before the collapse, or from its
+// normal in the original mesh, as the cosine between the normals, are refused.
+const MIN_NORMAL_COSINE: f32 = 0.2f32;
+
+#[derive(Copy, Clone, Debug)]
+pub struct DecimationOptions {
+    // Collapses stop once the mesh has at most this many triangles,
+    pub target_triangles: usize,
+    // or before the first collapse whose error, as a distance in model units, exceeds this.
+    pub max_error: f32,
+    // Weight of the planes holding boundaries and seams, relative to the triangle planes.
+    pub boundary_weight: f32,
+}
+
+impl Default for DecimationOptions {
+    fn default() -> Self {
+        DecimationOptions {
+            target_triangles: 0,
+            max_error: f32::INFINITY,
+            boundary_weight: 100f32,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Lod {
+    pub mesh: Mesh,
+    // Largest error of the collapses leading to this level, as a root mean square distance in
+    // model units.
+    pub error: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct LodChain {
+    // From the original mesh to the coarsest level.
+    pub levels: Vec<Lod>,
+    // Diagonal of the original bounding box.
+    pub diagonal: f32,
+}
+
+impl Mesh {
+    pub fn decimate(&self, options: &DecimationOptions) -> Mesh {
+        let mut levels: Vec<Lod> = decimate_levels(self, &[options.target_triangles], options);
+        return levels.remove(0).mesh;
+    }
+}
+
+impl LodChain {
+    // Up to `level_count` levels, the original mesh included, each with `reduction` times the
+    // triangles of the previous one. Levels stop early once no edge can be collapsed.
+    pub fn new(mesh: &Mesh, level_count: usize, reduction: f32) -> Self {
+        let diagonal: f32 = mesh.bounding_box().diagonal();
+        let targets: Vec<usize> = (1..level_count)
+            .map(|level| (mesh.triangles.len() as f32 * reduction.powi(level as i32)) as usize)
+            .collect();
+        let mut levels: Vec<Lod> = vec![Lod {
+            mesh: mesh.clone(),
+            error: 0f32,
+        }];
+        levels.extend(decimate_levels(
+            mesh,
+            &targets,
+            &DecimationOptions::default(),
+        ));
+        return LodChain { levels, diagonal };
+    }
+
+    // Index of the coarsest level whose error, once projected, stays within `pixel_error` pixels.
+    // `projected_size` is the on-screen length of the bounding box diagonal, in pixels, as given
+    // by `drawers::projected_size`.
+    pub fn select(&self, projected_size: f32, pixel_error: f32) -> usize {
+        let pixels_per_unit: f32 = projected_size / self.diagonal.max(f32::MIN_POSITIVE);
+        return self
+            .levels
+            .iter()
+            .rposition(|level| level.error * pixels_per_unit <= pixel_error)
+            .unwrap_or(0);
+    }
+}
+
+// Sum of squared distances to planes weighted by the area of their triangles, as the upper
+// triangle of a symmetric 4x4 matrix, with the total area.
+#[derive(Copy, Clone, Debug, Default)]
+struct Quadric {
+    coefficients: [f64; 10],
+    area: f64,
+}
+
+impl Quadric {
+    fn plane(normal: Point3D<f32>, point: Point3D<f32>, weight: f64) -> Quadric {
+        let [a, b, c] = [normal.x, normal.y, normal.z].map(f64::from);
+        let d: f64 = -(a * point.x as f64 + b * point.y as f64 + c * point.z as f64);
+        return Quadric {
+            coefficients: [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|q| q * weight),
+            area: 0f64,
+        };
+    }
+
+    fn add(&self, other: &Quadric) -> Quadric {
+        let mut sum: Quadric = *self;
+        for (q, r) in sum.coefficients.iter_mut().zip(other.coefficients) {
+            *q += r;
+        }
+        sum.area += other.area;
+        return sum;
+    }
+
+    // Mean squared distance over the area of the triangles.
+    fn error(&self, p: Point3D<f32>) -> f64 {
+        let [x, y, z] = [p.x, p.y, p.z].map(f64::from);
+        let q: &[f64; 10] = &self.coefficients;
+        let error: f64 = q[0] * x * x
+            + q[4] * y * y
+            + q[7] * z * z
+            + 2f64 * (q[1] * x * y + q[2] * x * z + q[5] * y * z)
+            + 2f64 * (q[3] * x + q[6] * y + q[8] * z)
+            + q[9];
+        return error.max(0f64) / self.area.max(f64::MIN_POSITIVE);
+    }
+
+    // Position of least error, unless the quadric is close to singular, as on flat areas.
+    fn minimum(&self) -> Option<Point3D<f32>> {
+        let q: &[f64; 10] = &self.coefficients;
+        let (a, b, c, d, e, f) = (q[0], q[1], q[2], q[4], q[5], q[7]);
+        let cofactors: [f64; 6] = [
+            d * f - e * e,
+            c * e - b * f,
+            b * e - c * d,
+            a * f - c * c,
+            b * c - a * e,
+            a * d - b * b,
+        ];
+        let determinant: f64 = a * cofactors[0] + b * cofactors[1] + c * cofactors[2];
+        let scale: f64 = a.max(d).max(f);
+        if determinant.abs() <= 1e-9 * scale * scale * scale {
+            return None;
+        }
+        let [u, v, w] = [-q[3], -q[6], -q[8]];
+        return Some(Point3D {
+            x: ((cofactors[0] * u + cofactors[1] * v + cofactors[2] * w) / determinant) as f32,
+            y: ((cofactors[1] * u + cofactors[3] * v + cofactors[4] * w) / determinant) as f32,
+            z: ((cofactors[2] * u + cofactors[4] * v + cofactors[5] * w) / determinant) as f32,
+        });
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Placement {
+    // The named end stays where it is and the other one joins it.
+    Origin,
+    Destination,
+    // Both ends move to the given position.
+    Optimal(Point3D<f32>),
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Candidate {
+    cost: f64,
+    half_edge: usize,
+    // Versions of the origin and destination when the cost was computed.
+    versions: [u32; 2],
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        return self.cmp(other) == Ordering::Equal;
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+// Reversed, for `BinaryHeap` to pop the cheapest candidate first.
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        return other
+            .cost
+            .total_cmp(&self.cost)
+            .then(other.half_edge.cmp(&self.half_edge));
+    }
+}
+
+// Normal and texture indices of a corner.
+type Attributes = (usize, usize);
+
+struct Decimation {
+    mesh: HalfEdgeMesh,
+    quadrics: Vec<Quadric>,
+    // The triangle planes alone, without those holding boundaries and seams.
+    surface_quadrics: Vec<Quadric>,
+    // Bumped whenever a vertex moves or goes, invalidating the candidates around it.
+    versions: Vec<u32>,
+    // Normals of the faces before any collapse, which the remaining ones must keep facing.
+    original_normals: Vec<Point3D<f32>>,
+    heap: BinaryHeap<Candidate>,
+    triangles: usize,
+}
+
+// Decimates `mesh` once, taking a copy of it whenever it first reaches one of the descending
+// `targets` triangle counts, and at the end if the options stop it before the last target.
+fn decimate_levels(mesh: &Mesh, targets: &[usize], options: &DecimationOptions) -> Vec<Lod> {
+    let mut decimation: Decimation = Decimation::new(mesh, options.boundary_weight as f64);
+    let mut levels: Vec<Lod> = Vec::new();
+    let mut targets = targets.iter().peekable();
+    let mut error: f32 = 0f32;
+    // Whether the mesh changed since the last copy.
+    let mut changed: bool = true;
+    loop {
+        let mut reached: bool = false;
+        while targets
+            .next_if(|&&target| decimation.triangles <= target)
+            .is_some()
+        {
+            reached = true;
+        }
+        if reached {
+            levels.push(Lod {
+                mesh: decimation.to_mesh(),
+                error,
+            });
+            changed = false;
+        }
+        if targets.peek().is_none() {
+            break;
+        }
+        let candidate: Candidate = match decimation.heap.pop() {
+            Some(candidate) => candidate,
+            None => break,
+        };
+        let placement: Placement = match decimation.plan(&candidate) {
+            Some((cost, placement)) if cost == candidate.cost => placement,
+            // The neighbourhood changed the cost, try again later.
+            Some((cost, _)) => {
+                decimation.heap.push(Candidate { cost, ..candidate });
+                continue;
+            }
+            None => continue,
+        };
+        let candidate_error: f32 = decimation.distance(candidate.half_edge, placement);
+        if candidate_error > options.max_error {
+            break;
+        }
+        if decimation.collapse(candidate.half_edge, placement) {
+            error = error.max(candidate_error);
+            changed = true;
+        }
+    }
+    if targets.peek().is_some() && (changed || levels.is_empty()) {
+        levels.push(Lod {
+            mesh: decimation.to_mesh(),
+            error,
+        });
+    }
+    return levels;
+}
+
+impl Decimation {
+    fn new(mesh: &Mesh, boundary_weight: f64) -> Self {
+        let he_mesh: HalfEdgeMesh = HalfEdgeMesh::from_mesh(&merge_duplicate_positions(mesh));
+        let mut quadrics: Vec<Quadric> = vec![Quadric::default(); he_mesh.vertex_count()];
+        let mut surface_quadrics: Vec<Quadric> = quadrics.clone();
+        let mut original_normals: Vec<Point3D<f32>> = Vec::with_capacity(he_mesh.face_count());
+        for f in 0..he_mesh.face_count() {
+            let vertices: [usize; 3] = he_mesh.face_vertices(f);
+            let [p, q, r] = vertices.map(|v| he_mesh.positions.get_at(v));
+            let normal: Point3D<f32> = linear_algebra::get_plane_normal(p, q, r);
+            original_normals.push(normal);
+            let area: f64 = linear_algebra::norm(linear_algebra::cross_product(
+                linear_algebra::subtract(q, p),
+                linear_algebra::subtract(r, p),
+            )) as f64
+                / 2f64;
+            let mut quadric: Quadric = Quadric::plane(normal, p, area);
+            quadric.area = area;
+            for v in vertices {
+                quadrics[v] = quadrics[v].add(&quadric);
+                surface_quadrics[v] = surface_quadrics[v].add(&quadric);
+            }
+
+            // Planes along the boundary and seam edges, orthogonal to the triangle.
+            for h in he_mesh.face_half_edges(f) {
+                if !is_border(&he_mesh, h) {
+                    continue;
+                }
+                let (a, b) = (he_mesh.half_edge(h).origin, he_mesh.destination(h));
+                let (pa, pb) = (he_mesh.positions.get_at(a), he_mesh.positions.get_at(b));
+                let edge: Point3D<f32> = linear_algebra::subtract(pb, pa);
+                let length: f64 = linear_algebra::norm(edge) as f64;
+                let border_normal: Point3D<f32> =
+                    linear_algebra::normalized(linear_algebra::cross_product(edge, normal));
+                let quadric: Quadric =
+                    Quadric::plane(border_normal, pa, boundary_weight * length * length);
+                quadrics[a] = quadrics[a].add(&quadric);
+                quadrics[b] = quadrics[b].add(&quadric);
+            }
+        }
+
+        let mut decimation: Decimation = Decimation {
+            versions: vec![0; he_mesh.vertex_count()],
+            original_normals,
+            triangles: he_mesh.face_count(),
+            mesh: he_mesh,
+            quadrics,
+            surface_quadrics,
+            heap: BinaryHeap::new(),
+        };
+        for h in 0..decimation.mesh.half_edge_count() {
+            // Interior edges once, from their lower numbered half-edge.
+            if decimation.mesh.half_edge(h).twin.is_none_or(|t| h < t) {
+                decimation.push_candidate(h);
+            }
+        }
+        return decimation;
+    }
+
+    fn to_mesh(&self) -> Mesh {
+        let mut mesh: Mesh = self.mesh.to_mesh();
+        mesh.remove_unreferenced_vertices();
+        return mesh;
+    }
+
+    fn push_candidate(&mut self, h: usize) {
+        let versions: [u32; 2] = [
+            self.versions[self.mesh.half_edge(h).origin],
+            self.versions[self.mesh.destination(h)],
+        ];
+        let candidate: Candidate = Candidate {
+            cost: 0f64,
+            half_edge: h,
+            versions,
+        };
+        if let Some((cost, _)) = self.plan(&candidate) {
+            self.heap.push(Candidate { cost, ..candidate });
+        }
+    }
+
+    // Cheapest allowed placement for the edge of the candidate, unless it is stale.
+    fn plan(&self, candidate: &Candidate) -> Option<(f64, Placement)> {
+        let h: usize = candidate.half_edge;
+        if self.mesh.half_edge(h).removed {
+            return None;
+        }
+        let (a, b) = (self.mesh.half_edge(h).origin, self.mesh.destination(h));
+        if [self.versions[a], self.versions[b]] != candidate.versions {
+            return None;
+        }
+        let quadric: Quadric = self.quadrics[a].add(&self.quadrics[b]);
+        let (pa, pb) = (self.mesh.positions.get_at(a), self.mesh.positions.get_at(b));
+
+        let mut best: Option<(f64, Placement)> = None;
+        let mut consider = |cost: f64, placement: Placement| {
+            if best.is_none_or(|(best_cost, _)| cost < best_cost) {
+                best = Some((cost, placement));
+            }
+        };
+        if self.corner_mapping(h, b).is_some() {
+            consider(quadric.error(pa), Placement::Origin);
+        }
+        if self.corner_mapping(h, a).is_some() {
+            consider(quadric.error(pb), Placement::Destination);
+        }
+        if self.is_smooth_interior(a) && self.is_smooth_interior(b) {
+            let midpoint: Point3D<f32> = lerp(pa, pb, 0.5f32);
+            let length: f32 = linear_algebra::norm(linear_algebra::subtract(pb, pa));
+            // Nearly singular quadrics can put the minimum far away, the midpoint is used then.
+            let position: Point3D<f32> = match quadric.minimum() {
+                Some(p)
+                    if linear_algebra::norm(linear_algebra::subtract(p, midpoint)) <= length =>
+                {
+                    p
+                }
+                _ => midpoint,
+            };
+            consider(quadric.error(position), Placement::Optimal(position));
+        }
+        return best;
+    }
+
+    // Root mean square distance from the position of the collapse to the triangle planes around
+    // the edge of `h`, unlike the cost which includes the weighted boundary and seam planes.
+    fn distance(&self, h: usize, placement: Placement) -> f32 {
+        let (a, b) = (self.mesh.half_edge(h).origin, self.mesh.destination(h));
+        let position: Point3D<f32> = match placement {
+            Placement::Origin => self.mesh.positions.get_at(a),
+            Placement::Destination => self.mesh.positions.get_at(b),
+            Placement::Optimal(p) => p,
+        };
+        let quadric: Quadric = self.surface_quadrics[a].add(&self.surface_quadrics[b]);
+        return quadric.error(position).sqrt() as f32;
+    }
+
+    // Interior vertex whose corners all share the same normal and texture indices.
+    fn is_smooth_interior(&self, v: usize) -> bool {
+        if self.mesh.is_boundary_vertex(v) || self.mesh.is_pinched(v) {
+            return false;
+        }
+        let outgoing: Vec<usize> = self.mesh.outgoing_half_edges(v);
+        return outgoing
+            .iter()
+            .all(|&g| attributes(&self.mesh, g) == attributes(&self.mesh, outgoing[0]));
+    }
+
+    // New attributes for the corners of `removed`, an end of the edge of `h`, when it joins the
+    // other end, or None if that would tear a boundary or a seam.
+    fn corner_mapping(&self, h: usize, removed: usize) -> Option<HashMap<Attributes, Attributes>> {
+        let twin: Option<usize> = self.mesh.half_edge(h).twin;
+        if self.mesh.is_pinched(removed)
+            || (twin.is_some() && self.mesh.is_boundary_vertex(removed))
+        {
+            return None;
+        }
+        // The triangles along the edge have a corner at each end.
+        let mut mapping: HashMap<Attributes, Attributes> = HashMap::new();
+        for side in [Some(h), twin].into_iter().flatten() {
+            let (from, to) = if self.mesh.half_edge(side).origin == removed {
+                (side, self.mesh.half_edge(side).next)
+            } else {
+                (self.mesh.half_edge(side).next, side)
+            };
+            let (from, to) = (attributes(&self.mesh, from), attributes(&self.mesh, to));
+            if *mapping.entry(from).or_insert(to) != to {
+                return None;
+            }
+        }
+        let all_mapped: bool = self
+            .mesh
+            .outgoing_half_edges(removed)
+            .iter()
+            .all(|&g| mapping.contains_key(&attributes(&self.mesh, g)));
+        return if all_mapped { Some(mapping) } else { None };
+    }
+
+    // Returns whether the edge of `h` could be collapsed.
+    fn collapse(&mut self, h: usize, placement: Placement) -> bool {
+        let (a, b) = (self.mesh.half_edge(h).origin, self.mesh.destination(h));
+        let (pa, pb) = (self.mesh.positions.get_at(a), self.mesh.positions.get_at(b));
+        let (position, removed) = match placement {
+            Placement::Origin => (pa, Some(b)),
+            Placement::Destination => (pb, Some(a)),
+            Placement::Optimal(p) => (p, None),
+        };
+        if self.folds_over(a, b, position) {
+            return false;
+        }
+        let mapping: Option<HashMap<Attributes, Attributes>> =
+            removed.and_then(|removed| self.corner_mapping(h, removed));
+        let removed_corners: Vec<usize> = removed
+            .map(|removed| self.mesh.outgoing_half_edges(removed))
+            .unwrap_or_default();
+        let merged_attributes: Option<Attributes> = match placement {
+            Placement::Optimal(p) => Some(self.interpolated_attributes(h, p)),
+            _ => None,
+        };
+        let twin: Option<usize> = self.mesh.half_edge(h).twin;
+
+        let merged: usize = match self.mesh.collapse_edge(h, position) {
+            Ok(merged) => merged,
+            Err(_) => return false,
+        };
+        // The origin of `h` is kept, which for `Destination` means that its corners take the
+        // attributes of the destination's.
+        if let Some(mapping) = mapping {
+            for g in removed_corners {
+                if !self.mesh.half_edge(g).removed {
+                    let (normal, texture) = mapping[&attributes(&self.mesh, g)];
+                    self.mesh.set_corner_attributes(g, normal, texture);
+                }
+            }
+        }
+        if let Some((normal, texture)) = merged_attributes {
+            for g in self.mesh.outgoing_half_edges(merged) {
+                self.mesh.set_corner_attributes(g, normal, texture);
+            }
+        }
+
+        self.quadrics[merged] = self.quadrics[a].add(&self.quadrics[b]);
+        self.surface_quadrics[merged] = self.surface_quadrics[a].add(&self.surface_quadrics[b]);
+        self.versions[a] += 1;
+        self.versions[b] += 1;
+        self.triangles -= if twin.is_some() { 2 } else { 1 };
+        for neighbour in self.mesh.one_ring(merged) {
+            let edge: Option<usize> = self
+                .mesh
+                .find_half_edge(merged, neighbour)
+                .or_else(|| self.mesh.find_half_edge(neighbour, merged));
+            if let Some(edge) = edge {
+                let twin: Option<usize> = self.mesh.half_edge(edge).twin;
+                self.push_candidate(twin.map_or(edge, |t| edge.min(t)));
+            }
+        }
+        return true;
+    }
+
+    // Whether moving `a` and `b` to `position` would turn one of their remaining triangles over.
+    fn folds_over(&self, a: usize, b: usize, position: Point3D<f32>) -> bool {
+        for v in [a, b] {
+            for f in self.mesh.vertex_faces(v) {
+                let vertices: [usize; 3] = self.mesh.face_vertices(f);
+                if vertices.contains(&a) && vertices.contains(&b) {
+                    continue;
+                }
+                let before: [Point3D<f32>; 3] = vertices.map(|w| self.mesh.positions.get_at(w));
+                let after: [Point3D<f32>; 3] = vertices.map(|w| {
+                    if w == v {
+                        position
+                    } else {
+                        self.mesh.positions.get_at(w)
+                    }
+                });
+                let normal: Point3D<f32> =
+                    linear_algebra::get_plane_normal(after[0], after[1], after[2]);
+                // Small turns would otherwise add up to a fold over successive collapses.
+                let turns: [Point3D<f32>; 2] = [
+                    linear_algebra::get_plane_normal(before[0], before[1], before[2]),
+                    self.original_normals[f],
+                ];
+                if turns.iter().any(|&previous| {
+                    linear_algebra::dot_product(previous, normal) < MIN_NORMAL_COSINE
+                }) {
+                    return true;
+                }
+            }
+        }
+        return false;
+    }
+
+    // Normal and UV at the projection of `position` on the edge of `h`, whose ends are smooth.
+    fn interpolated_attributes(&mut self, h: usize, position: Point3D<f32>) -> Attributes {
+        let next: usize = self.mesh.half_edge(h).next;
+        let ((normal_a, texture_a), (normal_b, texture_b)) =
+            (attributes(&self.mesh, h), attributes(&self.mesh, next));
+        let (a, b) = (self.mesh.half_edge(h).origin, self.mesh.destination(h));
+        let (pa, pb) = (self.mesh.positions.get_at(a), self.mesh.positions.get_at(b));
+        let edge: Point3D<f32> = linear_algebra::subtract(pb, pa);
+        let length_squared: f32 = linear_algebra::dot_product(edge, edge);
+        let t: f32 = if length_squared > 0f32 {
+            (linear_algebra::dot_product(linear_algebra::subtract(position, pa), edge)
+                / length_squared)
+                .clamp(0f32, 1f32)
+        } else {
+            0f32
+        };
+
+        let normals = &mut self.mesh.normals;
+        let normal: usize = if normal_a < normals.xs.len() && normal_b < normals.xs.len() {
+            let n: Point3D<f32> = linear_algebra::normalized(lerp(
+                normals.get_at(normal_a),
+                normals.get_at(normal_b),
+                t,
+            ));
+            normals.add_vector(n.x, n.y, n.z);
+            normals.xs.len() - 1
+        } else {
+            normal_a
+        };
+        let textures = &mut self.mesh.textures;
+        let texture: usize = if texture_a < textures.us.len() && texture_b < textures.us.len() {
+            let ((u0, v0), (u1, v1)) = (textures.get_at(texture_a), textures.get_at(texture_b));
+            textures.add_vector(u0 + (u1 - u0) * t, v0 + (v1 - v0) * t);
+            textures.us.len() - 1
+        } else {
+            texture_a
+        };
+        return (normal, texture);
+    }
+}
+
+// Triangles use the first of any exactly equal positions. Primitives and exporters split vertices
+// along UV seams and at poles, which would otherwise decimate as open boundaries, whereas with
+// their normal and texture indices kept they are seams.
+fn merge_duplicate_positions(mesh: &Mesh) -> Mesh {
+    let mut merged: Mesh = mesh.clone();
+    let mut first: HashMap<[u32; 3], usize> = HashMap::new();
+    let remap: Vec<usize> = (0..mesh.v_positions.xs.len())
+        .map(|v| {
+            // Adding zero turns -0 into +0.
+            let p: Point3D<f32> = mesh.v_positions.get_at(v);
+            let key: [u32; 3] = [p.x, p.y, p.z].map(|c| (c + 0f32).to_bits());
+            return *first.entry(key).or_insert(v);
+        })
+        .collect();
+    for triangle in &mut merged.triangles {
+        for vertex in triangle.vertices.iter_mut() {
+            *vertex = remap.get(*vertex).copied().unwrap_or(*vertex);
+        }
+    }
+    return merged;
+}
+
+fn attributes(mesh: &HalfEdgeMesh, h: usize) -> Attributes {
+    return (mesh.half_edge(h).normal, mesh.half_edge(h).texture);
+}
+
+// Boundary edge, or seam whose corners differ across the edge at either end.
+fn is_border(mesh: &HalfEdgeMesh, h: usize) -> bool {
+    return match mesh.half_edge(h).twin {
+        None => true,
+        Some(t) => {
+            attributes(mesh, h) != attributes(mesh, mesh.half_edge(t).next)
+                || attributes(mesh, mesh.half_edge(h).next) != attributes(mesh, t)
+        }
+    };
+}
+
+fn lerp(p: Point3D<f32>, q: Point3D<f32>, t: f32) -> Point3D<f32> {
+    return Point3D {
+        x: p.x + (q.x - p.x) * t,
+        y: p.y + (q.y - p.y) * t,
+        z: p.z + (q.z - p.z) * t,
+    };
+}
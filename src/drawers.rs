@@ -1,7 +1,11 @@
+use crate::blending::{self, BlendMode};
 use crate::bounding_box;
 use crate::linear_algebra;
-use crate::mesh::{Mesh, Point3D, Triangle};
+use crate::mesh::{EdgeClass, FeatureEdge, Material, Mesh, Point2D, Point3D, Triangle};
+use crate::oit;
 use crate::tga;
+use std::cmp::Ordering;
+use std::collections::HashSet;
 
 const LIGHT_DIRECTION: Point3D<f32> = Point3D {
     x: 0f32,
@@ -10,17 +14,82 @@ const LIGHT_DIRECTION: Point3D<f32> = Point3D {
 };
 //const OBSERVER_DISTANCE: f32 = 1f32;
 //const FOCAL_LENGTH: f32 = 1f32;
-const WIDTH: u16 = 1920;
-const HEIGHT: u16 = 1080;
+// Default viewport size.
+pub const WIDTH: u32 = 1920;
+pub const HEIGHT: u32 = 1080;
 const ZOOM: f32 = 0.15f32;
-const WIDTH_RENORMALISATION: f32 = WIDTH as f32 / (ZOOM * 32f32);
-const HEIGHT_RENORMALISATION: f32 = HEIGHT as f32 / (ZOOM * 18f32);
+// Polygon offset, in model units, applied when depth testing edges against rasterised faces.
+const DEPTH_OFFSET: f32 = 0.01f32;
 
-pub fn line(x0: u16, y0: u16, x1: u16, y1: u16, img: &mut tga::Image<tga::Rgb>, color: tga::Rgb) {
-    let mut start_x: u16 = x0;
-    let mut start_y: u16 = y0;
-    let mut end_x: u16 = x1;
-    let mut end_y: u16 = y1;
+// Size of the rendered images. Other sizes than the default scale the picture with the viewport
+// height or width, whichever keeps the whole default view in sight, and show more of the scene
+// along the other axis.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Viewport {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Viewport {
+            width: WIDTH,
+            height: HEIGHT,
+        }
+    }
+}
+
+impl Viewport {
+    // Model units seen across the `mesh` framing: 32 x 18 zoom steps, widened along one axis to
+    // the aspect ratio of the viewport.
+    fn zoom_extent(&self) -> (f32, f32) {
+        let (width, height) = (ZOOM * 32f32, ZOOM * 18f32);
+        let (columns, rows) = (self.width as f32, self.height.max(1) as f32);
+        return match (self.width as u64 * 18).cmp(&(self.height as u64 * 32)) {
+            Ordering::Greater => (height * columns / rows, height),
+            Ordering::Less => (width, width * rows / columns.max(1f32)),
+            Ordering::Equal => (width, height),
+        };
+    }
+
+    // Pixels per model unit, along x and y.
+    fn renormalisation(&self) -> (f32, f32) {
+        let (extent_x, extent_y) = self.zoom_extent();
+        return (self.width as f32 / extent_x, self.height as f32 / extent_y);
+    }
+
+    fn pixel_count(&self) -> usize {
+        return self.width as usize * self.height as usize;
+    }
+}
+
+pub fn line(x0: u32, y0: u32, x1: u32, y1: u32, img: &mut tga::Image<tga::Rgb>, color: tga::Rgb) {
+    shifted_line(x0, y0, x1, y1, (0, 0), img, color);
+}
+
+// Draws the pixels of `line` moved by `shift`, leaving out those falling outside the image.
+fn shifted_line(
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+    shift: (i64, i64),
+    img: &mut tga::Image<tga::Rgb>,
+    color: tga::Rgb,
+) {
+    let mut set = |x: u32, y: u32| {
+        let shifted = (
+            u32::try_from(x as i64 + shift.0),
+            u32::try_from(y as i64 + shift.1),
+        );
+        if let (Ok(x), Ok(y)) = shifted {
+            let _ = img.set(x, y, color);
+        }
+    };
+    let mut start_x: u32 = x0;
+    let mut start_y: u32 = y0;
+    let mut end_x: u32 = x1;
+    let mut end_y: u32 = y1;
 
     let mut transposed: bool = false;
     if x0.abs_diff(x1) < y0.abs_diff(y1) {
@@ -32,15 +101,15 @@ pub fn line(x0: u16, y0: u16, x1: u16, y1: u16, img: &mut tga::Image<tga::Rgb>,
         (start_x, end_x) = (end_x, start_x);
         (start_y, end_y) = (end_y, start_y);
     }
-    let dx: i32 = (end_x - start_x).into();
-    let derror2: i32 = (end_y.abs_diff(start_y) * 2).into();
-    let mut error2: i32 = 0i32;
-    let mut y: u16 = start_y;
+    let dx: i64 = (end_x - start_x) as i64;
+    let derror2: i64 = end_y.abs_diff(start_y) as i64 * 2;
+    let mut error2: i64 = 0i64;
+    let mut y: u32 = start_y;
     for x in start_x..end_x {
         if transposed {
-            let _ = img.set(y, x, color);
+            set(y, x);
         } else {
-            let _ = img.set(x, y, color);
+            set(x, y);
         }
         error2 += derror2;
         if error2 > dx {
@@ -54,51 +123,326 @@ pub fn line(x0: u16, y0: u16, x1: u16, y1: u16, img: &mut tga::Image<tga::Rgb>,
     }
 }
 
-pub fn mesh(mesh: Mesh) -> tga::Image<tga::Rgb> {
-    let mut mesh_img: tga::Image<tga::Rgb> = tga::Image::new(WIDTH, HEIGHT);
+pub fn mesh(mesh: Mesh, viewport: Viewport) -> tga::Image<tga::Rgb> {
+    let mut mesh_img: tga::Image<tga::Rgb> = tga::Image::new(viewport.width, viewport.height);
+    let mut z_buffer: Vec<f32> = new_z_buffer(viewport);
+    shade_mesh(&mesh, viewport, &mut mesh_img, &mut z_buffer);
+    return mesh_img;
+}
+
+// On-screen length, in pixels, of the bounding box diagonal of `mesh` in the `mesh` framing, for
+// picking a level with `decimation::LodChain::select`.
+pub fn projected_size(mesh: &Mesh, viewport: Viewport) -> f32 {
+    return mesh.bounding_box().diagonal() * viewport.renormalisation().0;
+}
+
+// Shaded render on a transparent background, coloured by the material diffuse colour. Opaque
+// triangles are drawn first, then triangles with a material dissolve below 1 are blended on top,
+// from back to front, without writing to the z-buffer. Material colours have straight alpha and
+// are premultiplied first for `BlendMode::Premultiplied`.
+pub fn mesh_rgba(mesh: Mesh, blend_mode: BlendMode, viewport: Viewport) -> tga::Image<tga::Rgba> {
+    let mut mesh_img: tga::Image<tga::Rgba> = tga::Image::new(viewport.width, viewport.height);
+    let mut z_buffer: Vec<f32> = new_z_buffer(viewport);
+    let translucent: Vec<&Triangle> =
+        shade_opaque_rgba(&mesh, viewport, &mut mesh_img, &mut z_buffer);
+
+    for triangle in sorted_back_to_front(&mesh, translucent) {
+        let material: &Material = &mesh.materials[triangle.material];
+        shade_triangle(
+            &mesh,
+            triangle,
+            viewport,
+            &mut z_buffer,
+            false,
+            |u, v, _, intensity| {
+                let offset: usize = v as usize * viewport.width as usize + u as usize;
+                let colour: tga::Rgba = material_colour(intensity, material);
+                let colour: tga::Rgba = if blend_mode == BlendMode::Premultiplied {
+                    blending::premultiply(colour)
+                } else {
+                    colour
+                };
+                mesh_img.data[offset] = blending::blend(colour, mesh_img.data[offset], blend_mode);
+            },
+        );
+    }
+    return mesh_img;
+}
+
+// Same as `mesh_rgba` with alpha over blending, but translucent fragments are composited in
+// depth order per pixel, which handles intersecting translucent triangles. The fragment lists
+// are limited to `memory_cap` bytes, past which weighted blended OIT is used instead.
+pub fn mesh_rgba_oit(mesh: Mesh, memory_cap: usize, viewport: Viewport) -> tga::Image<tga::Rgba> {
+    let mut mesh_img: tga::Image<tga::Rgba> = tga::Image::new(viewport.width, viewport.height);
+    let mut z_buffer: Vec<f32> = new_z_buffer(viewport);
+    let translucent: Vec<&Triangle> =
+        shade_opaque_rgba(&mesh, viewport, &mut mesh_img, &mut z_buffer);
 
-    let mut vertex_buffer_x = [0u16; 3];
-    let mut vertex_buffer_y = [0u16; 3];
+    let mut a_buffer: oit::ABuffer = oit::ABuffer::new(viewport.width, viewport.height, memory_cap);
+    for triangle in translucent {
+        let material: &Material = &mesh.materials[triangle.material];
+        shade_triangle(
+            &mesh,
+            triangle,
+            viewport,
+            &mut z_buffer,
+            false,
+            |u, v, z, intensity| {
+                let colour: tga::Rgba = material_colour(intensity, material);
+                a_buffer.insert(
+                    u,
+                    v,
+                    oit::Fragment {
+                        colour: tga::Rgb {
+                            b: colour.b,
+                            g: colour.g,
+                            r: colour.r,
+                        },
+                        alpha: material.dissolve,
+                        depth: z,
+                    },
+                );
+            },
+        );
+    }
+    a_buffer.resolve(&mut mesh_img);
+    return mesh_img;
+}
 
-    let mut z_buffer = [f32::NEG_INFINITY; (WIDTH as u32 * HEIGHT as u32) as usize];
+// Draws the opaque triangles and returns the translucent ones, left for a later pass.
+fn shade_opaque_rgba<'a>(
+    mesh: &'a Mesh,
+    viewport: Viewport,
+    mesh_img: &mut tga::Image<tga::Rgba>,
+    z_buffer: &mut [f32],
+) -> Vec<&'a Triangle> {
+    let (translucent, opaque): (Vec<&Triangle>, Vec<&Triangle>) = mesh
+        .triangles
+        .iter()
+        .partition(|triangle| mesh.materials[triangle.material].is_translucent());
 
-    let triangles = &mesh.triangles;
+    for triangle in opaque {
+        let material: &Material = &mesh.materials[triangle.material];
+        shade_triangle(
+            mesh,
+            triangle,
+            viewport,
+            z_buffer,
+            true,
+            |u, v, _, intensity| {
+                let mut colour: tga::Rgba = material_colour(intensity, material);
+                colour.a = 255;
+                let _ = mesh_img.set(u, v, colour);
+            },
+        );
+    }
+    return translucent;
+}
 
-    triangles.into_iter().for_each(|triangle| {
-        raster_triangle(
-            &triangle,
+// Linear radiance render, with the light scaled by `light_intensity` and the surfaces coloured
+// by their material diffuse colour. Every triangle is treated as opaque. Use
+// `tonemap::tone_map` to get a displayable image.
+pub fn mesh_hdr(mesh: Mesh, light_intensity: f32, viewport: Viewport) -> tga::Image<tga::RgbF32> {
+    let mut mesh_img: tga::Image<tga::RgbF32> = tga::Image::new(viewport.width, viewport.height);
+    let mut z_buffer: Vec<f32> = new_z_buffer(viewport);
+    for triangle in &mesh.triangles {
+        let diffuse: Point3D<f32> = mesh.materials[triangle.material].diffuse;
+        shade_triangle(
             &mesh,
-            &mut mesh_img,
-            &mut vertex_buffer_x,
-            &mut vertex_buffer_y,
+            triangle,
+            viewport,
             &mut z_buffer,
-        )
-    });
+            true,
+            |u, v, _, intensity| {
+                let radiance: f32 = intensity.max(0f32) * light_intensity;
+                let colour: tga::RgbF32 = tga::RgbF32 {
+                    r: radiance * diffuse.x,
+                    g: radiance * diffuse.y,
+                    b: radiance * diffuse.z,
+                };
+                let _ = mesh_img.set(u, v, colour);
+            },
+        );
+    }
     return mesh_img;
 }
 
-fn raster_triangle(
-    triangle: &Triangle,
+// Shaded render with the visible triangle edges drawn over it in the given color.
+pub fn mesh_with_wireframe(
+    mesh: Mesh,
+    color: tga::Rgb,
+    viewport: Viewport,
+) -> tga::Image<tga::Rgb> {
+    let mut mesh_img: tga::Image<tga::Rgb> = tga::Image::new(viewport.width, viewport.height);
+    let mut z_buffer: Vec<f32> = new_z_buffer(viewport);
+    shade_mesh(&mesh, viewport, &mut mesh_img, &mut z_buffer);
+    draw_visible_edges(
+        &mesh,
+        Framing::Zoom,
+        viewport,
+        &z_buffer,
+        &mut mesh_img,
+        color,
+    );
+    return mesh_img;
+}
+
+// Wireframe where only the edges that survive the depth test against the rasterised mesh are
+// drawn, in the same framing as `wireframe`.
+pub fn hidden_line(mesh: Mesh, viewport: Viewport) -> tga::Image<tga::Rgb> {
+    const WHITE: tga::Rgb = tga::Rgb {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+
+    let framing: Framing = wireframe_framing(&mesh, viewport);
+    let mut mesh_img: tga::Image<tga::Rgb> = tga::Image::new(viewport.width, viewport.height);
+    let z_buffer: Vec<f32> = depth_pass(&mesh, framing, viewport);
+    draw_visible_edges(&mesh, framing, viewport, &z_buffer, &mut mesh_img, WHITE);
+    return mesh_img;
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Framing {
+    // Fixed zoom around the origin, used by `mesh`.
+    Zoom,
+    // Offset from the lower corner of the box computed by `camera_box`, used by `wireframe`.
+    Camera((f32, f32)),
+}
+
+fn new_z_buffer(viewport: Viewport) -> Vec<f32> {
+    return vec![f32::NEG_INFINITY; viewport.pixel_count()];
+}
+
+fn z_offset(u: u32, v: u32, viewport: Viewport) -> Option<usize> {
+    if u >= viewport.width || v >= viewport.height {
+        return None;
+    }
+    return Some(v as usize * viewport.width as usize + u as usize);
+}
+
+fn shade_mesh(
     mesh: &Mesh,
+    viewport: Viewport,
     mesh_img: &mut tga::Image<tga::Rgb>,
-    vertex_buffer_x: &mut [u16; 3],
-    vertex_buffer_y: &mut [u16; 3],
     z_buffer: &mut [f32],
+) {
+    for triangle in &mesh.triangles {
+        shade_triangle(
+            mesh,
+            triangle,
+            viewport,
+            z_buffer,
+            true,
+            |u, v, _, intensity| {
+                let _ = mesh_img.set(u, v, grey_shade(intensity));
+            },
+        );
+    }
+}
+
+// Rasterises a front facing triangle in the `mesh` framing, calling `on_fragment` with the
+// position, depth and diffuse light intensity of every fragment passing the depth test.
+fn shade_triangle<F: FnMut(u32, u32, f32, f32)>(
+    mesh: &Mesh,
+    triangle: &Triangle,
+    viewport: Viewport,
+    z_buffer: &mut [f32],
+    depth_write: bool,
+    mut on_fragment: F,
 ) {
     if mesh.triangle_is_backface(triangle, LIGHT_DIRECTION) {
         return;
     }
-    project_vertices_in_screen_space(mesh, triangle, vertex_buffer_x, vertex_buffer_y);
+    let mut vertex_buffer_x = [0u32; 3];
+    let mut vertex_buffer_y = [0u32; 3];
+    project_vertices_in_screen_space(
+        mesh,
+        triangle,
+        Framing::Zoom,
+        viewport,
+        &mut vertex_buffer_x,
+        &mut vertex_buffer_y,
+    );
+
+    // Files without normals leave out of range indices, shaded with the face normal.
+    let face_normal: Point3D<f32> = mesh.triangle_normal(triangle);
+    let normal = |n: usize| -> Point3D<f32> {
+        return if n < mesh.v_normals.xs.len() {
+            mesh.v_normals.get_at(n)
+        } else {
+            face_normal
+        };
+    };
+    let vertex0_normal = normal(triangle.normals[0]);
+    let vertex1_normal = normal(triangle.normals[1]);
+    let vertex2_normal = normal(triangle.normals[2]);
+
+    raster_triangle(
+        &vertex_buffer_x,
+        &vertex_buffer_y,
+        triangle.vertices.map(|vertex| mesh.v_positions.zs[vertex]),
+        viewport,
+        z_buffer,
+        depth_write,
+        |u, v, z, barycentric_coords| {
+            let intensity = get_pixel_intensity(
+                barycentric_coords,
+                &vertex0_normal,
+                &vertex1_normal,
+                &vertex2_normal,
+            );
+            on_fragment(u, v, z, intensity);
+        },
+    );
+}
+
+fn depth_pass(mesh: &Mesh, framing: Framing, viewport: Viewport) -> Vec<f32> {
+    let mut z_buffer: Vec<f32> = new_z_buffer(viewport);
+    let mut vertex_buffer_x = [0u32; 3];
+    let mut vertex_buffer_y = [0u32; 3];
+
+    for triangle in &mesh.triangles {
+        if mesh.triangle_is_backface(triangle, LIGHT_DIRECTION) {
+            continue;
+        }
+        project_vertices_in_screen_space(
+            mesh,
+            triangle,
+            framing,
+            viewport,
+            &mut vertex_buffer_x,
+            &mut vertex_buffer_y,
+        );
+        raster_triangle(
+            &vertex_buffer_x,
+            &vertex_buffer_y,
+            triangle.vertices.map(|vertex| mesh.v_positions.zs[vertex]),
+            viewport,
+            &mut z_buffer,
+            true,
+            |_, _, _, _| {},
+        );
+    }
+    return z_buffer;
+}
 
+// Rasterises the projected triangle, calling `on_fragment` for every covered pixel that passes
+// the depth test, after the z-buffer has been updated when `depth_write` is set.
+fn raster_triangle<F: FnMut(u32, u32, f32, &Point3D<f32>)>(
+    vertex_buffer_x: &[u32; 3],
+    vertex_buffer_y: &[u32; 3],
+    vertex_z: [f32; 3],
+    viewport: Viewport,
+    z_buffer: &mut [f32],
+    depth_write: bool,
+    mut on_fragment: F,
+) {
     let tga_bounding_box: bounding_box::BoundingBox2D =
-        bounding_box::BoundingBox2D::get_bounding_box(&vertex_buffer_x, &vertex_buffer_y);
+        bounding_box::BoundingBox2D::get_bounding_box(vertex_buffer_x, vertex_buffer_y);
 
-    let vertex0_normal = mesh.v_normals.get_at(triangle.normals[0]);
-    let vertex1_normal = mesh.v_normals.get_at(triangle.normals[1]);
-    let vertex2_normal = mesh.v_normals.get_at(triangle.normals[2]);
-    let vertex0_z = mesh.v_positions.zs[triangle.vertices[0]];
-    let vertex1_z = mesh.v_positions.zs[triangle.vertices[1]];
-    let vertex2_z = mesh.v_positions.zs[triangle.vertices[2]];
+    let [vertex0_z, vertex1_z, vertex2_z] = vertex_z;
 
     for u in tga_bounding_box.min_u..=tga_bounding_box.max_u {
         for v in tga_bounding_box.min_v..=tga_bounding_box.max_v {
@@ -108,24 +452,23 @@ fn raster_triangle(
                 (vertex_buffer_x[1], vertex_buffer_y[1]),
                 (vertex_buffer_x[2], vertex_buffer_y[2]),
             );
-
-            let colour = get_pixel_colour(
-                &barycentric_coords,
-                &vertex0_normal,
-                &vertex1_normal,
-                &vertex2_normal,
-            );
+            if !linear_algebra::point_is_in_rast_triangle(&barycentric_coords) {
+                continue;
+            }
 
             let z = barycentric_coords.x * vertex0_z
                 + barycentric_coords.y * vertex1_z
                 + barycentric_coords.z * vertex2_z;
-            let z_offset: usize = (v as u32 * WIDTH as u32 + u as u32) as usize;
+            let z_offset: usize = match z_offset(u, v, viewport) {
+                Some(z_offset) => z_offset,
+                None => continue,
+            };
 
-            if z_buffer[z_offset] < z
-                && linear_algebra::point_is_in_rast_triangle(&barycentric_coords)
-            {
-                let _ = mesh_img.set(u, v, colour);
-                z_buffer[z_offset] = z;
+            if z_buffer[z_offset] < z {
+                if depth_write {
+                    z_buffer[z_offset] = z;
+                }
+                on_fragment(u, v, z, &barycentric_coords);
             }
         }
     }
@@ -134,24 +477,52 @@ fn raster_triangle(
 fn project_vertices_in_screen_space(
     mesh: &Mesh,
     triangle: &Triangle,
-    vertex_buffer_x: &mut [u16; 3],
-    vertex_buffer_y: &mut [u16; 3],
+    framing: Framing,
+    viewport: Viewport,
+    vertex_buffer_x: &mut [u32; 3],
+    vertex_buffer_y: &mut [u32; 3],
 ) {
     for i in 0..3usize {
         let proj = mesh
             .v_positions
             .get_at_orthonormal_projection(triangle.vertices[i]);
-        vertex_buffer_x[i] = (WIDTH_RENORMALISATION * (proj.x + ZOOM * 16f32)).floor() as u16;
-        vertex_buffer_y[i] = (HEIGHT_RENORMALISATION * (proj.y + ZOOM * 9f32)).floor() as u16;
+        (vertex_buffer_x[i], vertex_buffer_y[i]) =
+            project_in_screen_space(proj.x, proj.y, framing, viewport);
     }
 }
 
-fn get_pixel_colour(
+fn project_in_screen_space(x: f32, y: f32, framing: Framing, viewport: Viewport) -> (u32, u32) {
+    let proj = project_in_continuous_screen_space(x, y, framing, viewport);
+    return (proj.x.floor() as u32, proj.y.floor() as u32);
+}
+
+// Screen space position in pixels, before snapping to the pixel grid.
+fn project_in_continuous_screen_space(
+    x: f32,
+    y: f32,
+    framing: Framing,
+    viewport: Viewport,
+) -> Point2D<f32> {
+    let (offset_x, offset_y): (f32, f32) = match framing {
+        Framing::Zoom => {
+            let (extent_x, extent_y) = viewport.zoom_extent();
+            (extent_x / 2f32, extent_y / 2f32)
+        }
+        Framing::Camera((min_x, min_y)) => (-min_x, -min_y),
+    };
+    let (renormalisation_x, renormalisation_y) = viewport.renormalisation();
+    return Point2D {
+        x: renormalisation_x * (x + offset_x),
+        y: renormalisation_y * (y + offset_y),
+    };
+}
+
+fn get_pixel_intensity(
     barycentric_coords: &Point3D<f32>,
     vertex0_normal: &Point3D<f32>,
     vertex1_normal: &Point3D<f32>,
     vertex2_normal: &Point3D<f32>,
-) -> tga::Rgb {
+) -> f32 {
     let normal_at_point = linear_algebra::barycentric_interpolation(
         barycentric_coords,
         vertex0_normal,
@@ -159,8 +530,34 @@ fn get_pixel_colour(
         vertex2_normal,
     );
 
-    let intensity = -linear_algebra::dot_product(normal_at_point, LIGHT_DIRECTION);
+    return -linear_algebra::dot_product(normal_at_point, LIGHT_DIRECTION);
+}
+
+fn material_colour(intensity: f32, material: &Material) -> tga::Rgba {
+    let channel = |diffuse: f32| -> u8 { (intensity * diffuse * 255f32).clamp(0f32, 255f32) as u8 };
+    return tga::Rgba {
+        b: channel(material.diffuse.z),
+        g: channel(material.diffuse.y),
+        r: channel(material.diffuse.x),
+        a: (material.dissolve * 255f32).clamp(0f32, 255f32) as u8,
+    };
+}
+
+// Furthest triangles first, ordered by the depth of their centroid.
+fn sorted_back_to_front<'a>(mesh: &Mesh, mut triangles: Vec<&'a Triangle>) -> Vec<&'a Triangle> {
+    let centroid_z = |triangle: &Triangle| -> f32 {
+        return triangle
+            .vertices
+            .iter()
+            .map(|&vertex| mesh.v_positions.zs[vertex])
+            .sum::<f32>()
+            / 3f32;
+    };
+    triangles.sort_by(|a, b| centroid_z(a).total_cmp(&centroid_z(b)));
+    return triangles;
+}
 
+fn grey_shade(intensity: f32) -> tga::Rgb {
     let shade: u8 = (intensity * 255f32).max(0f32) as u8;
     let colour = tga::Rgb {
         r: shade,
@@ -170,33 +567,29 @@ fn get_pixel_colour(
     return colour;
 }
 
-pub fn wireframe(mesh: Mesh) -> tga::Image<tga::Rgb> {
+pub fn wireframe(mesh: Mesh, viewport: Viewport) -> tga::Image<tga::Rgb> {
     const WHITE: tga::Rgb = tga::Rgb {
         r: 255,
         g: 255,
         b: 255,
     };
 
-    let mut bounding_box: bounding_box::BoundingBox3D = mesh.bounding_box();
-    bounding_box.pad(10f32);
-    let (camera_min, _) = camera_box(&bounding_box);
+    let framing: Framing = wireframe_framing(&mesh, viewport);
 
-    let mut mesh_img: tga::Image<tga::Rgb> = tga::Image::new(WIDTH, HEIGHT);
+    let mut mesh_img: tga::Image<tga::Rgb> = tga::Image::new(viewport.width, viewport.height);
 
-    let mut vertex_buffer_x = [0u16; 3];
-    let mut vertex_buffer_y = [0u16; 3];
+    let mut vertex_buffer_x = [0u32; 3];
+    let mut vertex_buffer_y = [0u32; 3];
 
-    let min_x: f32 = camera_min.0;
-    let min_y: f32 = camera_min.1;
-    mesh.triangles.into_iter().for_each(|triangle| {
-        for i in 0..3usize {
-            vertex_buffer_x[i] = (WIDTH_RENORMALISATION
-                * (mesh.v_positions.xs[triangle.vertices[i]] - min_x))
-                .floor() as u16;
-            vertex_buffer_y[i] = (HEIGHT_RENORMALISATION
-                * (mesh.v_positions.ys[triangle.vertices[i]] - min_y))
-                .floor() as u16;
-        }
+    mesh.triangles.iter().for_each(|triangle| {
+        project_vertices_in_screen_space(
+            &mesh,
+            triangle,
+            framing,
+            viewport,
+            &mut vertex_buffer_x,
+            &mut vertex_buffer_y,
+        );
         for i in 0..3usize {
             line(
                 vertex_buffer_x[i],
@@ -212,10 +605,378 @@ pub fn wireframe(mesh: Mesh) -> tga::Image<tga::Rgb> {
     return mesh_img;
 }
 
-fn camera_box(bounding_box: &bounding_box::BoundingBox3D) -> ((f32, f32), (f32, f32)) {
+#[derive(Copy, Clone, Debug)]
+pub struct EdgeStyle {
+    pub color: tga::Rgb,
+    pub width: u32,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct FeatureLineStyle {
+    pub silhouette: EdgeStyle,
+    pub boundary: EdgeStyle,
+    pub crease: EdgeStyle,
+    pub material_border: EdgeStyle,
+    // Dihedral angle, in degrees, above which an edge counts as a crease.
+    pub crease_angle: f32,
+}
+
+impl Default for FeatureLineStyle {
+    fn default() -> Self {
+        const WHITE: tga::Rgb = tga::Rgb {
+            r: 255,
+            g: 255,
+            b: 255,
+        };
+        FeatureLineStyle {
+            silhouette: EdgeStyle {
+                color: WHITE,
+                width: 3,
+            },
+            boundary: EdgeStyle {
+                color: WHITE,
+                width: 2,
+            },
+            crease: EdgeStyle {
+                color: tga::Rgb {
+                    r: 160,
+                    g: 160,
+                    b: 160,
+                },
+                width: 1,
+            },
+            material_border: EdgeStyle {
+                color: tga::Rgb {
+                    r: 64,
+                    g: 128,
+                    b: 255,
+                },
+                width: 1,
+            },
+            crease_angle: 40f32,
+        }
+    }
+}
+
+// Line drawing of the silhouette, boundary, crease and material border edges of the mesh, in the
+// same framing as `wireframe`.
+pub fn feature_lines(
+    mesh: Mesh,
+    style: &FeatureLineStyle,
+    viewport: Viewport,
+) -> tga::Image<tga::Rgb> {
+    let framing: Framing = wireframe_framing(&mesh, viewport);
+    let mut mesh_img: tga::Image<tga::Rgb> = tga::Image::new(viewport.width, viewport.height);
+
+    let mut feature_edges: Vec<FeatureEdge> =
+        mesh.feature_edges(LIGHT_DIRECTION, style.crease_angle);
+    // Most important classes are drawn last so that they end up on top.
+    feature_edges.sort_by_key(|edge| match edge.class {
+        EdgeClass::MaterialBorder => 0,
+        EdgeClass::Crease => 1,
+        EdgeClass::Boundary => 2,
+        EdgeClass::Silhouette => 3,
+    });
+
+    for edge in feature_edges {
+        let edge_style: EdgeStyle = match edge.class {
+            EdgeClass::Silhouette => style.silhouette,
+            EdgeClass::Boundary => style.boundary,
+            EdgeClass::Crease => style.crease,
+            EdgeClass::MaterialBorder => style.material_border,
+        };
+        let start = mesh
+            .v_positions
+            .get_at_orthonormal_projection(edge.vertices[0]);
+        let end = mesh
+            .v_positions
+            .get_at_orthonormal_projection(edge.vertices[1]);
+        let (x0, y0) = project_in_screen_space(start.x, start.y, framing, viewport);
+        let (x1, y1) = project_in_screen_space(end.x, end.y, framing, viewport);
+        thick_line(x0, y0, x1, y1, edge_style, &mut mesh_img);
+    }
+    return mesh_img;
+}
+
+// Draws `width` parallel copies of the segment as `line` would, offset one pixel apart along the
+// segment's minor axis.
+fn thick_line(
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+    style: EdgeStyle,
+    img: &mut tga::Image<tga::Rgb>,
+) {
+    let offset_along_y: bool = x0.abs_diff(x1) >= y0.abs_diff(y1);
+    let first_offset: i32 = -((style.width.max(1) as i32 - 1) / 2);
+    for k in 0..style.width.max(1) as i32 {
+        let offset: i64 = (first_offset + k) as i64;
+        // Copies crossing the image border are clipped to it pixel by pixel.
+        let shift: (i64, i64) = if offset_along_y {
+            (0, offset)
+        } else {
+            (offset, 0)
+        };
+        shifted_line(x0, y0, x1, y1, shift, img, style.color);
+    }
+}
+
+fn wireframe_framing(mesh: &Mesh, viewport: Viewport) -> Framing {
+    let mut bounding_box: bounding_box::BoundingBox3D = mesh.bounding_box();
+    bounding_box.pad(10f32);
+    let (camera_min, _) = camera_box(&bounding_box, viewport);
+    return Framing::Camera(camera_min);
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Segment2D {
+    pub start: Point2D<f32>,
+    pub end: Point2D<f32>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct FlatPolygon {
+    pub vertices: [Point2D<f32>; 3],
+    pub color: tga::Rgb,
+}
+
+// Every mesh edge once, in the pixel space of `wireframe`.
+pub fn wireframe_segments(mesh: &Mesh, viewport: Viewport) -> Vec<Segment2D> {
+    return screen_edges(mesh, wireframe_framing(mesh, viewport), viewport, false)
+        .iter()
+        .map(|edge| Segment2D {
+            start: Point2D {
+                x: edge.start.x,
+                y: edge.start.y,
+            },
+            end: Point2D {
+                x: edge.end.x,
+                y: edge.end.y,
+            },
+        })
+        .collect();
+}
+
+// The visible parts of the edges drawn by `hidden_line`, in its pixel space. Partially hidden
+// edges are split into one segment per visible run of pixels.
+pub fn hidden_line_segments(mesh: &Mesh, viewport: Viewport) -> Vec<Segment2D> {
+    let framing: Framing = wireframe_framing(mesh, viewport);
+    let z_buffer: Vec<f32> = depth_pass(mesh, framing, viewport);
+    let mut segments: Vec<Segment2D> = Vec::new();
+
+    for edge in screen_edges(mesh, framing, viewport, true) {
+        let pixels: Vec<(u32, u32, bool)> = depth_tested_pixels(&edge, viewport, &z_buffer);
+        // Edges entirely outside the image have no pixels, and nothing visible.
+        if pixels.is_empty() {
+            continue;
+        }
+        if pixels.iter().all(|pixel| pixel.2) {
+            segments.push(Segment2D {
+                start: Point2D {
+                    x: edge.start.x,
+                    y: edge.start.y,
+                },
+                end: Point2D {
+                    x: edge.end.x,
+                    y: edge.end.y,
+                },
+            });
+            continue;
+        }
+        // Half a pixel along the edge, which runs of a single pixel extend to either side so as
+        // not to vanish.
+        let (length_x, length_y) = (edge.end.x - edge.start.x, edge.end.y - edge.start.y);
+        let length: f32 = (length_x * length_x + length_y * length_y)
+            .sqrt()
+            .max(f32::EPSILON);
+        let half_step: (f32, f32) = (0.5f32 * length_x / length, 0.5f32 * length_y / length);
+        for run in pixels.split(|pixel| !pixel.2) {
+            if run.is_empty() {
+                continue;
+            }
+            let (first, last) = (run[0], run[run.len() - 1]);
+            let reach: (f32, f32) = if run.len() == 1 {
+                half_step
+            } else {
+                (0f32, 0f32)
+            };
+            segments.push(Segment2D {
+                start: Point2D {
+                    x: first.0 as f32 + 0.5f32 - reach.0,
+                    y: first.1 as f32 + 0.5f32 - reach.1,
+                },
+                end: Point2D {
+                    x: last.0 as f32 + 0.5f32 + reach.0,
+                    y: last.1 as f32 + 0.5f32 + reach.1,
+                },
+            });
+        }
+    }
+    return segments;
+}
+
+// Front facing triangles flat shaded and projected in the pixel space of `wireframe`, sorted from
+// the furthest to the nearest so that painting them in order resolves visibility.
+pub fn flat_shaded_polygons(mesh: &Mesh, viewport: Viewport) -> Vec<FlatPolygon> {
+    let framing: Framing = wireframe_framing(mesh, viewport);
+    let mut polygons: Vec<(f32, FlatPolygon)> = Vec::new();
+
+    for triangle in &mesh.triangles {
+        if mesh.triangle_is_backface(triangle, LIGHT_DIRECTION) {
+            continue;
+        }
+        let intensity: f32 =
+            -linear_algebra::dot_product(mesh.triangle_normal(triangle), LIGHT_DIRECTION);
+        let shade: u8 = (intensity * 255f32).max(0f32) as u8;
+        let mut vertices = [Point2D { x: 0f32, y: 0f32 }; 3];
+        let mut centroid_z: f32 = 0f32;
+        for (vertex, &vertex_index) in vertices.iter_mut().zip(&triangle.vertices) {
+            let p = mesh.v_positions.get_at(vertex_index);
+            *vertex = project_in_continuous_screen_space(p.x, p.y, framing, viewport);
+            centroid_z += p.z / 3f32;
+        }
+        polygons.push((
+            centroid_z,
+            FlatPolygon {
+                vertices,
+                color: tga::Rgb {
+                    r: shade,
+                    g: shade,
+                    b: shade,
+                },
+            },
+        ));
+    }
+    polygons.sort_by(|a, b| a.0.total_cmp(&b.0));
+    return polygons.into_iter().map(|(_, polygon)| polygon).collect();
+}
+
+// Projected edge, with x and y in continuous screen space and z the model depth.
+#[derive(Copy, Clone, Debug)]
+struct ScreenEdge {
+    start: Point3D<f32>,
+    end: Point3D<f32>,
+}
+
+// Edges of the mesh triangles, each shared edge being listed once.
+fn screen_edges(
+    mesh: &Mesh,
+    framing: Framing,
+    viewport: Viewport,
+    cull_backfaces: bool,
+) -> Vec<ScreenEdge> {
+    let mut seen: HashSet<(usize, usize)> = HashSet::new();
+    let mut edges: Vec<ScreenEdge> = Vec::new();
+
+    for triangle in &mesh.triangles {
+        if cull_backfaces && mesh.triangle_is_backface(triangle, LIGHT_DIRECTION) {
+            continue;
+        }
+        for i in 0..3usize {
+            let a: usize = triangle.vertices[i];
+            let b: usize = triangle.vertices[(i + 1).rem_euclid(3)];
+            if !seen.insert((a.min(b), a.max(b))) {
+                continue;
+            }
+            let project = |vertex: usize| -> Point3D<f32> {
+                let p = mesh.v_positions.get_at(vertex);
+                let proj = project_in_continuous_screen_space(p.x, p.y, framing, viewport);
+                Point3D {
+                    x: proj.x,
+                    y: proj.y,
+                    z: p.z,
+                }
+            };
+            edges.push(ScreenEdge {
+                start: project(a),
+                end: project(b),
+            });
+        }
+    }
+    return edges;
+}
+
+// Draws the edges of every front facing triangle, keeping only the pixels that are no further
+// than DEPTH_OFFSET behind the z-buffer so that edges lying on the surface are not z-fighting it.
+fn draw_visible_edges(
+    mesh: &Mesh,
+    framing: Framing,
+    viewport: Viewport,
+    z_buffer: &[f32],
+    img: &mut tga::Image<tga::Rgb>,
+    color: tga::Rgb,
+) {
+    for edge in screen_edges(mesh, framing, viewport, true) {
+        for (u, v, visible) in depth_tested_pixels(&edge, viewport, z_buffer) {
+            if visible {
+                let _ = img.set(u, v, color);
+            }
+        }
+    }
+}
+
+// Same walk as `line`, with the depth linearly interpolated along the segment. Pixels falling
+// outside the image are skipped.
+fn depth_tested_pixels(
+    edge: &ScreenEdge,
+    viewport: Viewport,
+    z_buffer: &[f32],
+) -> Vec<(u32, u32, bool)> {
+    let (mut start_x, mut start_y, mut start_z) = (
+        edge.start.x.floor() as u32,
+        edge.start.y.floor() as u32,
+        edge.start.z,
+    );
+    let (mut end_x, mut end_y, mut end_z) = (
+        edge.end.x.floor() as u32,
+        edge.end.y.floor() as u32,
+        edge.end.z,
+    );
+
+    let mut transposed: bool = false;
+    if start_x.abs_diff(end_x) < start_y.abs_diff(end_y) {
+        (start_x, start_y) = (start_y, start_x);
+        (end_x, end_y) = (end_y, end_x);
+        transposed = true;
+    }
+    if start_x > end_x {
+        (start_x, end_x) = (end_x, start_x);
+        (start_y, end_y) = (end_y, start_y);
+        (start_z, end_z) = (end_z, start_z);
+    }
+    let dx: i64 = (end_x - start_x) as i64;
+    let derror2: i64 = end_y.abs_diff(start_y) as i64 * 2;
+    let mut error2: i64 = 0i64;
+    let mut y: u32 = start_y;
+    let mut pixels: Vec<(u32, u32, bool)> = Vec::with_capacity(dx as usize);
+    for x in start_x..end_x {
+        let t: f32 = (x - start_x) as f32 / dx as f32;
+        let z: f32 = start_z + t * (end_z - start_z);
+        let (u, v) = if transposed { (y, x) } else { (x, y) };
+        if let Some(z_offset) = z_offset(u, v, viewport) {
+            pixels.push((u, v, z + DEPTH_OFFSET >= z_buffer[z_offset]));
+        }
+        error2 += derror2;
+        if error2 > dx {
+            if end_y > start_y {
+                y += 1;
+            } else {
+                y -= 1;
+            }
+            error2 -= dx * 2;
+        }
+    }
+    return pixels;
+}
+
+fn camera_box(
+    bounding_box: &bounding_box::BoundingBox3D,
+    viewport: Viewport,
+) -> ((f32, f32), (f32, f32)) {
     let mut width: f32 = bounding_box.max_x - bounding_box.min_x;
     let mut height: f32 = bounding_box.max_y - bounding_box.min_y;
-    let ratio: f32 = (WIDTH as f32) / (HEIGHT as f32);
+    let ratio: f32 = (viewport.width as f32) / (viewport.height as f32);
     let mesh_is_wide: bool = width / (height * ratio) > 1f32;
 
     if mesh_is_wide {
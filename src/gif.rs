@@ -0,0 +1,475 @@
+use crate::tga;
+use std::collections::HashMap;
+use std::io::{self, prelude::*};
+
+// Animated GIF89a writing. Frames are reduced to at most 256 colours with median cut or octree
+// quantisation, optionally with Floyd-Steinberg dithering, then LZW compressed.
+
+const MAX_COLOURS: usize = 256;
+const MAX_CODE: u16 = 4096;
+const MAX_SUB_BLOCK: usize = 255;
+const OCTREE_DEPTH: usize = 6;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Quantizer {
+    MedianCut,
+    Octree,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct GifOptions {
+    pub quantizer: Quantizer,
+    // One palette built from every frame, instead of one per frame.
+    pub global_palette: bool,
+    pub dither: bool,
+    // None plays the animation once, Some(0) loops forever.
+    pub loop_count: Option<u16>,
+}
+
+impl Default for GifOptions {
+    fn default() -> Self {
+        GifOptions {
+            quantizer: Quantizer::MedianCut,
+            global_palette: false,
+            dither: true,
+            loop_count: Some(0),
+        }
+    }
+}
+
+pub struct Frame<'a> {
+    pub image: &'a tga::Image<tga::Rgb>,
+    // Display time in hundredths of a second.
+    pub delay: u16,
+}
+
+// All frames must have the dimensions of the first one. With `vflip`, the first image row is the
+// bottom of the picture, as in the drawers output.
+pub fn write<W: io::Write>(
+    writer: &mut io::BufWriter<W>,
+    frames: &[Frame],
+    options: &GifOptions,
+    vflip: bool,
+) -> io::Result<()> {
+    let first: &tga::Image<tga::Rgb> = match frames.first() {
+        Some(frame) => frame.image,
+        None => return Err(invalid_input("a GIF needs at least one frame")),
+    };
+    if frames.iter().any(|frame| {
+        frame.image.width != first.width
+            || frame.image.height != first.height
+            || frame.image.width > u16::MAX as u32
+            || frame.image.height > u16::MAX as u32
+    }) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            tga::Error::InvalidDimensions.to_string(),
+        ));
+    }
+
+    let global_palette: Option<Vec<[u8; 3]>> = if options.global_palette {
+        let histogram: HashMap<[u8; 3], u32> =
+            colour_histogram(frames.iter().map(|frame| frame.image));
+        Some(build_palette(&histogram, options.quantizer))
+    } else {
+        None
+    };
+    let (width, height) = (first.width as u16, first.height as u16);
+
+    writer.write_all(b"GIF89a")?;
+    writer.write_all(&width.to_le_bytes())?;
+    writer.write_all(&height.to_le_bytes())?;
+    match &global_palette {
+        Some(palette) => {
+            let bits: u8 = palette_bits(palette.len());
+            writer.write_all(&[0x80 | 0x70 | (bits - 1), 0, 0])?;
+            write_palette(writer, palette, bits)?;
+        }
+        None => writer.write_all(&[0x70, 0, 0])?,
+    }
+    if let Some(loop_count) = options.loop_count {
+        writer.write_all(&[0x21, 0xff, 0x0b])?;
+        writer.write_all(b"NETSCAPE2.0")?;
+        writer.write_all(&[0x03, 0x01])?;
+        writer.write_all(&loop_count.to_le_bytes())?;
+        writer.write_all(&[0x00])?;
+    }
+
+    for frame in frames {
+        let local_palette: Option<Vec<[u8; 3]>> = match global_palette {
+            Some(_) => None,
+            None => Some(build_palette(
+                &colour_histogram([frame.image].into_iter()),
+                options.quantizer,
+            )),
+        };
+        let palette: &[[u8; 3]] = local_palette
+            .as_deref()
+            .or(global_palette.as_deref())
+            .unwrap_or_default();
+        let bits: u8 = palette_bits(palette.len());
+
+        // Graphic control extension, carrying the frame delay.
+        writer.write_all(&[0x21, 0xf9, 0x04, 0x00])?;
+        writer.write_all(&frame.delay.to_le_bytes())?;
+        writer.write_all(&[0x00, 0x00])?;
+
+        writer.write_all(&[0x2c, 0, 0, 0, 0])?;
+        writer.write_all(&width.to_le_bytes())?;
+        writer.write_all(&height.to_le_bytes())?;
+        if local_palette.is_some() {
+            writer.write_all(&[0x80 | (bits - 1)])?;
+            write_palette(writer, palette, bits)?;
+        } else {
+            writer.write_all(&[0x00])?;
+        }
+
+        let indices: Vec<u8> = map_to_palette(frame.image, palette, options.dither, vflip);
+        let min_code_size: u8 = bits.max(2);
+        writer.write_all(&[min_code_size])?;
+        for sub_block in lzw_compress(&indices, min_code_size).chunks(MAX_SUB_BLOCK) {
+            writer.write_all(&[sub_block.len() as u8])?;
+            writer.write_all(sub_block)?;
+        }
+        writer.write_all(&[0x00])?;
+    }
+
+    writer.write_all(&[0x3b])?;
+    writer.flush()?;
+    Ok(())
+}
+
+// Number of bits needed to index the palette, at least 1.
+fn palette_bits(colours: usize) -> u8 {
+    let mut bits: u8 = 1;
+    while (1usize << bits) < colours {
+        bits += 1;
+    }
+    return bits;
+}
+
+// The colour table is padded with black up to its power of two size.
+fn write_palette<W: io::Write>(
+    writer: &mut io::BufWriter<W>,
+    palette: &[[u8; 3]],
+    bits: u8,
+) -> io::Result<()> {
+    for i in 0..1usize << bits {
+        writer.write_all(&palette.get(i).copied().unwrap_or([0u8; 3]))?;
+    }
+    Ok(())
+}
+
+fn colour_histogram<'a, I: Iterator<Item = &'a tga::Image<tga::Rgb>>>(
+    images: I,
+) -> HashMap<[u8; 3], u32> {
+    let mut histogram: HashMap<[u8; 3], u32> = HashMap::new();
+    for image in images {
+        for pixel in &image.data {
+            *histogram.entry([pixel.r, pixel.g, pixel.b]).or_insert(0) += 1;
+        }
+    }
+    return histogram;
+}
+
+fn build_palette(histogram: &HashMap<[u8; 3], u32>, quantizer: Quantizer) -> Vec<[u8; 3]> {
+    if histogram.len() <= MAX_COLOURS {
+        let mut palette: Vec<[u8; 3]> = histogram.keys().copied().collect();
+        palette.sort();
+        return palette;
+    }
+    return match quantizer {
+        Quantizer::MedianCut => median_cut(histogram),
+        Quantizer::Octree => octree(histogram),
+    };
+}
+
+// Repeatedly splits the colour box with the widest channel range at the pixel count median of
+// that channel, each final box contributing its weighted mean colour.
+fn median_cut(histogram: &HashMap<[u8; 3], u32>) -> Vec<[u8; 3]> {
+    let mut colours: Vec<([u8; 3], u32)> = histogram
+        .iter()
+        .map(|(&colour, &count)| (colour, count))
+        .collect();
+    colours.sort();
+    let mut boxes: Vec<Vec<([u8; 3], u32)>> = vec![colours];
+
+    while boxes.len() < MAX_COLOURS {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colour_box)| colour_box.len() > 1)
+            .map(|(i, colour_box)| {
+                let (channel, range) = widest_channel(colour_box);
+                (i, channel, range)
+            })
+            .max_by_key(|&(_, _, range)| range);
+        let (box_index, channel) = match widest {
+            Some((box_index, channel, _)) => (box_index, channel),
+            None => break,
+        };
+
+        let mut colour_box: Vec<([u8; 3], u32)> = boxes.swap_remove(box_index);
+        colour_box.sort_by_key(|&(colour, _)| colour[channel]);
+        let total: u64 = colour_box.iter().map(|&(_, count)| count as u64).sum();
+        let mut accumulated: u64 = 0;
+        let mut split: usize = 1;
+        for (i, &(_, count)) in colour_box.iter().enumerate() {
+            accumulated += count as u64;
+            if accumulated * 2 >= total {
+                split = (i + 1).clamp(1, colour_box.len() - 1);
+                break;
+            }
+        }
+        let upper: Vec<([u8; 3], u32)> = colour_box.split_off(split);
+        boxes.push(colour_box);
+        boxes.push(upper);
+    }
+
+    return boxes
+        .iter()
+        .map(|colour_box| {
+            let mut sums = [0u64; 3];
+            let mut total: u64 = 0;
+            for &(colour, count) in colour_box {
+                for c in 0..3usize {
+                    sums[c] += colour[c] as u64 * count as u64;
+                }
+                total += count as u64;
+            }
+            sums.map(|sum| (sum / total.max(1)) as u8)
+        })
+        .collect();
+}
+
+fn widest_channel(colours: &[([u8; 3], u32)]) -> (usize, u8) {
+    let mut best: (usize, u8) = (0, 0);
+    for channel in 0..3usize {
+        let min: u8 = colours
+            .iter()
+            .map(|(colour, _)| colour[channel])
+            .min()
+            .unwrap_or(0);
+        let max: u8 = colours
+            .iter()
+            .map(|(colour, _)| colour[channel])
+            .max()
+            .unwrap_or(0);
+        if max - min > best.1 {
+            best = (channel, max - min);
+        }
+    }
+    return best;
+}
+
+struct OctreeNode {
+    children: [Option<usize>; 8],
+    sums: [u64; 3],
+    count: u64,
+    is_leaf: bool,
+}
+
+// Builds an octree of the colours, then folds the least populated nodes of the deepest level
+// into their parent until at most MAX_COLOURS leaves remain.
+fn octree(histogram: &HashMap<[u8; 3], u32>) -> Vec<[u8; 3]> {
+    let mut nodes: Vec<OctreeNode> = vec![new_octree_node(false)];
+    let mut levels: Vec<Vec<usize>> = vec![Vec::new(); OCTREE_DEPTH];
+    levels[0].push(0);
+
+    for (&colour, &count) in histogram {
+        let mut node: usize = 0;
+        for level in 0..OCTREE_DEPTH {
+            nodes[node].sums =
+                [0usize, 1, 2].map(|c| nodes[node].sums[c] + colour[c] as u64 * count as u64);
+            nodes[node].count += count as u64;
+            let shift: usize = 7 - level;
+            let child_index: usize = (((colour[0] >> shift) & 1) << 2
+                | ((colour[1] >> shift) & 1) << 1
+                | ((colour[2] >> shift) & 1)) as usize;
+            node = match nodes[node].children[child_index] {
+                Some(child) => child,
+                None => {
+                    let is_leaf: bool = level + 1 == OCTREE_DEPTH;
+                    nodes.push(new_octree_node(is_leaf));
+                    let child: usize = nodes.len() - 1;
+                    nodes[node].children[child_index] = Some(child);
+                    if !is_leaf {
+                        levels[level + 1].push(child);
+                    }
+                    child
+                }
+            };
+        }
+        nodes[node].sums =
+            [0usize, 1, 2].map(|c| nodes[node].sums[c] + colour[c] as u64 * count as u64);
+        nodes[node].count += count as u64;
+    }
+
+    let mut leaf_count: usize = nodes.iter().filter(|node| node.is_leaf).count();
+    for level in (0..OCTREE_DEPTH).rev() {
+        if leaf_count <= MAX_COLOURS {
+            break;
+        }
+        let mut candidates: Vec<usize> = levels[level].clone();
+        candidates.sort_by_key(|&node| nodes[node].count);
+        for node in candidates {
+            if leaf_count <= MAX_COLOURS {
+                break;
+            }
+            let children: usize = nodes[node].children.iter().flatten().count();
+            nodes[node].children = [None; 8];
+            nodes[node].is_leaf = true;
+            leaf_count = leaf_count + 1 - children;
+        }
+    }
+
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    let mut stack: Vec<usize> = vec![0];
+    while let Some(node) = stack.pop() {
+        if nodes[node].is_leaf {
+            let count: u64 = nodes[node].count.max(1);
+            palette.push(nodes[node].sums.map(|sum| (sum / count) as u8));
+        } else {
+            stack.extend(nodes[node].children.iter().flatten());
+        }
+    }
+    return palette;
+}
+
+fn new_octree_node(is_leaf: bool) -> OctreeNode {
+    return OctreeNode {
+        children: [None; 8],
+        sums: [0u64; 3],
+        count: 0,
+        is_leaf,
+    };
+}
+
+// Palette index of every pixel, top row first. Nearest colours are cached per 24 bit colour.
+fn map_to_palette(
+    image: &tga::Image<tga::Rgb>,
+    palette: &[[u8; 3]],
+    dither: bool,
+    vflip: bool,
+) -> Vec<u8> {
+    let width: usize = image.width as usize;
+    let height: usize = image.height as usize;
+    let mut cache: HashMap<[u8; 3], u8> = HashMap::new();
+    let mut nearest = |colour: [f32; 3]| -> u8 {
+        let quantised: [u8; 3] = colour.map(|c| c.round().clamp(0f32, 255f32) as u8);
+        return *cache
+            .entry(quantised)
+            .or_insert_with(|| nearest_colour(palette, quantised));
+    };
+
+    let mut indices: Vec<u8> = Vec::with_capacity(width * height);
+    // Diffused error for the current and next rows.
+    let mut error: Vec<[f32; 3]> = vec![[0f32; 3]; width + 2];
+    let mut next_error: Vec<[f32; 3]> = vec![[0f32; 3]; width + 2];
+    for y in 0..height {
+        let row: usize = if vflip { height - 1 - y } else { y };
+        for x in 0..width {
+            let pixel: tga::Rgb = image.data[row * width + x];
+            let (r, g, b) = (pixel.r, pixel.g, pixel.b);
+            let mut colour: [f32; 3] = [r as f32, g as f32, b as f32];
+            if dither {
+                for c in 0..3usize {
+                    colour[c] += error[x + 1][c];
+                }
+            }
+            let index: u8 = nearest(colour);
+            indices.push(index);
+            if dither {
+                let chosen: [u8; 3] = palette[index as usize];
+                for c in 0..3usize {
+                    let diffused: f32 = colour[c].clamp(0f32, 255f32) - chosen[c] as f32;
+                    error[x + 2][c] += diffused * 7f32 / 16f32;
+                    next_error[x][c] += diffused * 3f32 / 16f32;
+                    next_error[x + 1][c] += diffused * 5f32 / 16f32;
+                    next_error[x + 2][c] += diffused / 16f32;
+                }
+            }
+        }
+        std::mem::swap(&mut error, &mut next_error);
+        next_error.fill([0f32; 3]);
+    }
+    return indices;
+}
+
+fn nearest_colour(palette: &[[u8; 3]], colour: [u8; 3]) -> u8 {
+    let distance = |candidate: &[u8; 3]| -> i32 {
+        return (0..3usize)
+            .map(|c| (candidate[c] as i32 - colour[c] as i32).pow(2))
+            .sum();
+    };
+    return palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, candidate)| distance(candidate))
+        .map(|(index, _)| index as u8)
+        .unwrap_or(0);
+}
+
+// Variable width LZW as used by GIF, codes being packed least significant bit first.
+fn lzw_compress(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear_code: u16 = 1 << min_code_size;
+    let end_code: u16 = clear_code + 1;
+    let mut next_code: u16 = end_code + 1;
+    let mut code_size: u32 = min_code_size as u32 + 1;
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut bit_buffer: u32 = 0;
+    let mut bit_count: u32 = 0;
+    let mut emit = |code: u16, size: u32| {
+        bit_buffer |= (code as u32) << bit_count;
+        bit_count += size;
+        while bit_count >= 8 {
+            bytes.push(bit_buffer as u8);
+            bit_buffer >>= 8;
+            bit_count -= 8;
+        }
+    };
+
+    emit(clear_code, code_size);
+    let mut current: Option<u16> = None;
+    for &index in indices {
+        let prefix: u16 = match current {
+            Some(prefix) => prefix,
+            None => {
+                current = Some(index as u16);
+                continue;
+            }
+        };
+        if let Some(&code) = table.get(&(prefix, index)) {
+            current = Some(code);
+            continue;
+        }
+        emit(prefix, code_size);
+        if next_code == MAX_CODE {
+            emit(clear_code, code_size);
+            table.clear();
+            next_code = end_code + 1;
+            code_size = min_code_size as u32 + 1;
+        } else {
+            if next_code >= 1 << code_size {
+                code_size += 1;
+            }
+            table.insert((prefix, index), next_code);
+            next_code += 1;
+        }
+        current = Some(index as u16);
+    }
+    if let Some(prefix) = current {
+        emit(prefix, code_size);
+    }
+    emit(end_code, code_size);
+    if bit_count > 0 {
+        bytes.push(bit_buffer as u8);
+    }
+    return bytes;
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidInput, message.to_string());
+}
@@ -0,0 +1,848 @@
+use crate::linear_algebra;
+use crate::mesh::{Coords2D, Coords3D, Material, Mesh, Point3D, Triangle};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+// Half-edge view of a triangle mesh. Every triangle corner becomes a half-edge leaving that
+// corner's vertex and keeping its normal and texture indices, so converting back to a `Mesh` is
+// lossless. Edges shared by more than two triangles, or by two triangles of opposite
+// orientations, are left without twins and reported as non-manifold.
+//
+// Edits leave tombstones: removed faces and half-edges keep their slot, and collapsed vertices
+// stay in the coordinate arrays without any face.
+
+#[derive(Copy, Clone, Debug)]
+pub struct HalfEdge {
+    pub origin: usize,
+    pub twin: Option<usize>,
+    pub next: usize,
+    pub prev: usize,
+    pub face: usize,
+    // Normal and texture indices of the corner at `origin`.
+    pub normal: usize,
+    pub texture: usize,
+    pub removed: bool,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Face {
+    pub half_edge: usize,
+    pub material: usize,
+    pub removed: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TopologyError {
+    RemovedElement,
+    BoundaryEdge,
+    // The operation would create an edge that already exists.
+    EdgeExists,
+    // Collapsing the edge would pinch the surface or fold it onto itself.
+    LinkCondition,
+}
+
+impl fmt::Display for TopologyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TopologyError::RemovedElement => write!(f, "Removed element"),
+            TopologyError::BoundaryEdge => write!(f, "Boundary edge"),
+            TopologyError::EdgeExists => write!(f, "Edge already exists"),
+            TopologyError::LinkCondition => write!(f, "Link condition violated"),
+        }
+    }
+}
+
+pub struct HalfEdgeMesh {
+    pub positions: Coords3D,
+    pub normals: Coords3D,
+    pub textures: Coords2D,
+    pub materials: Vec<Material>,
+    half_edges: Vec<HalfEdge>,
+    faces: Vec<Face>,
+    // One outgoing half-edge per vertex, on the boundary if the vertex is.
+    vertex_half_edges: Vec<Option<usize>>,
+    non_manifold_edges: Vec<[usize; 2]>,
+    // Vertices whose faces form several fans. Collapses are refused around them, and no edit
+    // creates new ones.
+    pinched: Vec<bool>,
+}
+
+impl HalfEdgeMesh {
+    pub fn from_mesh(mesh: &Mesh) -> Self {
+        let mut half_edges: Vec<HalfEdge> = Vec::with_capacity(mesh.triangles.len() * 3);
+        let mut faces: Vec<Face> = Vec::with_capacity(mesh.triangles.len());
+        for (f, triangle) in mesh.triangles.iter().enumerate() {
+            faces.push(Face {
+                half_edge: 3 * f,
+                material: triangle.material,
+                removed: false,
+            });
+            for k in 0..3usize {
+                half_edges.push(HalfEdge {
+                    origin: triangle.vertices[k],
+                    twin: None,
+                    next: 3 * f + (k + 1) % 3,
+                    prev: 3 * f + (k + 2) % 3,
+                    face: f,
+                    normal: triangle.normals[k],
+                    texture: triangle.textures[k],
+                    removed: false,
+                });
+            }
+        }
+
+        let mut directed: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for (h, half_edge) in half_edges.iter().enumerate() {
+            let destination: usize = half_edges[half_edge.next].origin;
+            directed
+                .entry((half_edge.origin, destination))
+                .or_default()
+                .push(h);
+        }
+        let mut non_manifold: HashSet<[usize; 2]> = HashSet::new();
+        for (&(a, b), hs) in &directed {
+            match directed.get(&(b, a)) {
+                Some(opposite) if hs.len() == 1 && opposite.len() == 1 => {
+                    half_edges[hs[0]].twin = Some(opposite[0]);
+                }
+                None if hs.len() == 1 => {}
+                _ => {
+                    non_manifold.insert([a.min(b), a.max(b)]);
+                }
+            }
+        }
+        let mut non_manifold_edges: Vec<[usize; 2]> = non_manifold.into_iter().collect();
+        non_manifold_edges.sort();
+
+        let mut vertex_half_edges: Vec<Option<usize>> = vec![None; mesh.v_positions.xs.len()];
+        for (h, half_edge) in half_edges.iter().enumerate() {
+            let slot: &mut Option<usize> = &mut vertex_half_edges[half_edge.origin];
+            if slot.is_none() || half_edge.twin.is_none() {
+                *slot = Some(h);
+            }
+        }
+
+        let mut he_mesh: HalfEdgeMesh = HalfEdgeMesh {
+            positions: mesh.v_positions.clone(),
+            normals: mesh.v_normals.clone(),
+            textures: mesh.v_textures.clone(),
+            materials: mesh.materials.clone(),
+            half_edges,
+            faces,
+            vertex_half_edges,
+            non_manifold_edges,
+            pinched: Vec::new(),
+        };
+        he_mesh.pinched = vec![false; he_mesh.vertex_count()];
+        for v in he_mesh.non_manifold_vertices() {
+            he_mesh.pinched[v] = true;
+        }
+        return he_mesh;
+    }
+
+    // Live faces become triangles. Vertex indices are kept, collapsed vertices included.
+    pub fn to_mesh(&self) -> Mesh {
+        let triangles: Vec<Triangle> = self
+            .faces
+            .iter()
+            .filter(|face| !face.removed)
+            .map(|face| {
+                let hs: [usize; 3] = self.cycle(face.half_edge);
+                Triangle {
+                    vertices: hs.map(|h| self.half_edges[h].origin),
+                    normals: hs.map(|h| self.half_edges[h].normal),
+                    textures: hs.map(|h| self.half_edges[h].texture),
+                    material: face.material,
+                }
+            })
+            .collect();
+        return Mesh {
+            v_positions: self.positions.clone(),
+            v_normals: self.normals.clone(),
+            v_textures: self.textures.clone(),
+            triangles,
+            materials: self.materials.clone(),
+        };
+    }
+
+    pub fn half_edge(&self, h: usize) -> &HalfEdge {
+        return &self.half_edges[h];
+    }
+
+    pub fn face(&self, f: usize) -> &Face {
+        return &self.faces[f];
+    }
+
+    // Slot counts, removed elements included.
+    pub fn half_edge_count(&self) -> usize {
+        return self.half_edges.len();
+    }
+
+    pub fn face_count(&self) -> usize {
+        return self.faces.len();
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        return self.vertex_half_edges.len();
+    }
+
+    pub fn destination(&self, h: usize) -> usize {
+        return self.half_edges[self.half_edges[h].next].origin;
+    }
+
+    pub fn is_boundary_half_edge(&self, h: usize) -> bool {
+        return self.half_edges[h].twin.is_none();
+    }
+
+    pub fn is_boundary_vertex(&self, v: usize) -> bool {
+        return match self.vertex_half_edges[v] {
+            Some(h) => self.half_edges[h].twin.is_none(),
+            None => false,
+        };
+    }
+
+    pub fn find_half_edge(&self, from: usize, to: usize) -> Option<usize> {
+        return self
+            .outgoing_half_edges(from)
+            .into_iter()
+            .find(|&h| self.destination(h) == to);
+    }
+
+    pub fn face_half_edges(&self, f: usize) -> [usize; 3] {
+        return self.cycle(self.faces[f].half_edge);
+    }
+
+    pub fn face_vertices(&self, f: usize) -> [usize; 3] {
+        return self.face_half_edges(f).map(|h| self.half_edges[h].origin);
+    }
+
+    // Faces across the edges of `f`, in the order of its half-edges.
+    pub fn face_neighbours(&self, f: usize) -> [Option<usize>; 3] {
+        return self
+            .face_half_edges(f)
+            .map(|h| self.half_edges[h].twin.map(|t| self.half_edges[t].face));
+    }
+
+    // Half-edges leaving `v` in counter-clockwise order. For a non-manifold vertex, only the fan
+    // of its stored half-edge is visited.
+    pub fn outgoing_half_edges(&self, v: usize) -> Vec<usize> {
+        let start: usize = match self.vertex_half_edges[v] {
+            Some(h) => h,
+            None => return Vec::new(),
+        };
+        let mut outgoing: Vec<usize> = vec![start];
+        let mut h: usize = start;
+        while let Some(t) = self.half_edges[self.half_edges[h].prev].twin {
+            if t == start {
+                break;
+            }
+            outgoing.push(t);
+            h = t;
+        }
+        return outgoing;
+    }
+
+    // Neighbouring vertices in counter-clockwise order.
+    pub fn one_ring(&self, v: usize) -> Vec<usize> {
+        let outgoing: Vec<usize> = self.outgoing_half_edges(v);
+        let mut ring: Vec<usize> = outgoing.iter().map(|&h| self.destination(h)).collect();
+        if let Some(&last) = outgoing.last() {
+            // On the boundary, the last neighbour is only reached by an incoming half-edge.
+            let incoming: usize = self.half_edges[last].prev;
+            if self.half_edges[incoming].twin.is_none() {
+                ring.push(self.half_edges[incoming].origin);
+            }
+        }
+        return ring;
+    }
+
+    pub fn vertex_faces(&self, v: usize) -> Vec<usize> {
+        return self
+            .outgoing_half_edges(v)
+            .iter()
+            .map(|&h| self.half_edges[h].face)
+            .collect();
+    }
+
+    pub fn valence(&self, v: usize) -> usize {
+        return self.one_ring(v).len();
+    }
+
+    // Closed chains of boundary vertices, each following the orientation of its faces.
+    pub fn boundary_loops(&self) -> Vec<Vec<usize>> {
+        let mut visited: Vec<bool> = vec![false; self.half_edges.len()];
+        let mut loops: Vec<Vec<usize>> = Vec::new();
+        for start in 0..self.half_edges.len() {
+            if visited[start]
+                || self.half_edges[start].removed
+                || !self.is_boundary_half_edge(start)
+            {
+                continue;
+            }
+            let mut boundary: Vec<usize> = Vec::new();
+            let mut h: usize = start;
+            while !visited[h] {
+                visited[h] = true;
+                boundary.push(self.half_edges[h].origin);
+                h = self.next_boundary_half_edge(h);
+            }
+            loops.push(boundary);
+        }
+        return loops;
+    }
+
+    // Boundary half-edge leaving the destination of boundary half-edge `h`.
+    fn next_boundary_half_edge(&self, h: usize) -> usize {
+        let mut g: usize = self.half_edges[h].next;
+        while let Some(t) = self.half_edges[g].twin {
+            g = self.half_edges[t].next;
+        }
+        return g;
+    }
+
+    pub fn non_manifold_edges(&self) -> &[[usize; 2]] {
+        return &self.non_manifold_edges;
+    }
+
+    // Vertices whose faces do not form a single fan, like the tip shared by two cones.
+    pub fn non_manifold_vertices(&self) -> Vec<usize> {
+        let mut face_counts: Vec<usize> = vec![0; self.vertex_half_edges.len()];
+        for half_edge in self.half_edges.iter().filter(|h| !h.removed) {
+            face_counts[half_edge.origin] += 1;
+        }
+        return (0..self.vertex_half_edges.len())
+            .filter(|&v| face_counts[v] != self.outgoing_half_edges(v).len())
+            .collect();
+    }
+
+    // Whether the faces of `v` form several fans, like `non_manifold_vertices` but in constant
+    // time.
+    pub fn is_pinched(&self, v: usize) -> bool {
+        return self.pinched[v];
+    }
+
+    pub fn is_manifold(&self) -> bool {
+        return self.non_manifold_edges.is_empty() && self.non_manifold_vertices().is_empty();
+    }
+
+    // Points the corner of `h` at other normal and texture coordinates.
+    pub fn set_corner_attributes(&mut self, h: usize, normal: usize, texture: usize) {
+        self.half_edges[h].normal = normal;
+        self.half_edges[h].texture = texture;
+    }
+
+    // Replaces the edge of `h` by the other diagonal of its two triangles.
+    pub fn flip_edge(&mut self, h: usize) -> Result<(), TopologyError> {
+        self.check_live(h)?;
+        let t: usize = self.half_edges[h].twin.ok_or(TopologyError::BoundaryEdge)?;
+        let [_, n1, p1] = self.cycle(h);
+        let [_, n2, p2] = self.cycle(t);
+        let (a, b) = (self.half_edges[h].origin, self.half_edges[t].origin);
+        let (c, d) = (self.half_edges[p1].origin, self.half_edges[p2].origin);
+        // A boundary edge between c and d only has a half-edge in one direction.
+        if c == d || self.find_half_edge(c, d).is_some() || self.find_half_edge(d, c).is_some() {
+            return Err(TopologyError::EdgeExists);
+        }
+        let (f0, f1) = (self.half_edges[h].face, self.half_edges[t].face);
+
+        // (a, b, c) and (b, a, d) become (c, a, d) and (d, b, c).
+        let (d_normal, d_texture) = (self.half_edges[p2].normal, self.half_edges[p2].texture);
+        let (c_normal, c_texture) = (self.half_edges[p1].normal, self.half_edges[p1].texture);
+        self.set_corner(h, d, d_normal, d_texture);
+        self.set_corner(t, c, c_normal, c_texture);
+        self.link_face(f0, [p1, n2, h]);
+        self.link_face(f1, [p2, n1, t]);
+        if self.vertex_half_edges[a] == Some(h) {
+            self.vertex_half_edges[a] = Some(n2);
+        }
+        if self.vertex_half_edges[b] == Some(t) {
+            self.vertex_half_edges[b] = Some(n1);
+        }
+        return Ok(());
+    }
+
+    // Inserts a vertex in the middle of the edge of `h`, splitting its one or two triangles.
+    // Normals and UVs are interpolated per triangle. Returns the new vertex.
+    pub fn split_edge(&mut self, h: usize) -> Result<usize, TopologyError> {
+        self.check_live(h)?;
+        let (a, b) = (self.half_edges[h].origin, self.destination(h));
+        let (pa, pb) = (self.positions.get_at(a), self.positions.get_at(b));
+        self.positions.add_vector(
+            (pa.x + pb.x) / 2f32,
+            (pa.y + pb.y) / 2f32,
+            (pa.z + pb.z) / 2f32,
+        );
+        let m: usize = self.positions.xs.len() - 1;
+        self.vertex_half_edges.push(None);
+        self.pinched.push(false);
+
+        let twin: Option<usize> = self.half_edges[h].twin;
+        // Each side keeps the half-edge from its first vertex to m and returns the new one
+        // from m to its second vertex.
+        let m_to_b: usize = self.split_side(h, m);
+        if let Some(t) = twin {
+            let m_to_a: usize = self.split_side(t, m);
+            self.half_edges[h].twin = Some(m_to_a);
+            self.half_edges[m_to_a].twin = Some(h);
+            self.half_edges[t].twin = Some(m_to_b);
+            self.half_edges[m_to_b].twin = Some(t);
+        }
+        self.vertex_half_edges[m] = Some(m_to_b);
+        return Ok(m);
+    }
+
+    // Splits the triangle (x, y, z) of half-edge `h` = x -> y into (x, m, z) and (m, y, z).
+    fn split_side(&mut self, h: usize, m: usize) -> usize {
+        let [_, n, p] = self.cycle(h);
+        let f: usize = self.half_edges[h].face;
+        let normal: usize =
+            self.interpolate_normal(self.half_edges[h].normal, self.half_edges[n].normal);
+        let texture: usize =
+            self.interpolate_texture(self.half_edges[h].texture, self.half_edges[n].texture);
+        let z: HalfEdge = self.half_edges[p];
+
+        let new_face: usize = self.faces.len();
+        self.faces.push(Face {
+            half_edge: n,
+            material: self.faces[f].material,
+            removed: false,
+        });
+        let m_to_z: usize = self.push_half_edge(m, normal, texture, f);
+        let z_to_m: usize = self.push_half_edge(z.origin, z.normal, z.texture, new_face);
+        let m_to_y: usize = self.push_half_edge(m, normal, texture, new_face);
+        self.half_edges[m_to_z].twin = Some(z_to_m);
+        self.half_edges[z_to_m].twin = Some(m_to_z);
+        self.link_face(f, [h, m_to_z, p]);
+        self.link_face(new_face, [m_to_y, n, z_to_m]);
+        return m_to_y;
+    }
+
+    // Merges the destination of `h` into its origin, which moves to `position`. Returns the
+    // remaining vertex.
+    pub fn collapse_edge(
+        &mut self,
+        h: usize,
+        position: Point3D<f32>,
+    ) -> Result<usize, TopologyError> {
+        self.check_live(h)?;
+        let (a, b) = (self.half_edges[h].origin, self.destination(h));
+        let twin: Option<usize> = self.half_edges[h].twin;
+        let mut opposite: Vec<usize> = vec![self.half_edges[self.half_edges[h].prev].origin];
+        if let Some(t) = twin {
+            opposite.push(self.half_edges[self.half_edges[t].prev].origin);
+        }
+
+        // Link condition: the two rings only share the opposite vertices, an interior edge does
+        // not join two boundaries, and no opposite vertex is left with a degenerate fan.
+        let ring_a: HashSet<usize> = self.one_ring(a).into_iter().collect();
+        let shared: usize = self
+            .one_ring(b)
+            .iter()
+            .filter(|v| ring_a.contains(v))
+            .count();
+        if shared != opposite.len()
+            || self.pinched[a]
+            || self.pinched[b]
+            || opposite.iter().any(|&v| self.pinched[v])
+            || (twin.is_some() && self.is_boundary_vertex(a) && self.is_boundary_vertex(b))
+            || opposite
+                .iter()
+                .any(|&v| self.valence(v) <= if self.is_boundary_vertex(v) { 2 } else { 3 })
+        {
+            return Err(TopologyError::LinkCondition);
+        }
+
+        let outgoing_b: Vec<usize> = self.outgoing_half_edges(b);
+        let mut a_half_edge: Option<usize> = None;
+        let mut sides: Vec<usize> = vec![h];
+        sides.extend(twin);
+        for &side in &sides {
+            // (x, y, z) loses its edge x -> y, gluing the twins of y -> z and z -> x.
+            let [_, n, p] = self.cycle(side);
+            let (outer_n, outer_p) = (self.half_edges[n].twin, self.half_edges[p].twin);
+            if let Some(o) = outer_n {
+                self.half_edges[o].twin = outer_p;
+            }
+            if let Some(o) = outer_p {
+                self.half_edges[o].twin = outer_n;
+            }
+            for removed in [side, n, p] {
+                self.half_edges[removed].removed = true;
+            }
+            let f: usize = self.half_edges[side].face;
+            self.faces[f].removed = true;
+            // Outer half-edges leave z and the merged vertex respectively, or end there.
+            let z: usize = self.half_edges[p].origin;
+            self.vertex_half_edges[z] = outer_n.or(outer_p.map(|o| self.half_edges[o].next));
+            a_half_edge = a_half_edge
+                .or(outer_p)
+                .or(outer_n.map(|o| self.half_edges[o].next));
+        }
+        for g in outgoing_b {
+            if !self.half_edges[g].removed {
+                self.half_edges[g].origin = a;
+            }
+        }
+
+        self.positions.xs[a] = position.x;
+        self.positions.ys[a] = position.y;
+        self.positions.zs[a] = position.z;
+        self.vertex_half_edges[b] = None;
+        self.vertex_half_edges[a] = a_half_edge;
+        for v in opposite.into_iter().chain([a]) {
+            self.settle_vertex(v);
+        }
+        return Ok(a);
+    }
+
+    fn check_live(&self, h: usize) -> Result<(), TopologyError> {
+        if self.half_edges[h].removed {
+            return Err(TopologyError::RemovedElement);
+        }
+        return Ok(());
+    }
+
+    fn cycle(&self, h: usize) -> [usize; 3] {
+        let next: usize = self.half_edges[h].next;
+        return [h, next, self.half_edges[next].next];
+    }
+
+    fn set_corner(&mut self, h: usize, origin: usize, normal: usize, texture: usize) {
+        self.half_edges[h].origin = origin;
+        self.half_edges[h].normal = normal;
+        self.half_edges[h].texture = texture;
+    }
+
+    fn link_face(&mut self, f: usize, hs: [usize; 3]) {
+        for k in 0..3usize {
+            self.half_edges[hs[k]].next = hs[(k + 1) % 3];
+            self.half_edges[hs[k]].prev = hs[(k + 2) % 3];
+            self.half_edges[hs[k]].face = f;
+        }
+        self.faces[f].half_edge = hs[0];
+    }
+
+    fn push_half_edge(
+        &mut self,
+        origin: usize,
+        normal: usize,
+        texture: usize,
+        face: usize,
+    ) -> usize {
+        self.half_edges.push(HalfEdge {
+            origin,
+            twin: None,
+            next: 0,
+            prev: 0,
+            face,
+            normal,
+            texture,
+            removed: false,
+        });
+        return self.half_edges.len() - 1;
+    }
+
+    // Moves the stored half-edge of `v` clockwise onto the boundary, if `v` is on it.
+    fn settle_vertex(&mut self, v: usize) {
+        let start: usize = match self.vertex_half_edges[v] {
+            Some(h) => h,
+            None => return,
+        };
+        let mut h: usize = start;
+        while let Some(t) = self.half_edges[h].twin {
+            h = self.half_edges[t].next;
+            if h == start {
+                return;
+            }
+        }
+        self.vertex_half_edges[v] = Some(h);
+    }
+
+    fn interpolate_normal(&mut self, i: usize, j: usize) -> usize {
+        if i == j || i >= self.normals.xs.len() || j >= self.normals.xs.len() {
+            return i;
+        }
+        let (p, q) = (self.normals.get_at(i), self.normals.get_at(j));
+        let normal: Point3D<f32> = linear_algebra::normalized(Point3D {
+            x: p.x + q.x,
+            y: p.y + q.y,
+            z: p.z + q.z,
+        });
+        self.normals.add_vector(normal.x, normal.y, normal.z);
+        return self.normals.xs.len() - 1;
+    }
+
+    fn interpolate_texture(&mut self, i: usize, j: usize) -> usize {
+        if i == j || i >= self.textures.us.len() || j >= self.textures.us.len() {
+            return i;
+        }
+        let ((u0, v0), (u1, v1)) = (self.textures.get_at(i), self.textures.get_at(j));
+        self.textures.add_vector((u0 + u1) / 2f32, (v0 + v1) / 2f32);
+        return self.textures.us.len() - 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mesh(positions: &[[f32; 3]], triangles: &[[usize; 3]]) -> Mesh {
+        let mut v_positions: Coords3D = Coords3D {
+            xs: Vec::new(),
+            ys: Vec::new(),
+            zs: Vec::new(),
+        };
+        for &[x, y, z] in positions {
+            v_positions.add_vector(x, y, z);
+        }
+        return Mesh {
+            v_positions,
+            v_normals: Coords3D {
+                xs: Vec::new(),
+                ys: Vec::new(),
+                zs: Vec::new(),
+            },
+            v_textures: Coords2D {
+                us: Vec::new(),
+                vs: Vec::new(),
+            },
+            // Distinct attribute indices per corner, to check that they are carried along.
+            triangles: triangles
+                .iter()
+                .enumerate()
+                .map(|(f, &vertices)| Triangle {
+                    vertices,
+                    normals: [3 * f, 3 * f + 1, 3 * f + 2],
+                    textures: [f; 3],
+                    material: 0,
+                })
+                .collect(),
+            materials: vec![Material::new("default")],
+        };
+    }
+
+    // Three quads in the xy plane facing +z, bottom vertices 0 to 3 and top ones 4 to 7.
+    fn quad_strip() -> Mesh {
+        let mut positions: Vec<[f32; 3]> = Vec::new();
+        for y in [0f32, 1f32] {
+            for x in 0..4 {
+                positions.push([x as f32, y, 0f32]);
+            }
+        }
+        let mut triangles: Vec<[usize; 3]> = Vec::new();
+        for i in 0..3 {
+            triangles.push([i, i + 1, i + 5]);
+            triangles.push([i, i + 5, i + 4]);
+        }
+        return mesh(&positions, &triangles);
+    }
+
+    fn tetrahedron() -> Mesh {
+        return mesh(
+            &[
+                [0f32, 0f32, 0f32],
+                [1f32, 0f32, 0f32],
+                [0f32, 1f32, 0f32],
+                [0f32, 0f32, 1f32],
+            ],
+            &[[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]],
+        );
+    }
+
+    // Two open cones, pointing up and down, whose tips are vertex 0.
+    fn two_cones() -> Mesh {
+        let mut positions: Vec<[f32; 3]> = vec![[0f32, 0f32, 0f32]];
+        let mut triangles: Vec<[usize; 3]> = Vec::new();
+        for y in [1f32, -1f32] {
+            let first: usize = positions.len();
+            for k in 0..4 {
+                let angle: f32 = std::f32::consts::FRAC_PI_2 * k as f32;
+                positions.push([angle.sin(), y, angle.cos()]);
+            }
+            for k in 0..4 {
+                triangles.push([0, first + k, first + (k + 1) % 4]);
+            }
+        }
+        return mesh(&positions, &triangles);
+    }
+
+    fn live_faces(he_mesh: &HalfEdgeMesh) -> usize {
+        return (0..he_mesh.face_count())
+            .filter(|&f| !he_mesh.face(f).removed)
+            .count();
+    }
+
+    // Whether `cycle` is `expected` up to the starting element.
+    fn is_rotation(cycle: &[usize], expected: &[usize]) -> bool {
+        return cycle.len() == expected.len()
+            && (0..expected.len()).any(|shift| {
+                (0..expected.len()).all(|i| cycle[(i + shift) % cycle.len()] == expected[i])
+            });
+    }
+
+    // Triangles of the strip facing +z after an edit.
+    fn faces_up(mesh: &Mesh) -> bool {
+        return mesh.triangles.iter().all(|triangle| {
+            let [p, q, r] = triangle.vertices.map(|v| mesh.v_positions.get_at(v));
+            let normal: Point3D<f32> = linear_algebra::cross_product(
+                linear_algebra::subtract(q, p),
+                linear_algebra::subtract(r, p),
+            );
+            return normal.z > 0f32;
+        });
+    }
+
+    #[test]
+    fn converting_back_is_lossless() {
+        for mesh in [quad_strip(), tetrahedron(), two_cones()] {
+            let round_trip: Mesh = HalfEdgeMesh::from_mesh(&mesh).to_mesh();
+            assert_eq!(round_trip.triangles.len(), mesh.triangles.len());
+            for (triangle, expected) in round_trip.triangles.iter().zip(&mesh.triangles) {
+                assert_eq!(triangle.vertices, expected.vertices);
+                assert_eq!(triangle.normals, expected.normals);
+                assert_eq!(triangle.textures, expected.textures);
+            }
+            assert_eq!(round_trip.v_positions.xs, mesh.v_positions.xs);
+        }
+    }
+
+    #[test]
+    fn one_ring_is_counter_clockwise() {
+        let strip: HalfEdgeMesh = HalfEdgeMesh::from_mesh(&quad_strip());
+        // A boundary ring starts and ends on the boundary.
+        assert_eq!(strip.one_ring(1), vec![2, 6, 5, 0]);
+        assert_eq!(strip.one_ring(4), vec![0, 5]);
+        assert_eq!(strip.valence(5), 4);
+        assert!(strip.is_boundary_vertex(5));
+
+        let tetrahedron: HalfEdgeMesh = HalfEdgeMesh::from_mesh(&tetrahedron());
+        assert!(is_rotation(&tetrahedron.one_ring(0), &[2, 1, 3]));
+        assert!(is_rotation(&tetrahedron.one_ring(3), &[0, 1, 2]));
+        assert!(!tetrahedron.is_boundary_vertex(0));
+    }
+
+    #[test]
+    fn boundary_loops_follow_the_faces() {
+        let strip: HalfEdgeMesh = HalfEdgeMesh::from_mesh(&quad_strip());
+        let loops: Vec<Vec<usize>> = strip.boundary_loops();
+        assert_eq!(loops.len(), 1);
+        assert!(is_rotation(&loops[0], &[0, 1, 2, 3, 7, 6, 5, 4]));
+        assert!(strip.is_manifold());
+
+        let tetrahedron: HalfEdgeMesh = HalfEdgeMesh::from_mesh(&tetrahedron());
+        assert!(tetrahedron.boundary_loops().is_empty());
+        assert!(tetrahedron.is_manifold());
+
+        let cones: HalfEdgeMesh = HalfEdgeMesh::from_mesh(&two_cones());
+        assert_eq!(cones.boundary_loops().len(), 2);
+        assert!(cones.non_manifold_edges().is_empty());
+        assert_eq!(cones.non_manifold_vertices(), vec![0]);
+        assert!(cones.is_pinched(0));
+        assert!(!cones.is_manifold());
+    }
+
+    #[test]
+    fn flip_edge_swaps_the_diagonal() {
+        let mut strip: HalfEdgeMesh = HalfEdgeMesh::from_mesh(&quad_strip());
+        let h: usize = strip.find_half_edge(1, 6).unwrap();
+        assert_eq!(strip.flip_edge(h), Ok(()));
+        assert_eq!(strip.find_half_edge(1, 6), None);
+        assert_eq!(strip.find_half_edge(6, 1), None);
+        assert!(strip.find_half_edge(2, 5).is_some() && strip.find_half_edge(5, 2).is_some());
+        assert_eq!(strip.one_ring(1), vec![2, 5, 0]);
+        assert!(faces_up(&strip.to_mesh()));
+        assert!(strip.is_manifold());
+
+        let boundary: usize = strip.find_half_edge(0, 1).unwrap();
+        assert_eq!(strip.flip_edge(boundary), Err(TopologyError::BoundaryEdge));
+        // The other diagonal of two tetrahedron faces is already an edge.
+        let mut tetrahedron: HalfEdgeMesh = HalfEdgeMesh::from_mesh(&tetrahedron());
+        let h: usize = tetrahedron.find_half_edge(0, 1).unwrap();
+        assert_eq!(tetrahedron.flip_edge(h), Err(TopologyError::EdgeExists));
+    }
+
+    #[test]
+    fn split_edge_adds_a_midpoint() {
+        let mut tetrahedron: HalfEdgeMesh = HalfEdgeMesh::from_mesh(&tetrahedron());
+        let h: usize = tetrahedron.find_half_edge(0, 1).unwrap();
+        let m: usize = tetrahedron.split_edge(h).unwrap();
+        assert_eq!(m, 4);
+        let midpoint: Point3D<f32> = tetrahedron.positions.get_at(m);
+        assert_eq!((midpoint.x, midpoint.y, midpoint.z), (0.5f32, 0f32, 0f32));
+        assert_eq!(live_faces(&tetrahedron), 6);
+        assert!(is_rotation(&tetrahedron.one_ring(m), &[1, 3, 0, 2]));
+        assert_eq!(tetrahedron.find_half_edge(0, 1), None);
+        assert!(tetrahedron.boundary_loops().is_empty());
+        assert!(tetrahedron.is_manifold());
+
+        let mut strip: HalfEdgeMesh = HalfEdgeMesh::from_mesh(&quad_strip());
+        let boundary: usize = strip.find_half_edge(0, 1).unwrap();
+        let m: usize = strip.split_edge(boundary).unwrap();
+        assert_eq!(live_faces(&strip), 7);
+        assert!(strip.is_boundary_vertex(m));
+        assert_eq!(strip.one_ring(m), vec![1, 5, 0]);
+        assert_eq!(strip.boundary_loops()[0].len(), 9);
+        assert!(faces_up(&strip.to_mesh()));
+    }
+
+    #[test]
+    fn collapse_edge_merges_its_vertices() {
+        let mut strip: HalfEdgeMesh = HalfEdgeMesh::from_mesh(&quad_strip());
+        let h: usize = strip.find_half_edge(1, 2).unwrap();
+        let position: Point3D<f32> = Point3D {
+            x: 1.5f32,
+            y: 0f32,
+            z: 0f32,
+        };
+        assert_eq!(strip.collapse_edge(h, position), Ok(1));
+        assert_eq!(live_faces(&strip), 5);
+        assert_eq!(strip.one_ring(1), vec![3, 7, 6, 5, 0]);
+        assert!(strip.one_ring(2).is_empty());
+        assert_eq!(strip.positions.get_at(1).x, 1.5f32);
+        let loops: Vec<Vec<usize>> = strip.boundary_loops();
+        assert_eq!(loops.len(), 1);
+        assert!(is_rotation(&loops[0], &[0, 1, 3, 7, 6, 5, 4]));
+        assert!(strip.is_manifold());
+        let collapsed: Mesh = strip.to_mesh();
+        assert!(faces_up(&collapsed));
+        assert!(collapsed
+            .triangles
+            .iter()
+            .all(|triangle| !triangle.vertices.contains(&2)));
+    }
+
+    #[test]
+    fn collapse_edge_refuses_to_break_the_link_condition() {
+        // Collapsing any tetrahedron edge would leave two faces back to back.
+        let mut tetrahedron: HalfEdgeMesh = HalfEdgeMesh::from_mesh(&tetrahedron());
+        let h: usize = tetrahedron.find_half_edge(0, 1).unwrap();
+        let position: Point3D<f32> = tetrahedron.positions.get_at(0);
+        assert_eq!(
+            tetrahedron.collapse_edge(h, position),
+            Err(TopologyError::LinkCondition)
+        );
+        assert_eq!(live_faces(&tetrahedron), 4);
+
+        // Neither into nor out of the shared tip.
+        let mut cones: HalfEdgeMesh = HalfEdgeMesh::from_mesh(&two_cones());
+        for (from, to) in [(0, 1), (1, 0)] {
+            let h: usize = cones.find_half_edge(from, to).unwrap();
+            let position: Point3D<f32> = cones.positions.get_at(from);
+            assert_eq!(
+                cones.collapse_edge(h, position),
+                Err(TopologyError::LinkCondition)
+            );
+        }
+        assert_eq!(live_faces(&cones), 8);
+
+        // An edge removed by an earlier collapse is refused as such.
+        let mut strip: HalfEdgeMesh = HalfEdgeMesh::from_mesh(&quad_strip());
+        let h: usize = strip.find_half_edge(1, 2).unwrap();
+        let position: Point3D<f32> = strip.positions.get_at(1);
+        strip.collapse_edge(h, position).unwrap();
+        assert_eq!(
+            strip.collapse_edge(h, position),
+            Err(TopologyError::RemovedElement)
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use crate::netpbm;
+use crate::tga::{self, PixelChannels};
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::Path,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Tga,
+    Png,
+    Bmp,
+    Qoi,
+    // PGM, PPM or PAM, depending on the pixel type.
+    Netpbm,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension: String = path.extension()?.to_str()?.to_ascii_lowercase();
+        return match extension.as_str() {
+            "tga" => Some(ImageFormat::Tga),
+            "png" => Some(ImageFormat::Png),
+            "bmp" => Some(ImageFormat::Bmp),
+            "qoi" => Some(ImageFormat::Qoi),
+            "pgm" | "ppm" | "pam" | "pnm" => Some(ImageFormat::Netpbm),
+            _ => None,
+        };
+    }
+}
+
+impl<T: PixelChannels> tga::Image<T> {
+    // Writes the image with the encoder matching the file extension. The first image row is taken
+    // to be the bottom of the picture, as in the drawers output.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path: &Path = path.as_ref();
+        let format: ImageFormat = ImageFormat::from_path(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown image extension for {}", path.display()),
+            )
+        })?;
+        let mut writer: BufWriter<File> = BufWriter::new(File::create(path)?);
+        return match format {
+            ImageFormat::Tga => self.write(&mut writer, true, false),
+            ImageFormat::Png => self.write_png(&mut writer, true),
+            ImageFormat::Bmp => self.write_bmp(&mut writer, true),
+            ImageFormat::Qoi => self.write_qoi(&mut writer, true),
+            ImageFormat::Netpbm => self.write_netpbm(&mut writer, true, netpbm::Encoding::Binary),
+        };
+    }
+}
@@ -0,0 +1,29 @@
+#![allow(clippy::needless_return)]
+
+pub mod blending;
+pub mod bmp;
+pub mod bounding_box;
+pub mod bvh;
+pub mod compare;
+pub mod decimation;
+pub mod drawers;
+pub mod gif;
+pub mod half_edge;
+pub mod image_io;
+pub mod linear_algebra;
+pub mod mesh;
+pub mod netpbm;
+pub mod obj_importer;
+pub mod oit;
+pub mod pfm;
+pub mod png;
+pub mod primitives;
+pub mod qoi;
+pub mod radiance;
+pub mod repair;
+pub mod subdivision;
+pub mod svg;
+pub mod tga;
+pub mod tonemap;
+pub mod y4m;
+mod zlib;
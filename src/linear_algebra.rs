@@ -12,6 +12,14 @@ pub fn dot_product(u: Point3D<f32>, v: Point3D<f32>) -> f32 {
     return u.x * v.x + u.y * v.y + u.z * v.z;
 }
 
+pub fn subtract(u: Point3D<f32>, v: Point3D<f32>) -> Point3D<f32> {
+    return Point3D {
+        x: u.x - v.x,
+        y: u.y - v.y,
+        z: u.z - v.z,
+    };
+}
+
 pub fn norm(v: Point3D<f32>) -> f32 {
     return dot_product(v, v).sqrt();
 }
@@ -48,7 +56,7 @@ pub fn get_plane_normal(u: Point3D<f32>, v: Point3D<f32>, w: Point3D<f32>) -> Po
 }
 
 // Assuming a, b, and c appear in a direct/clockwise order
-pub fn triangle_area(a: (u16, u16), b: (u16, u16), c: (u16, u16)) -> f32 {
+pub fn triangle_area(a: (u32, u32), b: (u32, u32), c: (u32, u32)) -> f32 {
     let v_ca = Point3D {
         x: a.0 as f32 - c.0 as f32,
         y: a.1 as f32 - c.1 as f32,
@@ -64,10 +72,10 @@ pub fn triangle_area(a: (u16, u16), b: (u16, u16), c: (u16, u16)) -> f32 {
 }
 
 pub fn point_barycentric_coord_in_rast_triangle(
-    point: (u16, u16),
-    a: (u16, u16),
-    b: (u16, u16),
-    c: (u16, u16),
+    point: (u32, u32),
+    a: (u32, u32),
+    b: (u32, u32),
+    c: (u32, u32),
 ) -> Point3D<f32> {
     let abc_area: f32 = triangle_area(a, b, c);
     let norm_pab_area: f32 = triangle_area(point, a, b) / abc_area;
@@ -104,3 +112,179 @@ pub fn barycentric_interpolation(
             + barycentric_coords.z * vertex2_normal.z,
     };
 }
+
+// Row-major 4x4 matrix applied to column vectors, so that `a.multiply(&b)` applies `b` first.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mat4 {
+    pub rows: [[f32; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn identity() -> Self {
+        return Mat4::scaling(Point3D {
+            x: 1f32,
+            y: 1f32,
+            z: 1f32,
+        });
+    }
+
+    pub fn translation(offset: Point3D<f32>) -> Self {
+        let mut matrix: Mat4 = Mat4::identity();
+        matrix.rows[0][3] = offset.x;
+        matrix.rows[1][3] = offset.y;
+        matrix.rows[2][3] = offset.z;
+        return matrix;
+    }
+
+    pub fn scaling(factors: Point3D<f32>) -> Self {
+        return Mat4 {
+            rows: [
+                [factors.x, 0f32, 0f32, 0f32],
+                [0f32, factors.y, 0f32, 0f32],
+                [0f32, 0f32, factors.z, 0f32],
+                [0f32, 0f32, 0f32, 1f32],
+            ],
+        };
+    }
+
+    // Counterclockwise by `angle` radians when looking from the tip of `axis` towards the origin.
+    pub fn rotation(axis: Point3D<f32>, angle: f32) -> Self {
+        let Point3D { x, y, z } = normalized(axis);
+        let (sin, cos) = angle.sin_cos();
+        let t: f32 = 1f32 - cos;
+        return Mat4 {
+            rows: [
+                [
+                    t * x * x + cos,
+                    t * x * y - sin * z,
+                    t * x * z + sin * y,
+                    0f32,
+                ],
+                [
+                    t * x * y + sin * z,
+                    t * y * y + cos,
+                    t * y * z - sin * x,
+                    0f32,
+                ],
+                [
+                    t * x * z - sin * y,
+                    t * y * z + sin * x,
+                    t * z * z + cos,
+                    0f32,
+                ],
+                [0f32, 0f32, 0f32, 1f32],
+            ],
+        };
+    }
+
+    pub fn multiply(&self, other: &Mat4) -> Mat4 {
+        let mut rows: [[f32; 4]; 4] = [[0f32; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, element) in row.iter_mut().enumerate() {
+                *element = (0..4).map(|k| self.rows[i][k] * other.rows[k][j]).sum();
+            }
+        }
+        return Mat4 { rows };
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut rows: [[f32; 4]; 4] = [[0f32; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, element) in row.iter_mut().enumerate() {
+                *element = self.rows[j][i];
+            }
+        }
+        return Mat4 { rows };
+    }
+
+    pub fn determinant(&self) -> f32 {
+        return (0..4).map(|j| self.rows[0][j] * self.cofactor(0, j)).sum();
+    }
+
+    // None for a singular matrix.
+    pub fn inverse(&self) -> Option<Mat4> {
+        let determinant: f32 = self.determinant();
+        if determinant == 0f32 || !determinant.is_finite() {
+            return None;
+        }
+        let mut rows: [[f32; 4]; 4] = [[0f32; 4]; 4];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, element) in row.iter_mut().enumerate() {
+                *element = self.cofactor(j, i) / determinant;
+            }
+        }
+        return Some(Mat4 { rows });
+    }
+
+    // Determinant of the upper left 3x3 block, negative when the matrix mirrors.
+    pub fn linear_determinant(&self) -> f32 {
+        let m: [[f32; 4]; 4] = self.rows;
+        return m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+    }
+
+    // Divides by w, for projections.
+    pub fn transform_point(&self, p: Point3D<f32>) -> Point3D<f32> {
+        let m: [[f32; 4]; 4] = self.rows;
+        let row = |i: usize| -> f32 { m[i][0] * p.x + m[i][1] * p.y + m[i][2] * p.z + m[i][3] };
+        let w: f32 = row(3);
+        let w: f32 = if w == 0f32 { 1f32 } else { w };
+        return Point3D {
+            x: row(0) / w,
+            y: row(1) / w,
+            z: row(2) / w,
+        };
+    }
+
+    // Ignores the translation.
+    pub fn transform_vector(&self, v: Point3D<f32>) -> Point3D<f32> {
+        let m: [[f32; 4]; 4] = self.rows;
+        let row = |i: usize| -> f32 { m[i][0] * v.x + m[i][1] * v.y + m[i][2] * v.z };
+        return Point3D {
+            x: row(0),
+            y: row(1),
+            z: row(2),
+        };
+    }
+
+    // Normals are transformed by the inverse transpose of the upper left 3x3 block, which keeps
+    // them orthogonal to the surface under non-uniform scaling. The result is normalised, and
+    // null for a singular matrix.
+    pub fn transform_normal(&self, n: Point3D<f32>) -> Point3D<f32> {
+        // The cofactor matrix is the inverse transpose times the determinant.
+        let m: [[f32; 4]; 4] = self.rows;
+        let cofactor = |i: usize, j: usize| -> f32 {
+            let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+            let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+            return m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+        };
+        let sign: f32 = self.linear_determinant().signum();
+        let row = |i: usize| -> f32 {
+            sign * (cofactor(i, 0) * n.x + cofactor(i, 1) * n.y + cofactor(i, 2) * n.z)
+        };
+        return normalized(Point3D {
+            x: row(0),
+            y: row(1),
+            z: row(2),
+        });
+    }
+
+    fn cofactor(&self, row: usize, column: usize) -> f32 {
+        let mut minor: [[f32; 3]; 3] = [[0f32; 3]; 3];
+        for (i, r) in (0..4).filter(|&r| r != row).enumerate() {
+            for (j, c) in (0..4).filter(|&c| c != column).enumerate() {
+                minor[i][j] = self.rows[r][c];
+            }
+        }
+        let determinant: f32 = minor[0][0]
+            * (minor[1][1] * minor[2][2] - minor[1][2] * minor[2][1])
+            - minor[0][1] * (minor[1][0] * minor[2][2] - minor[1][2] * minor[2][0])
+            + minor[0][2] * (minor[1][0] * minor[2][1] - minor[1][1] * minor[2][0]);
+        return if (row + column).is_multiple_of(2) {
+            determinant
+        } else {
+            -determinant
+        };
+    }
+}
@@ -1,5 +1,7 @@
+use super::linear_algebra::Mat4;
 use super::{bounding_box, linear_algebra};
 use std::cmp::Ordering;
+use std::collections::HashMap;
 
 #[derive(Copy, Clone, Debug)]
 pub struct Point3D<T> {
@@ -32,6 +34,16 @@ pub struct Triangle {
     pub vertices: [usize; 3],
     pub normals: [usize; 3],
     pub textures: [usize; 3],
+    pub material: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub name: String,
+    // MTL `Kd`, linear RGB in [0, 1].
+    pub diffuse: Point3D<f32>,
+    // MTL `d`, 1 being fully opaque.
+    pub dissolve: f32,
 }
 
 #[derive(Clone, Debug)]
@@ -40,6 +52,58 @@ pub struct Mesh {
     pub v_normals: Coords3D,
     pub v_textures: Coords2D,
     pub triangles: Vec<Triangle>,
+    pub materials: Vec<Material>,
+}
+
+// Face with any number of corners, as read from an OBJ file before triangulation.
+#[derive(Clone, Debug)]
+pub struct Polygon {
+    pub vertices: Vec<usize>,
+    pub normals: Vec<usize>,
+    pub textures: Vec<usize>,
+    pub material: usize,
+}
+
+// `Mesh` keeping its polygons, such as the quads of a subdivision cage.
+#[derive(Clone, Debug)]
+pub struct PolygonMesh {
+    pub v_positions: Coords3D,
+    pub v_normals: Coords3D,
+    pub v_textures: Coords2D,
+    pub polygons: Vec<Polygon>,
+    pub materials: Vec<Material>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EdgeClass {
+    Silhouette,
+    Boundary,
+    Crease,
+    MaterialBorder,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct FeatureEdge {
+    pub vertices: [usize; 2],
+    pub class: EdgeClass,
+}
+
+impl Material {
+    pub fn new(name: &str) -> Self {
+        Material {
+            name: String::from(name),
+            diffuse: Point3D {
+                x: 1f32,
+                y: 1f32,
+                z: 1f32,
+            },
+            dissolve: 1f32,
+        }
+    }
+
+    pub fn is_translucent(&self) -> bool {
+        return self.dissolve < 1f32;
+    }
 }
 
 impl Coords3D {
@@ -101,6 +165,120 @@ impl Mesh {
         };
     }
 
+    // Normals follow the inverse transpose of the matrix, and mirroring matrices reverse the
+    // winding of the triangles so that they keep facing outwards.
+    pub fn transform(&mut self, matrix: &Mat4) {
+        for v in 0..self.v_positions.xs.len() {
+            let p: Point3D<f32> = matrix.transform_point(self.v_positions.get_at(v));
+            (
+                self.v_positions.xs[v],
+                self.v_positions.ys[v],
+                self.v_positions.zs[v],
+            ) = (p.x, p.y, p.z);
+        }
+        for n in 0..self.v_normals.xs.len() {
+            let normal: Point3D<f32> = matrix.transform_normal(self.v_normals.get_at(n));
+            (
+                self.v_normals.xs[n],
+                self.v_normals.ys[n],
+                self.v_normals.zs[n],
+            ) = (normal.x, normal.y, normal.z);
+        }
+        if matrix.linear_determinant() < 0f32 {
+            for triangle in &mut self.triangles {
+                triangle.vertices.swap(1, 2);
+                triangle.normals.swap(1, 2);
+                triangle.textures.swap(1, 2);
+            }
+        }
+    }
+
+    // Moves the mesh so that its bounding box is centred on the origin, returning the
+    // translation applied.
+    pub fn center(&mut self) -> Mat4 {
+        if self.v_positions.xs.is_empty() {
+            return Mat4::identity();
+        }
+        let center: Point3D<f32> = self.bounding_box().center();
+        let translation: Mat4 = Mat4::translation(Point3D {
+            x: -center.x,
+            y: -center.y,
+            z: -center.z,
+        });
+        self.transform(&translation);
+        return translation;
+    }
+
+    // Centres the mesh and scales it uniformly so that it fits in [-1, 1] on every axis, as
+    // `drawers::mesh` expects, returning the transform applied.
+    pub fn normalize_to_unit_box(&mut self) -> Mat4 {
+        let translation: Mat4 = self.center();
+        if self.v_positions.xs.is_empty() {
+            return translation;
+        }
+        let extent: Point3D<f32> = self.bounding_box().extent();
+        let half_extent: f32 = extent.x.max(extent.y).max(extent.z) / 2f32;
+        if half_extent <= 0f32 || !half_extent.is_finite() {
+            return translation;
+        }
+        let scaling: Mat4 = Mat4::scaling(Point3D {
+            x: 1f32 / half_extent,
+            y: 1f32 / half_extent,
+            z: 1f32 / half_extent,
+        });
+        self.transform(&scaling);
+        return scaling.multiply(&translation);
+    }
+
+    // One mesh holding the triangles of all `meshes`, their indices shifted past the
+    // positions, normals, textures and materials of the meshes before them.
+    pub fn merge(meshes: &[Mesh]) -> Mesh {
+        let mut merged: Mesh = Mesh {
+            v_positions: Coords3D {
+                xs: Vec::new(),
+                ys: Vec::new(),
+                zs: Vec::new(),
+            },
+            v_normals: Coords3D {
+                xs: Vec::new(),
+                ys: Vec::new(),
+                zs: Vec::new(),
+            },
+            v_textures: Coords2D {
+                us: Vec::new(),
+                vs: Vec::new(),
+            },
+            triangles: Vec::new(),
+            materials: Vec::new(),
+        };
+        for mesh in meshes {
+            let position_offset: usize = merged.v_positions.xs.len();
+            let normal_offset: usize = merged.v_normals.xs.len();
+            let texture_offset: usize = merged.v_textures.us.len();
+            let material_offset: usize = merged.materials.len();
+            for coords in [
+                (&mut merged.v_positions, &mesh.v_positions),
+                (&mut merged.v_normals, &mesh.v_normals),
+            ] {
+                coords.0.xs.extend_from_slice(&coords.1.xs);
+                coords.0.ys.extend_from_slice(&coords.1.ys);
+                coords.0.zs.extend_from_slice(&coords.1.zs);
+            }
+            merged.v_textures.us.extend_from_slice(&mesh.v_textures.us);
+            merged.v_textures.vs.extend_from_slice(&mesh.v_textures.vs);
+            merged.materials.extend_from_slice(&mesh.materials);
+            merged
+                .triangles
+                .extend(mesh.triangles.iter().map(|triangle| Triangle {
+                    vertices: triangle.vertices.map(|v| v + position_offset),
+                    normals: triangle.normals.map(|n| n + normal_offset),
+                    textures: triangle.textures.map(|t| t + texture_offset),
+                    material: triangle.material + material_offset,
+                }));
+        }
+        return merged;
+    }
+
     pub fn triangle_normal(&self, triangle: &Triangle) -> Point3D<f32> {
         return linear_algebra::get_plane_normal(
             self.v_positions.get_at(triangle.vertices[0]),
@@ -113,19 +291,107 @@ impl Mesh {
         let triangle_normal = self.triangle_normal(triangle);
         return linear_algebra::dot_product(triangle_normal, view_direction) > 0f32;
     }
+
+    // Edges worth drawing in a line illustration of the mesh seen along `view_direction`.
+    // An edge belonging to several classes is reported once, under the first of silhouette,
+    // boundary, crease and material border. Edges with only back facing neighbours are hidden
+    // and left out, silhouettes aside.
+    pub fn feature_edges(
+        &self,
+        view_direction: Point3D<f32>,
+        crease_angle_degrees: f32,
+    ) -> Vec<FeatureEdge> {
+        let crease_cos: f32 = crease_angle_degrees.to_radians().cos();
+        let mut feature_edges: Vec<FeatureEdge> = Vec::new();
+
+        for (vertices, adjacent_triangles) in self.edge_triangles() {
+            let backfaces: Vec<bool> = adjacent_triangles
+                .iter()
+                .map(|&t| self.triangle_is_backface(&self.triangles[t], view_direction))
+                .collect();
+            let is_silhouette: bool = backfaces.contains(&true) && backfaces.contains(&false);
+            if !is_silhouette && !backfaces.contains(&false) {
+                continue;
+            }
+
+            let class: Option<EdgeClass> = if is_silhouette {
+                Some(EdgeClass::Silhouette)
+            } else if adjacent_triangles.len() == 1 {
+                Some(EdgeClass::Boundary)
+            } else if self.edge_is_crease(&adjacent_triangles, crease_cos) {
+                Some(EdgeClass::Crease)
+            } else if adjacent_triangles.iter().any(|&t| {
+                self.triangles[t].material != self.triangles[adjacent_triangles[0]].material
+            }) {
+                Some(EdgeClass::MaterialBorder)
+            } else {
+                None
+            };
+
+            if let Some(class) = class {
+                feature_edges.push(FeatureEdge { vertices, class });
+            }
+        }
+        feature_edges.sort_by_key(|edge| edge.vertices);
+        return feature_edges;
+    }
+
+    // Maps every undirected edge, keyed by its sorted vertex indices, to the triangles using it.
+    fn edge_triangles(&self) -> HashMap<[usize; 2], Vec<usize>> {
+        let mut edges: HashMap<[usize; 2], Vec<usize>> = HashMap::new();
+        for (t, triangle) in self.triangles.iter().enumerate() {
+            for i in 0..3usize {
+                let a: usize = triangle.vertices[i];
+                let b: usize = triangle.vertices[(i + 1).rem_euclid(3)];
+                edges.entry([a.min(b), a.max(b)]).or_default().push(t);
+            }
+        }
+        return edges;
+    }
+
+    fn edge_is_crease(&self, adjacent_triangles: &[usize], crease_cos: f32) -> bool {
+        let first_normal = self.triangle_normal(&self.triangles[adjacent_triangles[0]]);
+        return adjacent_triangles[1..].iter().any(|&t| {
+            let normal = self.triangle_normal(&self.triangles[t]);
+            linear_algebra::dot_product(first_normal, normal) < crease_cos
+        });
+    }
+}
+
+impl PolygonMesh {
+    // Splits every polygon into a fan of triangles around its first corner.
+    pub fn triangulate(&self) -> Mesh {
+        let mut triangles: Vec<Triangle> = Vec::new();
+        for polygon in &self.polygons {
+            for i in 2..polygon.vertices.len() {
+                let corners: [usize; 3] = [0, i - 1, i];
+                triangles.push(Triangle {
+                    vertices: corners.map(|k| polygon.vertices[k]),
+                    normals: corners.map(|k| polygon.normals[k]),
+                    textures: corners.map(|k| polygon.textures[k]),
+                    material: polygon.material,
+                });
+            }
+        }
+        return Mesh {
+            v_positions: self.v_positions.clone(),
+            v_normals: self.v_normals.clone(),
+            v_textures: self.v_textures.clone(),
+            triangles,
+            materials: self.materials.clone(),
+        };
+    }
 }
 
 fn get_vec_min_max(elements: &Vec<f32>) -> (f32, f32) {
     let mut min = elements[0];
     let mut max = elements[0];
     for element in elements {
-        match element.partial_cmp(&min) {
-            Some(Ordering::Less) => min = *element,
-            _ => (),
+        if let Some(Ordering::Less) = element.partial_cmp(&min) {
+            min = *element;
         }
-        match element.partial_cmp(&max) {
-            Some(Ordering::Greater) => max = *element,
-            _ => (),
+        if let Some(Ordering::Greater) = element.partial_cmp(&max) {
+            max = *element;
         }
     }
     return (min, max);
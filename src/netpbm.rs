@@ -0,0 +1,242 @@
+use crate::tga::{self, PixelChannels};
+use std::io::{self, prelude::*};
+
+// Netpbm reading and writing: PGM (P2, P5) for greyscale, PPM (P3, P6) for RGB and PAM (P7) for
+// RGBA. With `vflip`, the first image row is the bottom of the picture, as in the drawers output.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    // Plain text samples, P2 and P3.
+    Ascii,
+    // Raw bytes, P5, P6 and P7.
+    Binary,
+}
+
+impl<T: PixelChannels> tga::Image<T> {
+    // RGBA images can only be written as binary PAM.
+    pub fn write_netpbm<W: io::Write>(
+        &self,
+        writer: &mut io::BufWriter<W>,
+        vflip: bool,
+        encoding: Encoding,
+    ) -> io::Result<()> {
+        let magic: &str = match (T::BPP, encoding) {
+            (1, Encoding::Ascii) => "P2",
+            (1, Encoding::Binary) => "P5",
+            (3, Encoding::Ascii) => "P3",
+            (3, Encoding::Binary) => "P6",
+            (4, Encoding::Binary) => "P7",
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "no plain text Netpbm format holds an alpha channel",
+                ))
+            }
+        };
+        if magic == "P7" {
+            write!(
+                writer,
+                "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+                self.width, self.height
+            )?;
+        } else {
+            write!(writer, "{}\n{} {}\n255\n", magic, self.width, self.height)?;
+        }
+
+        let width: usize = self.width as usize;
+        let mut bytes: Vec<u8> = Vec::with_capacity(width * T::BPP as usize);
+        for y in 0..self.height as usize {
+            let row: usize = if vflip {
+                self.height as usize - 1 - y
+            } else {
+                y
+            };
+            bytes.clear();
+            for pixel in &self.data[row * width..(row + 1) * width] {
+                pixel.push_channels(&mut bytes);
+            }
+            match encoding {
+                Encoding::Binary => writer.write_all(&bytes)?,
+                Encoding::Ascii => {
+                    // Plain formats ask for lines of at most 70 characters.
+                    for line in bytes.chunks(17) {
+                        let samples: Vec<String> =
+                            line.iter().map(|sample| sample.to_string()).collect();
+                        writeln!(writer, "{}", samples.join(" "))?;
+                    }
+                }
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+// Samples with a maxval other than 255 are rescaled to 8 bits. Greyscale with alpha is expanded
+// to `Rgba`.
+pub fn read<R: io::BufRead>(reader: &mut R, vflip: bool) -> io::Result<tga::DynamicImage> {
+    let magic: String = token(reader)?;
+    let (width, height, depth, max_value, ascii) = match magic.as_str() {
+        "P2" | "P3" | "P5" | "P6" => {
+            let width: usize = number(reader)?;
+            let height: usize = number(reader)?;
+            let max_value: usize = number(reader)?;
+            let depth: usize = if magic == "P2" || magic == "P5" { 1 } else { 3 };
+            (
+                width,
+                height,
+                depth,
+                max_value,
+                magic == "P2" || magic == "P3",
+            )
+        }
+        "P7" => {
+            let (width, height, depth, max_value) = read_pam_header(reader)?;
+            (width, height, depth, max_value, false)
+        }
+        _ => return Err(invalid_data("unsupported Netpbm format")),
+    };
+    // Decoded images take at most four bytes per pixel.
+    if width == 0
+        || height == 0
+        || width > u32::MAX as usize
+        || height > u32::MAX as usize
+        || tga::decoded_size(width, height, 4).is_err()
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            tga::Error::InvalidDimensions.to_string(),
+        ));
+    }
+    if max_value == 0 || max_value > u16::MAX as usize || !(1..=4).contains(&depth) {
+        return Err(invalid_data("unsupported maxval or depth"));
+    }
+
+    // Buffers grow with the data read, so that a truncated file does not allocate the whole
+    // image.
+    let sample_count: usize = width * height * depth;
+    let mut samples: Vec<u8> = Vec::new();
+    let to_u8 = |value: usize| -> u8 { (value.min(max_value) * 255 / max_value) as u8 };
+    if ascii {
+        for _ in 0..sample_count {
+            samples.push(to_u8(number(reader)?));
+        }
+    } else if max_value < 256 {
+        let bytes: Vec<u8> = read_samples(reader, sample_count)?;
+        samples.extend(bytes.iter().map(|&byte| to_u8(byte as usize)));
+    } else {
+        let bytes: Vec<u8> = read_samples(reader, sample_count * 2)?;
+        samples.extend(
+            bytes
+                .chunks_exact(2)
+                .map(|pair| to_u8(u16::from_be_bytes([pair[0], pair[1]]) as usize)),
+        );
+    }
+
+    let (width, height) = (width as u32, height as u32);
+    return Ok(match depth {
+        1 => tga::DynamicImage::Grayscale(to_image(&samples, 1, width, height, vflip)),
+        3 => tga::DynamicImage::Rgb(to_image(&samples, 3, width, height, vflip)),
+        2 => {
+            let expanded: Vec<u8> = samples
+                .chunks_exact(2)
+                .flat_map(|grey_alpha| {
+                    let (grey, alpha) = (grey_alpha[0], grey_alpha[1]);
+                    [grey, grey, grey, alpha]
+                })
+                .collect();
+            tga::DynamicImage::Rgba(to_image(&expanded, 4, width, height, vflip))
+        }
+        _ => tga::DynamicImage::Rgba(to_image(&samples, 4, width, height, vflip)),
+    });
+}
+
+fn to_image<T: PixelChannels>(
+    samples: &[u8],
+    depth: usize,
+    width: u32,
+    height: u32,
+    vflip: bool,
+) -> tga::Image<T> {
+    let mut img: tga::Image<T> = tga::Image::new(width, height);
+    for (y, row) in samples.chunks(width as usize * depth).enumerate() {
+        let row_start: usize = if vflip {
+            (height as usize - 1 - y) * width as usize
+        } else {
+            y * width as usize
+        };
+        for (x, pixel) in row.chunks_exact(depth).enumerate() {
+            img.data[row_start + x] = T::from_channels(pixel);
+        }
+    }
+    return img;
+}
+
+fn read_samples<R: io::BufRead>(reader: &mut R, length: usize) -> io::Result<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::new();
+    reader.take(length as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != length {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "truncated image data",
+        ));
+    }
+    return Ok(bytes);
+}
+
+fn read_pam_header<R: io::BufRead>(reader: &mut R) -> io::Result<(usize, usize, usize, usize)> {
+    let (mut width, mut height, mut depth, mut max_value) = (0usize, 0usize, 0usize, 0usize);
+    loop {
+        let mut line: String = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid_data("unterminated PAM header"));
+        }
+        let mut split = line.split_whitespace();
+        let parse = |value: Option<&str>| -> io::Result<usize> {
+            value
+                .and_then(|value| value.parse::<usize>().ok())
+                .ok_or_else(|| invalid_data("invalid PAM header value"))
+        };
+        match split.next() {
+            Some("WIDTH") => width = parse(split.next())?,
+            Some("HEIGHT") => height = parse(split.next())?,
+            Some("DEPTH") => depth = parse(split.next())?,
+            Some("MAXVAL") => max_value = parse(split.next())?,
+            Some("ENDHDR") => return Ok((width, height, depth, max_value)),
+            // TUPLTYPE is implied by the depth, comments and blank lines are skipped.
+            _ => (),
+        }
+    }
+}
+
+// Next whitespace delimited header token, skipping `#` comments. The whitespace byte ending the
+// token is consumed, which leaves binary readers at the start of the raster.
+fn token<R: io::BufRead>(reader: &mut R) -> io::Result<String> {
+    let mut token: String = String::new();
+    let mut byte = [0u8; 1];
+    loop {
+        reader.read_exact(&mut byte)?;
+        if byte[0] == b'#' && token.is_empty() {
+            let mut comment: Vec<u8> = Vec::new();
+            reader.read_until(b'\n', &mut comment)?;
+            continue;
+        }
+        if byte[0].is_ascii_whitespace() {
+            if token.is_empty() {
+                continue;
+            }
+            return Ok(token);
+        }
+        token.push(byte[0] as char);
+    }
+}
+
+fn number<R: io::BufRead>(reader: &mut R) -> io::Result<usize> {
+    return token(reader)?
+        .parse::<usize>()
+        .map_err(|_| invalid_data("invalid number"));
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+}
@@ -1,7 +1,12 @@
 use super::mesh;
-use std::{fs, str};
+use std::{fs, path::Path, str};
 
 pub fn obj_to_mesh(obj_path: &str) -> mesh::Mesh {
+    return obj_to_polygon_mesh(obj_path).triangulate();
+}
+
+// Keeps every corner of the faces, for the algorithms that work on quads.
+pub fn obj_to_polygon_mesh(obj_path: &str) -> mesh::PolygonMesh {
     let obj_content = fs::read_to_string(obj_path).expect("Cannot read from the obj file.");
 
     let mut v_positions: mesh::Coords3D = mesh::Coords3D {
@@ -18,7 +23,9 @@ pub fn obj_to_mesh(obj_path: &str) -> mesh::Mesh {
         us: Vec::new(),
         vs: Vec::new(),
     };
-    let mut triangles: Vec<mesh::Triangle> = Vec::new();
+    let mut polygons: Vec<mesh::Polygon> = Vec::new();
+    let mut materials: Vec<mesh::Material> = vec![mesh::Material::new("default")];
+    let mut current_material: usize = 0;
 
     for line in obj_content.lines() {
         let mut split: str::SplitWhitespace<'_> = line.split_whitespace();
@@ -27,16 +34,89 @@ pub fn obj_to_mesh(obj_path: &str) -> mesh::Mesh {
             Some("v") => add_3d_coords(&mut v_positions, &mut split),
             Some("vn") => add_3d_coords(&mut v_normals, &mut split),
             Some("vt") => add_2d_coords(&mut v_textures, &mut split),
-            Some("f") => add_triangle(&mut triangles, &mut split),
+            Some("f") => {
+                let counts: [usize; 3] = [
+                    v_positions.xs.len(),
+                    v_textures.us.len(),
+                    v_normals.xs.len(),
+                ];
+                add_polygon(&mut polygons, &mut split, counts, current_material)
+            }
+            Some("mtllib") => {
+                for mtl_name in split {
+                    let mtl_path = Path::new(obj_path).with_file_name(mtl_name);
+                    add_mtl_materials(&mut materials, &mtl_path);
+                }
+            }
+            Some("usemtl") => {
+                current_material = use_material(&mut materials, split.next().unwrap_or("default"))
+            }
             _ => (),
         }
     }
 
-    mesh::Mesh {
+    mesh::PolygonMesh {
         v_positions,
         v_normals,
         v_textures,
-        triangles,
+        polygons,
+        materials,
+    }
+}
+
+fn use_material(materials: &mut Vec<mesh::Material>, name: &str) -> usize {
+    match materials.iter().position(|material| material.name == name) {
+        Some(index) => index,
+        None => {
+            materials.push(mesh::Material::new(name));
+            materials.len() - 1
+        }
+    }
+}
+
+// A missing or unreadable material library is ignored, faces then keep the default material.
+fn add_mtl_materials(materials: &mut Vec<mesh::Material>, mtl_path: &Path) {
+    let mtl_content = match fs::read_to_string(mtl_path) {
+        Ok(mtl_content) => mtl_content,
+        Err(_) => return,
+    };
+
+    let mut current: Option<usize> = None;
+    for line in mtl_content.lines() {
+        let mut split: str::SplitWhitespace<'_> = line.split_whitespace();
+        let indicator: Option<&str> = split.next();
+        match (indicator, current) {
+            (Some("newmtl"), _) => {
+                current = Some(use_material(materials, split.next().unwrap_or("default")))
+            }
+            (Some("Kd"), Some(index)) => {
+                // `Kd spectral` and `Kd xyz` colours are skipped, a single value is a grey.
+                if let Ok(values) = split
+                    .map(str::parse::<f32>)
+                    .collect::<Result<Vec<f32>, _>>()
+                {
+                    let rgb: Option<[f32; 3]> = match *values.as_slice() {
+                        [r, g, b] => Some([r, g, b]),
+                        [grey] => Some([grey; 3]),
+                        _ => None,
+                    };
+                    if let Some([x, y, z]) = rgb {
+                        materials[index].diffuse = mesh::Point3D { x, y, z };
+                    }
+                }
+            }
+            (Some("d"), Some(index)) => {
+                if let Some(Ok(dissolve)) = split.next().map(str::parse::<f32>) {
+                    materials[index].dissolve = dissolve;
+                }
+            }
+            (Some("Tr"), Some(index)) => {
+                if let Some(Ok(transparency)) = split.next().map(str::parse::<f32>) {
+                    materials[index].dissolve = 1f32 - transparency;
+                }
+            }
+            _ => (),
+        }
     }
 }
 
@@ -46,17 +126,14 @@ fn add_3d_coords(
 ) {
     for i in 0..3usize {
         let str_num_opt: Option<&str> = string_num_iterator.next();
-        match str_num_opt {
-            Some(str_num) => {
-                let coord: f32 = str_num.parse::<f32>().unwrap();
-                match i {
-                    0 => to_add_to.xs.push(coord),
-                    1 => to_add_to.ys.push(coord),
-                    2 => to_add_to.zs.push(coord),
-                    _ => {}
-                }
+        if let Some(str_num) = str_num_opt {
+            let coord: f32 = str_num.parse::<f32>().unwrap();
+            match i {
+                0 => to_add_to.xs.push(coord),
+                1 => to_add_to.ys.push(coord),
+                2 => to_add_to.zs.push(coord),
+                _ => {}
             }
-            None => {}
         }
     }
 }
@@ -67,50 +144,57 @@ fn add_2d_coords(
 ) {
     for i in 0..2usize {
         let str_num_opt: Option<&str> = string_num_iterator.next();
-        match str_num_opt {
-            Some(str_num) => {
-                let coord: f32 = str_num.parse::<f32>().unwrap();
-                match i {
-                    0 => to_add_to.us.push(coord),
-                    1 => to_add_to.vs.push(coord),
-                    _ => {}
-                }
+        if let Some(str_num) = str_num_opt {
+            let coord: f32 = str_num.parse::<f32>().unwrap();
+            match i {
+                0 => to_add_to.us.push(coord),
+                1 => to_add_to.vs.push(coord),
+                _ => {}
             }
-            None => {}
         }
     }
 }
 
-fn add_triangle(
-    triangles: &mut Vec<mesh::Triangle>,
+// Missing texture or normal indices default to the first one. Negative indices count back from
+// the last position, UV or normal read so far, given by `counts`. Faces with an index of 0, one
+// that cannot be read or one before the first element are skipped.
+fn add_polygon(
+    polygons: &mut Vec<mesh::Polygon>,
     string_num_iterator: &mut str::SplitWhitespace<'_>,
+    counts: [usize; 3],
+    material: usize,
 ) {
-    let mut vertices: [usize; 3] = [0usize; 3];
-    let mut normals: [usize; 3] = [0usize; 3];
-    let mut textures: [usize; 3] = [0usize; 3];
+    let mut vertices: Vec<usize> = Vec::new();
+    let mut normals: Vec<usize> = Vec::new();
+    let mut textures: Vec<usize> = Vec::new();
 
-    for i in 0..3usize {
-        let coords_concat_opt: Option<&str> = string_num_iterator.next();
-        match coords_concat_opt {
-            Some(coords_concat) => {
-                let coords: Vec<&str> = coords_concat.split("/").collect::<Vec<&str>>();
-                for j in 0..3usize {
-                    let coord: usize = coords[j].parse::<usize>().unwrap();
-                    match j {
-                        0 => vertices[i] = coord - 1,
-                        1 => textures[i] = coord - 1,
-                        2 => normals[i] = coord - 1,
-                        _ => {}
-                    }
-                }
+    for coords_concat in string_num_iterator {
+        let mut corner: [usize; 3] = [0usize; 3];
+        for (j, coord_str) in coords_concat.split("/").enumerate().take(3) {
+            if coord_str.is_empty() {
+                continue;
+            }
+            let index: Option<usize> = match coord_str.parse::<i64>() {
+                Ok(coord) if coord > 0 => Some(coord as usize - 1),
+                Ok(coord) if coord < 0 => counts[j].checked_sub(coord.unsigned_abs() as usize),
+                _ => None,
+            };
+            match index {
+                Some(index) => corner[j] = index,
+                None => return,
             }
-            _ => {}
         }
+        vertices.push(corner[0]);
+        textures.push(corner[1]);
+        normals.push(corner[2]);
     }
-    let tri: mesh::Triangle = mesh::Triangle {
+    if vertices.len() < 3 {
+        return;
+    }
+    polygons.push(mesh::Polygon {
         vertices,
         normals,
         textures,
-    };
-    triangles.push(tri);
+        material,
+    });
 }
@@ -0,0 +1,144 @@
+use crate::blending::{self, BlendMode};
+use crate::tga;
+use std::mem;
+
+const NO_FRAGMENT: u32 = u32::MAX;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Fragment {
+    pub colour: tga::Rgb,
+    pub alpha: f32,
+    // Model space depth, larger values being closer to the viewer.
+    pub depth: f32,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct FragmentNode {
+    fragment: Fragment,
+    next: u32,
+}
+
+// Per-pixel linked lists of translucent fragments, resolved by sorting each list by depth.
+// Fragments are also accumulated for weighted blended OIT, which is used for the resolve instead
+// once the node pool has reached its memory cap.
+pub struct ABuffer {
+    width: u32,
+    height: u32,
+    heads: Vec<u32>,
+    nodes: Vec<FragmentNode>,
+    max_nodes: usize,
+    overflowed: bool,
+    accumulation: Vec<[f32; 4]>,
+    revealage: Vec<f32>,
+}
+
+impl ABuffer {
+    // `memory_cap` is the size in bytes the fragment lists may grow to.
+    pub fn new(width: u32, height: u32, memory_cap: usize) -> Self {
+        let pixel_count: usize = width as usize * height as usize;
+        ABuffer {
+            width,
+            height,
+            heads: vec![NO_FRAGMENT; pixel_count],
+            nodes: Vec::new(),
+            max_nodes: (memory_cap / mem::size_of::<FragmentNode>()).min(NO_FRAGMENT as usize),
+            overflowed: false,
+            accumulation: vec![[0f32; 4]; pixel_count],
+            revealage: vec![1f32; pixel_count],
+        }
+    }
+
+    pub fn overflowed(&self) -> bool {
+        return self.overflowed;
+    }
+
+    pub fn insert(&mut self, x: u32, y: u32, fragment: Fragment) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let offset: usize = y as usize * self.width as usize + x as usize;
+
+        let weight: f32 = weight(fragment.alpha, fragment.depth);
+        let (r, g, b) = (fragment.colour.r, fragment.colour.g, fragment.colour.b);
+        let accumulation: &mut [f32; 4] = &mut self.accumulation[offset];
+        accumulation[0] += r as f32 / 255f32 * fragment.alpha * weight;
+        accumulation[1] += g as f32 / 255f32 * fragment.alpha * weight;
+        accumulation[2] += b as f32 / 255f32 * fragment.alpha * weight;
+        accumulation[3] += fragment.alpha * weight;
+        self.revealage[offset] *= 1f32 - fragment.alpha;
+
+        if self.overflowed {
+            return;
+        }
+        if self.nodes.len() >= self.max_nodes {
+            self.overflowed = true;
+            self.nodes = Vec::new();
+            return;
+        }
+        self.nodes.push(FragmentNode {
+            fragment,
+            next: self.heads[offset],
+        });
+        self.heads[offset] = (self.nodes.len() - 1) as u32;
+    }
+
+    // Composites the stored fragments over `img`, which must have the dimensions of the buffer.
+    pub fn resolve(&self, img: &mut tga::Image<tga::Rgba>) {
+        let mut pixel_fragments: Vec<Fragment> = Vec::new();
+        for offset in 0..self.heads.len() {
+            if self.overflowed {
+                img.data[offset] = self.resolve_weighted(offset, img.data[offset]);
+                continue;
+            }
+
+            pixel_fragments.clear();
+            let mut node: u32 = self.heads[offset];
+            while node != NO_FRAGMENT {
+                pixel_fragments.push(self.nodes[node as usize].fragment);
+                node = self.nodes[node as usize].next;
+            }
+            pixel_fragments.sort_by(|a, b| a.depth.total_cmp(&b.depth));
+            for fragment in &pixel_fragments {
+                img.data[offset] = blending::blend(
+                    to_rgba(fragment.colour, fragment.alpha),
+                    img.data[offset],
+                    BlendMode::AlphaOver,
+                );
+            }
+        }
+    }
+
+    fn resolve_weighted(&self, offset: usize, background: tga::Rgba) -> tga::Rgba {
+        let accumulation: [f32; 4] = self.accumulation[offset];
+        if accumulation[3] <= 0f32 {
+            return background;
+        }
+        let channel = |c: f32| -> u8 { (c / accumulation[3] * 255f32).clamp(0f32, 255f32) as u8 };
+        let average: tga::Rgb = tga::Rgb {
+            b: channel(accumulation[2]),
+            g: channel(accumulation[1]),
+            r: channel(accumulation[0]),
+        };
+        return blending::blend(
+            to_rgba(average, 1f32 - self.revealage[offset]),
+            background,
+            BlendMode::AlphaOver,
+        );
+    }
+}
+
+// Depth weight from McGuire and Bavoil's weighted blended OIT, favouring fragments closer to the
+// viewer. Depths are expected in [-1, 1].
+fn weight(alpha: f32, depth: f32) -> f32 {
+    let distance: f32 = ((1f32 - depth) / 2f32).clamp(0f32, 1f32);
+    return alpha * (0.03f32 / (1e-5f32 + distance.powi(4))).clamp(1e-2f32, 3e3f32);
+}
+
+fn to_rgba(colour: tga::Rgb, alpha: f32) -> tga::Rgba {
+    return tga::Rgba {
+        b: colour.b,
+        g: colour.g,
+        r: colour.r,
+        a: (alpha * 255f32).clamp(0f32, 255f32) as u8,
+    };
+}
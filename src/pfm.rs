@@ -0,0 +1,102 @@
+use crate::tga;
+use std::io::{self, prelude::*};
+use std::mem;
+
+// Portable FloatMap (.pfm) reading and writing. PFM stores rows from the bottom of the picture
+// up, so with `vflip` the image rows are kept in file order.
+
+pub fn write<W: io::Write>(
+    img: &tga::Image<tga::RgbF32>,
+    writer: &mut io::BufWriter<W>,
+    vflip: bool,
+) -> io::Result<()> {
+    // A negative scale marks little endian samples.
+    write!(writer, "PF\n{} {}\n-1.0\n", img.width, img.height)?;
+    let width: usize = img.width as usize;
+    for y in 0..img.height as usize {
+        let row: usize = if vflip {
+            y
+        } else {
+            img.height as usize - 1 - y
+        };
+        for pixel in &img.data[row * width..(row + 1) * width] {
+            writer.write_all(&pixel.r.to_le_bytes())?;
+            writer.write_all(&pixel.g.to_le_bytes())?;
+            writer.write_all(&pixel.b.to_le_bytes())?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// Greyscale `Pf` files are expanded to RgbF32.
+pub fn read<R: io::BufRead>(reader: &mut R, vflip: bool) -> io::Result<tga::Image<tga::RgbF32>> {
+    let channels: usize = match header_token(reader)?.as_str() {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return Err(invalid_data("missing PFM signature")),
+    };
+    let width: u32 = header_token(reader)?
+        .parse::<u32>()
+        .map_err(|_| invalid_data("invalid width"))?;
+    let height: u32 = header_token(reader)?
+        .parse::<u32>()
+        .map_err(|_| invalid_data("invalid height"))?;
+    let scale: f32 = header_token(reader)?
+        .parse::<f32>()
+        .map_err(|_| invalid_data("invalid scale"))?;
+    let little_endian: bool = scale < 0f32;
+    let pixel_size: usize = mem::size_of::<tga::RgbF32>();
+    if tga::decoded_size(width as usize, height as usize, pixel_size).is_err() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            tga::Error::InvalidDimensions.to_string(),
+        ));
+    }
+
+    let mut img: tga::Image<tga::RgbF32> = tga::Image::new(width, height);
+    let mut sample = [0u8; 4];
+    let mut samples = [0f32; 3];
+    for y in 0..height as usize {
+        let row: usize = if vflip { y } else { height as usize - 1 - y };
+        for x in 0..width as usize {
+            for value in samples.iter_mut().take(channels) {
+                reader.read_exact(&mut sample)?;
+                *value = if little_endian {
+                    f32::from_le_bytes(sample)
+                } else {
+                    f32::from_be_bytes(sample)
+                };
+            }
+            if channels == 1 {
+                samples = [samples[0]; 3];
+            }
+            img.data[row * width as usize + x] = tga::RgbF32 {
+                r: samples[0],
+                g: samples[1],
+                b: samples[2],
+            };
+        }
+    }
+    return Ok(img);
+}
+
+// Reads a whitespace delimited header token, consuming the single whitespace byte after it.
+fn header_token<R: io::BufRead>(reader: &mut R) -> io::Result<String> {
+    let mut token: String = String::new();
+    let mut byte = [0u8; 1];
+    loop {
+        reader.read_exact(&mut byte)?;
+        if byte[0].is_ascii_whitespace() {
+            if token.is_empty() {
+                continue;
+            }
+            return Ok(token);
+        }
+        token.push(byte[0] as char);
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+}
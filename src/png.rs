@@ -0,0 +1,513 @@
+use crate::tga::{self, PixelChannels};
+use crate::zlib;
+use std::io::{self, prelude::*};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+const MAX_IDAT_LENGTH: usize = 1 << 20;
+
+impl<T: PixelChannels> tga::Image<T> {
+    // Same `vflip` convention as `write`: the first row of the image is the bottom of the
+    // picture.
+    pub fn write_png<W: io::Write>(
+        &self,
+        writer: &mut io::BufWriter<W>,
+        vflip: bool,
+    ) -> io::Result<()> {
+        let mut ihdr: Vec<u8> = Vec::with_capacity(13);
+        ihdr.extend_from_slice(&self.width.to_be_bytes());
+        ihdr.extend_from_slice(&self.height.to_be_bytes());
+        // Bit depth, colour type, deflate compression, adaptive filtering, no interlace.
+        ihdr.extend_from_slice(&[8, color_type(T::BPP), 0, 0, 0]);
+
+        let compressed: Vec<u8> = zlib::compress(&self.filtered_scanlines(vflip));
+
+        writer.write_all(&SIGNATURE)?;
+        write_chunk(writer, b"IHDR", &ihdr)?;
+        for idat in compressed.chunks(MAX_IDAT_LENGTH) {
+            write_chunk(writer, b"IDAT", idat)?;
+        }
+        write_chunk(writer, b"IEND", &[])?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    // Scanlines prefixed with their filter type, the filter being picked per row as the one
+    // minimising the sum of absolute filtered values.
+    fn filtered_scanlines(&self, vflip: bool) -> Vec<u8> {
+        let bytes_per_pixel: usize = T::BPP as usize;
+        let stride: usize = self.width as usize * bytes_per_pixel;
+        let mut filtered: Vec<u8> = Vec::with_capacity((stride + 1) * self.height as usize);
+        let mut previous: Vec<u8> = vec![0u8; stride];
+        let mut current: Vec<u8> = Vec::with_capacity(stride);
+        let mut candidate: Vec<u8> = vec![0u8; stride];
+        let mut best: Vec<u8> = vec![0u8; stride];
+
+        for y in 0..self.height as usize {
+            let row: usize = if vflip {
+                self.height as usize - 1 - y
+            } else {
+                y
+            };
+            current.clear();
+            for pixel in &self.data[row * self.width as usize..(row + 1) * self.width as usize] {
+                pixel.push_channels(&mut current);
+            }
+
+            let mut best_filter: u8 = 0;
+            let mut best_score: u64 = u64::MAX;
+            for filter in 0..5u8 {
+                apply_filter(filter, &current, &previous, bytes_per_pixel, &mut candidate);
+                let score: u64 = candidate
+                    .iter()
+                    .map(|&byte| (byte as i8).unsigned_abs() as u64)
+                    .sum();
+                if score < best_score {
+                    best_score = score;
+                    best_filter = filter;
+                    best.copy_from_slice(&candidate);
+                }
+            }
+            filtered.push(best_filter);
+            filtered.extend_from_slice(&best);
+            std::mem::swap(&mut previous, &mut current);
+        }
+        return filtered;
+    }
+}
+
+fn color_type(bytes_per_pixel: u8) -> u8 {
+    return match bytes_per_pixel {
+        1 => 0,
+        3 => 2,
+        _ => 6,
+    };
+}
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+    return crc32_update(0xffffffff, bytes) ^ 0xffffffff;
+}
+
+fn crc32_update(mut crc: u32, bytes: &[u8]) -> u32 {
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                0xedb88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    return crc;
+}
+
+fn write_chunk<W: io::Write>(
+    writer: &mut io::BufWriter<W>,
+    chunk_type: &[u8; 4],
+    data: &[u8],
+) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(chunk_type)?;
+    writer.write_all(data)?;
+    let crc: u32 = crc32_update(crc32_update(0xffffffff, chunk_type), data) ^ 0xffffffff;
+    writer.write_all(&crc.to_be_bytes())?;
+    Ok(())
+}
+
+fn apply_filter(
+    filter: u8,
+    current: &[u8],
+    previous: &[u8],
+    bytes_per_pixel: usize,
+    filtered: &mut [u8],
+) {
+    for i in 0..current.len() {
+        let left: u8 = if i >= bytes_per_pixel {
+            current[i - bytes_per_pixel]
+        } else {
+            0
+        };
+        let up: u8 = previous[i];
+        let up_left: u8 = if i >= bytes_per_pixel {
+            previous[i - bytes_per_pixel]
+        } else {
+            0
+        };
+        let predictor: u8 = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            _ => paeth_predictor(left, up, up_left),
+        };
+        filtered[i] = current[i].wrapping_sub(predictor);
+    }
+}
+
+pub(crate) fn paeth_predictor(left: u8, up: u8, up_left: u8) -> u8 {
+    let p: i16 = left as i16 + up as i16 - up_left as i16;
+    let distance_left: i16 = (p - left as i16).abs();
+    let distance_up: i16 = (p - up as i16).abs();
+    let distance_up_left: i16 = (p - up_left as i16).abs();
+    return if distance_left <= distance_up && distance_left <= distance_up_left {
+        left
+    } else if distance_up <= distance_up_left {
+        up
+    } else {
+        up_left
+    };
+}
+
+// Interlacing passes as (x start, y start, x step, y step).
+const ADAM7_PASSES: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+struct Header {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn channels(&self) -> usize {
+        return match self.color_type {
+            0 | 3 => 1,
+            4 => 2,
+            2 => 3,
+            _ => 4,
+        };
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        return self.channels() * self.bit_depth as usize;
+    }
+
+    fn passes(&self) -> &'static [(usize, usize, usize, usize)] {
+        return if self.interlaced {
+            &ADAM7_PASSES
+        } else {
+            &[(0, 0, 1, 1)]
+        };
+    }
+
+    // Pass width, height and row stride without the filter byte.
+    fn pass_size(&self, pass: &(usize, usize, usize, usize)) -> (usize, usize, usize) {
+        let &(start_x, start_y, step_x, step_y) = pass;
+        let pass_width: usize = (self.width + step_x - 1 - start_x) / step_x;
+        let pass_height: usize = (self.height + step_y - 1 - start_y) / step_y;
+        let stride: usize = (pass_width * self.bits_per_pixel()).div_ceil(8);
+        return (pass_width, pass_height, stride);
+    }
+
+    // Size of the decompressed image data, a filter byte starting every row of the non-empty
+    // passes. Cannot overflow, the dimensions being bounded by `parse_header`.
+    fn filtered_size(&self) -> usize {
+        return self
+            .passes()
+            .iter()
+            .map(|pass| self.pass_size(pass))
+            .filter(|&(pass_width, _, _)| pass_width > 0)
+            .map(|(_, pass_height, stride)| pass_height * (1 + stride))
+            .sum();
+    }
+}
+
+// Decodes any standard PNG: all colour types and bit depths, palettes, tRNS transparency and
+// Adam7 interlacing. 16 bit samples are reduced to 8 bits. Greyscale images without
+// transparency decode to `Grayscale`, colour ones without transparency to `Rgb`, and everything
+// else to `Rgba`. With `vflip`, the first image row is the bottom of the picture.
+pub fn read<R: io::Read>(reader: &mut R, vflip: bool) -> io::Result<tga::DynamicImage> {
+    let mut signature = [0u8; 8];
+    reader.read_exact(&mut signature)?;
+    if signature != SIGNATURE {
+        return Err(invalid_data("missing PNG signature"));
+    }
+
+    let mut header: Option<Header> = None;
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    let mut transparency: Vec<u8> = Vec::new();
+    let mut compressed: Vec<u8> = Vec::new();
+    loop {
+        let (chunk_type, data) = read_chunk(reader)?;
+        match &chunk_type {
+            b"IHDR" => header = Some(parse_header(&data)?),
+            b"PLTE" => {
+                palette = data
+                    .chunks_exact(3)
+                    .map(|rgb| [rgb[0], rgb[1], rgb[2]])
+                    .collect()
+            }
+            b"tRNS" => transparency = data,
+            b"IDAT" => compressed.extend_from_slice(&data),
+            b"IEND" => break,
+            // Ancillary chunks are skipped, unknown critical ones cannot be.
+            _ if chunk_type[0] & 0x20 == 0 => {
+                return Err(invalid_data("unsupported critical chunk"))
+            }
+            _ => (),
+        }
+    }
+    let header: Header = header.ok_or_else(|| invalid_data("missing IHDR chunk"))?;
+    if header.color_type == 3 && palette.is_empty() {
+        return Err(invalid_data("missing PLTE chunk"));
+    }
+
+    let decompressed: Vec<u8> = zlib::decompress(&compressed, header.filtered_size())?;
+    let pixels: Vec<[u8; 4]> = decode_pixels(&header, &decompressed, &palette, &transparency)?;
+
+    let has_alpha: bool = matches!(header.color_type, 4 | 6) || !transparency.is_empty();
+    let is_grey: bool = matches!(header.color_type, 0 | 4);
+    return Ok(if is_grey && !has_alpha {
+        tga::DynamicImage::Grayscale(to_image(&header, &pixels, vflip))
+    } else if !has_alpha {
+        tga::DynamicImage::Rgb(to_image(&header, &pixels, vflip))
+    } else {
+        tga::DynamicImage::Rgba(to_image(&header, &pixels, vflip))
+    });
+}
+
+fn to_image<T: PixelChannels>(header: &Header, pixels: &[[u8; 4]], vflip: bool) -> tga::Image<T> {
+    let mut img: tga::Image<T> = tga::Image::new(header.width as u32, header.height as u32);
+    for (y, row) in pixels.chunks(header.width).enumerate() {
+        let row_start: usize = if vflip {
+            (header.height - 1 - y) * header.width
+        } else {
+            y * header.width
+        };
+        for (x, pixel) in row.iter().enumerate() {
+            img.data[row_start + x] = T::from_channels(&pixel[..T::BPP as usize]);
+        }
+    }
+    return img;
+}
+
+fn read_chunk<R: io::Read>(reader: &mut R) -> io::Result<([u8; 4], Vec<u8>)> {
+    let mut length = [0u8; 4];
+    reader.read_exact(&mut length)?;
+    let mut chunk_type = [0u8; 4];
+    reader.read_exact(&mut chunk_type)?;
+    // The length is not trusted for the allocation, a truncated stream fails at the end.
+    let length: u64 = u32::from_be_bytes(length) as u64;
+    let mut data: Vec<u8> = Vec::new();
+    reader.by_ref().take(length).read_to_end(&mut data)?;
+    if data.len() as u64 != length {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "truncated chunk",
+        ));
+    }
+    let mut crc = [0u8; 4];
+    reader.read_exact(&mut crc)?;
+    if u32::from_be_bytes(crc)
+        != crc32_update(crc32_update(0xffffffff, &chunk_type), &data) ^ 0xffffffff
+    {
+        return Err(invalid_data("chunk CRC mismatch"));
+    }
+    return Ok((chunk_type, data));
+}
+
+fn parse_header(data: &[u8]) -> io::Result<Header> {
+    if data.len() != 13 {
+        return Err(invalid_data("invalid IHDR length"));
+    }
+    let width: u32 = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+    let height: u32 = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+    if width == 0
+        || height == 0
+        || width > i32::MAX as u32
+        || height > i32::MAX as u32
+        || tga::decoded_size(width as usize, height as usize, 4).is_err()
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            tga::Error::InvalidDimensions.to_string(),
+        ));
+    }
+    let (bit_depth, color_type) = (data[8], data[9]);
+    let valid_depths: &[u8] = match color_type {
+        0 => &[1, 2, 4, 8, 16],
+        3 => &[1, 2, 4, 8],
+        2 | 4 | 6 => &[8, 16],
+        _ => return Err(invalid_data("invalid colour type")),
+    };
+    if !valid_depths.contains(&bit_depth) {
+        return Err(invalid_data("invalid bit depth"));
+    }
+    if data[10] != 0 || data[11] != 0 || data[12] > 1 {
+        return Err(invalid_data(
+            "unsupported compression, filter or interlace method",
+        ));
+    }
+    return Ok(Header {
+        width: width as usize,
+        height: height as usize,
+        bit_depth,
+        color_type,
+        interlaced: data[12] == 1,
+    });
+}
+
+// Unfilters every pass and expands the samples to top-down RGBA8 pixels.
+fn decode_pixels(
+    header: &Header,
+    data: &[u8],
+    palette: &[[u8; 3]],
+    transparency: &[u8],
+) -> io::Result<Vec<[u8; 4]>> {
+    let filter_bytes_per_pixel: usize = (header.bits_per_pixel() / 8).max(1);
+    if data.len() < header.filtered_size() {
+        return Err(invalid_data("truncated image data"));
+    }
+
+    let mut pixels: Vec<[u8; 4]> = vec![[0u8; 4]; header.width * header.height];
+    let mut offset: usize = 0;
+    for pass in header.passes() {
+        let &(start_x, start_y, step_x, step_y) = pass;
+        let (pass_width, pass_height, stride) = header.pass_size(pass);
+        if pass_width == 0 || pass_height == 0 {
+            continue;
+        }
+        let mut previous: Vec<u8> = vec![0u8; stride];
+        let mut current: Vec<u8> = vec![0u8; stride];
+        for y in 0..pass_height {
+            let row: &[u8] = data
+                .get(offset..offset + 1 + stride)
+                .ok_or_else(|| invalid_data("truncated image data"))?;
+            offset += 1 + stride;
+            unfilter(
+                row[0],
+                &row[1..],
+                &previous,
+                filter_bytes_per_pixel,
+                &mut current,
+            )?;
+            for x in 0..pass_width {
+                let pixel: [u8; 4] = expand_pixel(header, &current, x, palette, transparency)?;
+                pixels[(start_y + y * step_y) * header.width + start_x + x * step_x] = pixel;
+            }
+            std::mem::swap(&mut previous, &mut current);
+        }
+    }
+    return Ok(pixels);
+}
+
+fn unfilter(
+    filter: u8,
+    filtered: &[u8],
+    previous: &[u8],
+    bytes_per_pixel: usize,
+    current: &mut [u8],
+) -> io::Result<()> {
+    for i in 0..filtered.len() {
+        let left: u8 = if i >= bytes_per_pixel {
+            current[i - bytes_per_pixel]
+        } else {
+            0
+        };
+        let up: u8 = previous[i];
+        let up_left: u8 = if i >= bytes_per_pixel {
+            previous[i - bytes_per_pixel]
+        } else {
+            0
+        };
+        let predictor: u8 = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => paeth_predictor(left, up, up_left),
+            _ => return Err(invalid_data("invalid filter type")),
+        };
+        current[i] = filtered[i].wrapping_add(predictor);
+    }
+    Ok(())
+}
+
+fn expand_pixel(
+    header: &Header,
+    row: &[u8],
+    x: usize,
+    palette: &[[u8; 3]],
+    transparency: &[u8],
+) -> io::Result<[u8; 4]> {
+    let depth: usize = header.bit_depth as usize;
+    let channels: usize = header.channels();
+    let sample = |channel: usize| -> u16 {
+        let bit: usize = (x * channels + channel) * depth;
+        return match depth {
+            16 => u16::from_be_bytes([row[bit / 8], row[bit / 8 + 1]]),
+            8 => row[bit / 8] as u16,
+            _ => ((row[bit / 8] >> (8 - depth - bit % 8)) & ((1u8 << depth) - 1)) as u16,
+        };
+    };
+    let to_u8 = |value: u16| -> u8 {
+        return match depth {
+            16 => (value >> 8) as u8,
+            8 => value as u8,
+            _ => (value as u32 * 255 / ((1u32 << depth) - 1)) as u8,
+        };
+    };
+    // tRNS holds 16 bit samples for greyscale and truecolour images.
+    let transparent_key = |channel: usize| -> Option<u16> {
+        return transparency
+            .get(channel * 2..channel * 2 + 2)
+            .map(|key| u16::from_be_bytes([key[0], key[1]]));
+    };
+
+    return Ok(match header.color_type {
+        0 => {
+            let grey: u16 = sample(0);
+            let alpha: u8 = if transparent_key(0) == Some(grey) {
+                0
+            } else {
+                255
+            };
+            [to_u8(grey), to_u8(grey), to_u8(grey), alpha]
+        }
+        2 => {
+            let rgb: [u16; 3] = [sample(0), sample(1), sample(2)];
+            let is_key: bool = (0..3usize).all(|c| transparent_key(c) == Some(rgb[c]));
+            [
+                to_u8(rgb[0]),
+                to_u8(rgb[1]),
+                to_u8(rgb[2]),
+                if is_key { 0 } else { 255 },
+            ]
+        }
+        3 => {
+            let index: usize = sample(0) as usize;
+            let rgb: [u8; 3] = *palette
+                .get(index)
+                .ok_or_else(|| invalid_data("palette index out of range"))?;
+            [
+                rgb[0],
+                rgb[1],
+                rgb[2],
+                transparency.get(index).copied().unwrap_or(255),
+            ]
+        }
+        4 => {
+            let grey: u8 = to_u8(sample(0));
+            [grey, grey, grey, to_u8(sample(1))]
+        }
+        _ => [
+            to_u8(sample(0)),
+            to_u8(sample(1)),
+            to_u8(sample(2)),
+            to_u8(sample(3)),
+        ],
+    });
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+}
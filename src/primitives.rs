@@ -0,0 +1,497 @@
+use crate::linear_algebra;
+use crate::mesh::{Coords2D, Coords3D, Material, Mesh, Point3D, Triangle};
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+// Procedural meshes, centred on the origin with y up unless stated otherwise. Triangles are
+// counter-clockwise seen from outside and the `segments`/`rings`/`subdivisions` parameters set
+// the tessellation density. Every corner has its own normal and UV, while corners at the same
+// position share their vertex, so that closed shapes are closed meshes with seams in their
+// attributes only.
+
+struct MeshBuilder {
+    mesh: Mesh,
+    // Vertex of each corner, and the vertex at each position.
+    corner_vertices: Vec<usize>,
+    vertices: HashMap<[u32; 3], usize>,
+}
+
+impl MeshBuilder {
+    fn new() -> Self {
+        return MeshBuilder {
+            mesh: Mesh {
+                v_positions: Coords3D {
+                    xs: Vec::new(),
+                    ys: Vec::new(),
+                    zs: Vec::new(),
+                },
+                v_normals: Coords3D {
+                    xs: Vec::new(),
+                    ys: Vec::new(),
+                    zs: Vec::new(),
+                },
+                v_textures: Coords2D {
+                    us: Vec::new(),
+                    vs: Vec::new(),
+                },
+                triangles: Vec::new(),
+                materials: vec![Material::new("default")],
+            },
+            corner_vertices: Vec::new(),
+            vertices: HashMap::new(),
+        };
+    }
+
+    // Normal and UV share the returned corner index, the position is shared with the previous
+    // corners at exactly the same place.
+    fn corner(&mut self, position: Point3D<f32>, normal: Point3D<f32>, uv: (f32, f32)) -> usize {
+        let normal: Point3D<f32> = linear_algebra::normalized(normal);
+        self.mesh.v_normals.add_vector(normal.x, normal.y, normal.z);
+        self.mesh.v_textures.add_vector(uv.0, uv.1);
+        // Adding zero turns -0 into 0.
+        let key: [u32; 3] = [position.x, position.y, position.z].map(|c| (c + 0f32).to_bits());
+        let positions: &mut Coords3D = &mut self.mesh.v_positions;
+        let vertex: usize = *self.vertices.entry(key).or_insert_with(|| {
+            positions.add_vector(position.x, position.y, position.z);
+            return positions.xs.len() - 1;
+        });
+        self.corner_vertices.push(vertex);
+        return self.corner_vertices.len() - 1;
+    }
+
+    // Triangles collapsed onto a pole, apex or centre are dropped.
+    fn triangle(&mut self, a: usize, b: usize, c: usize) {
+        let vertices: [usize; 3] = [a, b, c].map(|corner| self.corner_vertices[corner]);
+        if vertices[0] == vertices[1] || vertices[1] == vertices[2] || vertices[2] == vertices[0] {
+            return;
+        }
+        self.mesh.triangles.push(Triangle {
+            vertices,
+            normals: [a, b, c],
+            textures: [a, b, c],
+            material: 0,
+        });
+    }
+
+    // (columns + 1) x (rows + 1) corners from `surface(column, row)`, which returns the
+    // position, normal and UV. Quads face the side of d/dcolumn x d/drow, or the other one when
+    // `flip` is set.
+    fn grid<F: Fn(u32, u32) -> (Point3D<f32>, Point3D<f32>, (f32, f32))>(
+        &mut self,
+        columns: u32,
+        rows: u32,
+        flip: bool,
+        surface: F,
+    ) {
+        let first: usize = self.corner_vertices.len();
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let (position, normal, uv) = surface(column, row);
+                self.corner(position, normal, uv);
+            }
+        }
+        let index = |column: u32, row: u32| -> usize {
+            return first + (row * (columns + 1) + column) as usize;
+        };
+        for row in 0..rows {
+            for column in 0..columns {
+                let (a, b) = (index(column, row), index(column + 1, row));
+                let (c, d) = (index(column + 1, row + 1), index(column, row + 1));
+                if flip {
+                    self.triangle(a, c, b);
+                    self.triangle(a, d, c);
+                } else {
+                    self.triangle(a, b, c);
+                    self.triangle(a, c, d);
+                }
+            }
+        }
+    }
+
+    // Horizontal ring between two radii at height y, facing up or down. An inner radius of zero
+    // makes a disc around a single centre vertex.
+    fn annulus(&mut self, y: f32, inner: f32, outer: f32, segments: u32, rings: u32, up: bool) {
+        let normal: Point3D<f32> = point(0f32, if up { 1f32 } else { -1f32 }, 0f32);
+        self.grid(segments, rings, up, |column, row| {
+            let (sin, cos) = angle(column, segments).sin_cos();
+            // Exact at both radii, so that the edges match the neighbouring surfaces.
+            let t: f32 = fraction(row, rings);
+            let radius: f32 = inner * (1f32 - t) + outer * t;
+            let uv: (f32, f32) = (
+                0.5f32 + 0.5f32 * sin * radius / outer,
+                0.5f32 + 0.5f32 * cos * radius / outer,
+            );
+            return (point(radius * sin, y, radius * cos), normal, uv);
+        });
+    }
+
+    fn build(self) -> Mesh {
+        return self.mesh;
+    }
+}
+
+fn point(x: f32, y: f32, z: f32) -> Point3D<f32> {
+    return Point3D { x, y, z };
+}
+
+fn fraction(i: u32, count: u32) -> f32 {
+    return i as f32 / count as f32;
+}
+
+// From -1 at 0 to 1 at `count`, with i and count - i giving exactly opposite values.
+fn centred_fraction(i: u32, count: u32) -> f32 {
+    return (2 * i as i64 - count as i64) as f32 / count as f32;
+}
+
+// Angle around the y axis, starting from +z and going towards +x. The last column wraps back to
+// exactly the first one.
+fn angle(column: u32, segments: u32) -> f32 {
+    return TAU * fraction(column % segments, segments);
+}
+
+// Latitude going from -pi/2 at row 0 to pi/2 at the last row, exact at the poles so that their
+// triangles collapse.
+fn latitude_sin_cos(row: u32, rows: u32) -> (f32, f32) {
+    if row == 0 {
+        return (-1f32, 0f32);
+    }
+    if row == rows {
+        return (1f32, 0f32);
+    }
+    return (-FRAC_PI_2 + PI * fraction(row, rows)).sin_cos();
+}
+
+// Axis aligned cube of side `size`, each face split into `segments` x `segments` quads.
+pub fn cube(size: f32, segments: u32) -> Mesh {
+    let segments: u32 = segments.max(1);
+    let half: f32 = size / 2f32;
+    // Normal, then the face's u and v axes, with u x v = normal.
+    let faces: [[[f32; 3]; 3]; 6] = [
+        [[1f32, 0f32, 0f32], [0f32, 0f32, -1f32], [0f32, 1f32, 0f32]],
+        [[-1f32, 0f32, 0f32], [0f32, 0f32, 1f32], [0f32, 1f32, 0f32]],
+        [[0f32, 1f32, 0f32], [1f32, 0f32, 0f32], [0f32, 0f32, -1f32]],
+        [[0f32, -1f32, 0f32], [1f32, 0f32, 0f32], [0f32, 0f32, 1f32]],
+        [[0f32, 0f32, 1f32], [1f32, 0f32, 0f32], [0f32, 1f32, 0f32]],
+        [[0f32, 0f32, -1f32], [-1f32, 0f32, 0f32], [0f32, 1f32, 0f32]],
+    ];
+    let mut builder: MeshBuilder = MeshBuilder::new();
+    for [n, u, v] in faces {
+        builder.grid(segments, segments, false, |column, row| {
+            let (s, t) = (fraction(column, segments), fraction(row, segments));
+            let a: f32 = half * centred_fraction(column, segments);
+            let b: f32 = half * centred_fraction(row, segments);
+            let position: Point3D<f32> = point(
+                n[0] * half + a * u[0] + b * v[0],
+                n[1] * half + a * u[1] + b * v[1],
+                n[2] * half + a * u[2] + b * v[2],
+            );
+            return (position, point(n[0], n[1], n[2]), (s, t));
+        });
+    }
+    return builder.build();
+}
+
+// Latitude/longitude sphere, with `rings` bands from pole to pole.
+pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> Mesh {
+    let (segments, rings) = (segments.max(3), rings.max(2));
+    let mut builder: MeshBuilder = MeshBuilder::new();
+    builder.grid(segments, rings, false, |column, row| {
+        let (sin_phi, cos_phi) = angle(column, segments).sin_cos();
+        let (sin_lat, cos_lat) = latitude_sin_cos(row, rings);
+        let normal: Point3D<f32> = point(cos_lat * sin_phi, sin_lat, cos_lat * cos_phi);
+        let position: Point3D<f32> = point(radius * normal.x, radius * normal.y, radius * normal.z);
+        return (
+            position,
+            normal,
+            (fraction(column, segments), fraction(row, rings)),
+        );
+    });
+    return builder.build();
+}
+
+// Icosahedron whose faces are split in four `subdivisions` times, then pushed onto the sphere.
+// UVs are the spherical coordinates, with the corners split along the -z meridian so that the
+// triangles crossing it do not wrap around, and at the poles.
+pub fn icosphere(radius: f32, subdivisions: u32) -> Mesh {
+    let t: f32 = (1f32 + 5f32.sqrt()) / 2f32;
+    let mut directions: Vec<Point3D<f32>> = [
+        [-1f32, t, 0f32],
+        [1f32, t, 0f32],
+        [-1f32, -t, 0f32],
+        [1f32, -t, 0f32],
+        [0f32, -1f32, t],
+        [0f32, 1f32, t],
+        [0f32, -1f32, -t],
+        [0f32, 1f32, -t],
+        [t, 0f32, -1f32],
+        [t, 0f32, 1f32],
+        [-t, 0f32, -1f32],
+        [-t, 0f32, 1f32],
+    ]
+    .iter()
+    .map(|&[x, y, z]| linear_algebra::normalized(point(x, y, z)))
+    .collect();
+    let mut faces: Vec<[usize; 3]> = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut midpoint = |a: usize, b: usize, directions: &mut Vec<Point3D<f32>>| -> usize {
+            let key: (usize, usize) = (a.min(b), a.max(b));
+            if let Some(&index) = midpoints.get(&key) {
+                return index;
+            }
+            let (p, q) = (directions[a], directions[b]);
+            directions.push(linear_algebra::normalized(point(
+                p.x + q.x,
+                p.y + q.y,
+                p.z + q.z,
+            )));
+            midpoints.insert(key, directions.len() - 1);
+            return directions.len() - 1;
+        };
+        let mut subdivided: Vec<[usize; 3]> = Vec::with_capacity(faces.len() * 4);
+        for [a, b, c] in faces {
+            let ab: usize = midpoint(a, b, &mut directions);
+            let bc: usize = midpoint(b, c, &mut directions);
+            let ca: usize = midpoint(c, a, &mut directions);
+            subdivided.extend([[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]);
+        }
+        faces = subdivided;
+    }
+
+    let mut builder: MeshBuilder = MeshBuilder::new();
+    let uvs: Vec<(f32, f32)> = directions
+        .iter()
+        .map(|direction| {
+            return (
+                0.5f32 + direction.x.atan2(direction.z) / TAU,
+                0.5f32 + direction.y.clamp(-1f32, 1f32).asin() / PI,
+            );
+        })
+        .collect();
+    // Corners by direction and u, the first one of each direction having its own u.
+    let mut corners: HashMap<(usize, u32), usize> = HashMap::new();
+    for [a, b, c] in faces {
+        let mut us: [f32; 3] = [a, b, c].map(|i| uvs[i].0);
+        let poles: [bool; 3] =
+            [a, b, c].map(|i| directions[i].x == 0f32 && directions[i].z == 0f32);
+        // Corners on the far side of the seam move past u = 1.
+        let (mut min_u, mut max_u) = (f32::INFINITY, f32::NEG_INFINITY);
+        for k in (0..3).filter(|&k| !poles[k]) {
+            (min_u, max_u) = (min_u.min(us[k]), max_u.max(us[k]));
+        }
+        if max_u - min_u > 0.5f32 {
+            for u in us.iter_mut().filter(|u| **u < 0.5f32) {
+                *u += 1f32;
+            }
+        }
+        // A pole takes the u of the middle of its triangle's opposite edge.
+        for k in (0..3).filter(|&k| poles[k]) {
+            us[k] = (us[(k + 1) % 3] + us[(k + 2) % 3]) / 2f32;
+        }
+        let [a, b, c] = [(0, a), (1, b), (2, c)].map(|(k, i)| {
+            return *corners.entry((i, us[k].to_bits())).or_insert_with(|| {
+                let position: Point3D<f32> = point(
+                    radius * directions[i].x,
+                    radius * directions[i].y,
+                    radius * directions[i].z,
+                );
+                return builder.corner(position, directions[i], (us[k], uvs[i].1));
+            });
+        });
+        builder.triangle(a, b, c);
+    }
+    return builder.build();
+}
+
+// Capped cylinder along y, with `height_segments` bands on its side.
+pub fn cylinder(radius: f32, height: f32, segments: u32, height_segments: u32) -> Mesh {
+    let (segments, height_segments) = (segments.max(3), height_segments.max(1));
+    let half: f32 = height / 2f32;
+    let mut builder: MeshBuilder = MeshBuilder::new();
+    builder.grid(segments, height_segments, false, |column, row| {
+        let (sin, cos) = angle(column, segments).sin_cos();
+        let v: f32 = fraction(row, height_segments);
+        return (
+            point(
+                radius * sin,
+                half * centred_fraction(row, height_segments),
+                radius * cos,
+            ),
+            point(sin, 0f32, cos),
+            (fraction(column, segments), v),
+        );
+    });
+    builder.annulus(half, 0f32, radius, segments, 1, true);
+    builder.annulus(-half, 0f32, radius, segments, 1, false);
+    return builder.build();
+}
+
+// Cone along y with its apex on top and a capped base.
+pub fn cone(radius: f32, height: f32, segments: u32, height_segments: u32) -> Mesh {
+    let (segments, height_segments) = (segments.max(3), height_segments.max(1));
+    let half: f32 = height / 2f32;
+    let mut builder: MeshBuilder = MeshBuilder::new();
+    cone_side(
+        &mut builder,
+        -half,
+        height,
+        radius,
+        segments,
+        height_segments,
+    );
+    builder.annulus(-half, 0f32, radius, segments, 1, false);
+    return builder.build();
+}
+
+// Side of a cone standing on y = base, whose apex corners share a single vertex.
+fn cone_side(
+    builder: &mut MeshBuilder,
+    base: f32,
+    height: f32,
+    radius: f32,
+    segments: u32,
+    height_segments: u32,
+) {
+    builder.grid(segments, height_segments, false, |column, row| {
+        let (sin, cos) = angle(column, segments).sin_cos();
+        let v: f32 = fraction(row, height_segments);
+        let ring: f32 = if row == height_segments {
+            0f32
+        } else {
+            radius * (1f32 - v)
+        };
+        return (
+            point(ring * sin, base + height * v, ring * cos),
+            point(height * sin, radius, height * cos),
+            (fraction(column, segments), v),
+        );
+    });
+}
+
+// Ring torus around the y axis. `major_radius` is the distance from the centre to the middle of
+// the tube.
+pub fn torus(
+    major_radius: f32,
+    minor_radius: f32,
+    major_segments: u32,
+    minor_segments: u32,
+) -> Mesh {
+    let (major_segments, minor_segments) = (major_segments.max(3), minor_segments.max(3));
+    let mut builder: MeshBuilder = MeshBuilder::new();
+    builder.grid(major_segments, minor_segments, false, |column, row| {
+        let (sin_phi, cos_phi) = angle(column, major_segments).sin_cos();
+        let (sin_theta, cos_theta) = angle(row, minor_segments).sin_cos();
+        let ring: f32 = major_radius + minor_radius * cos_theta;
+        return (
+            point(ring * sin_phi, minor_radius * sin_theta, ring * cos_phi),
+            point(cos_theta * sin_phi, sin_theta, cos_theta * cos_phi),
+            (
+                fraction(column, major_segments),
+                fraction(row, minor_segments),
+            ),
+        );
+    });
+    return builder.build();
+}
+
+// Grid in the xy plane facing +z, towards the viewer of the drawers.
+pub fn plane(width: f32, height: f32, columns: u32, rows: u32) -> Mesh {
+    let (columns, rows) = (columns.max(1), rows.max(1));
+    let mut builder: MeshBuilder = MeshBuilder::new();
+    builder.grid(columns, rows, false, |column, row| {
+        let (u, v) = (fraction(column, columns), fraction(row, rows));
+        return (
+            point((u - 0.5f32) * width, (v - 0.5f32) * height, 0f32),
+            point(0f32, 0f32, 1f32),
+            (u, v),
+        );
+    });
+    return builder.build();
+}
+
+// Cylinder of length `height` along y closed by two hemispheres of `rings` bands each.
+pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> Mesh {
+    let (segments, rings) = (segments.max(3), rings.max(1));
+    let half: f32 = height / 2f32;
+    // V follows the arc length from the bottom pole to the top one.
+    let total_length: f32 = PI * radius + height;
+    let mut builder: MeshBuilder = MeshBuilder::new();
+    builder.grid(segments, 2 * rings + 1, false, |column, row| {
+        let (sin_phi, cos_phi) = angle(column, segments).sin_cos();
+        let (hemisphere_row, centre) = if row <= rings {
+            (row, -half)
+        } else {
+            (row - 1, half)
+        };
+        let (sin_lat, cos_lat) = latitude_sin_cos(hemisphere_row, 2 * rings);
+        let normal: Point3D<f32> = point(cos_lat * sin_phi, sin_lat, cos_lat * cos_phi);
+        let arc: f32 = radius * PI * fraction(hemisphere_row, 2 * rings)
+            + if row > rings { height } else { 0f32 };
+        return (
+            point(
+                radius * normal.x,
+                centre + radius * normal.y,
+                radius * normal.z,
+            ),
+            normal,
+            (fraction(column, segments), arc / total_length),
+        );
+    });
+    return builder.build();
+}
+
+// Arrow starting at the origin and pointing along +y, its cone shaped head being part of
+// `length`.
+pub fn arrow(
+    length: f32,
+    shaft_radius: f32,
+    head_radius: f32,
+    head_length: f32,
+    segments: u32,
+) -> Mesh {
+    let segments: u32 = segments.max(3);
+    let shaft_length: f32 = (length - head_length).max(0f32);
+    let mut builder: MeshBuilder = MeshBuilder::new();
+    builder.annulus(0f32, 0f32, shaft_radius, segments, 1, false);
+    builder.grid(segments, 1, false, |column, row| {
+        let (sin, cos) = angle(column, segments).sin_cos();
+        let v: f32 = fraction(row, 1);
+        return (
+            point(shaft_radius * sin, shaft_length * v, shaft_radius * cos),
+            point(sin, 0f32, cos),
+            (fraction(column, segments), v),
+        );
+    });
+    builder.annulus(shaft_length, shaft_radius, head_radius, segments, 1, false);
+    cone_side(
+        &mut builder,
+        shaft_length,
+        length - shaft_length,
+        head_radius,
+        segments,
+        1,
+    );
+    return builder.build();
+}
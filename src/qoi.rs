@@ -0,0 +1,205 @@
+use crate::tga::{self, PixelChannels};
+use std::io::{self, prelude::*};
+
+// "Quite OK Image" format reading and writing. With `vflip`, the first image row is the bottom of
+// the picture, as in the drawers output.
+
+const MAGIC: &[u8; 4] = b"qoif";
+const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const OP_RUN: u8 = 0xc0;
+const OP_RGB: u8 = 0xfe;
+const OP_RGBA: u8 = 0xff;
+const TAG_MASK: u8 = 0xc0;
+const MAX_RUN: u8 = 62;
+const SRGB_COLORSPACE: u8 = 0;
+
+impl<T: PixelChannels> tga::Image<T> {
+    // Greyscale images are stored as RGB, QOI having no single channel mode.
+    pub fn write_qoi<W: io::Write>(
+        &self,
+        writer: &mut io::BufWriter<W>,
+        vflip: bool,
+    ) -> io::Result<()> {
+        let channels: u8 = if T::BPP == 4 { 4 } else { 3 };
+        writer.write_all(MAGIC)?;
+        writer.write_all(&self.width.to_be_bytes())?;
+        writer.write_all(&self.height.to_be_bytes())?;
+        writer.write_all(&[channels, SRGB_COLORSPACE])?;
+
+        let mut index = [[0u8; 4]; 64];
+        let mut previous: [u8; 4] = [0, 0, 0, 255];
+        let mut run: u8 = 0;
+        let mut bytes: Vec<u8> = Vec::new();
+        let width: usize = self.width as usize;
+        for y in 0..self.height as usize {
+            let row: usize = if vflip {
+                self.height as usize - 1 - y
+            } else {
+                y
+            };
+            for pixel in &self.data[row * width..(row + 1) * width] {
+                bytes.clear();
+                pixel.push_channels(&mut bytes);
+                let rgba: [u8; 4] = match bytes.as_slice() {
+                    [grey] => [*grey, *grey, *grey, 255],
+                    [r, g, b] => [*r, *g, *b, 255],
+                    [r, g, b, a] => [*r, *g, *b, *a],
+                    _ => [0, 0, 0, 255],
+                };
+
+                if rgba == previous {
+                    run += 1;
+                    if run == MAX_RUN {
+                        writer.write_all(&[OP_RUN | (run - 1)])?;
+                        run = 0;
+                    }
+                    continue;
+                }
+                if run > 0 {
+                    writer.write_all(&[OP_RUN | (run - 1)])?;
+                    run = 0;
+                }
+
+                let hash: usize = hash(rgba);
+                if index[hash] == rgba {
+                    writer.write_all(&[OP_INDEX | hash as u8])?;
+                } else {
+                    index[hash] = rgba;
+                    write_colour(writer, rgba, previous)?;
+                }
+                previous = rgba;
+            }
+        }
+        if run > 0 {
+            writer.write_all(&[OP_RUN | (run - 1)])?;
+        }
+        writer.write_all(&END_MARKER)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+// Three channel images decode to `Rgb`, four channel ones to `Rgba`.
+pub fn read<R: io::Read>(reader: &mut R, vflip: bool) -> io::Result<tga::DynamicImage> {
+    let mut header = [0u8; 14];
+    reader.read_exact(&mut header)?;
+    if &header[0..4] != MAGIC {
+        return Err(invalid_data("missing QOI signature"));
+    }
+    let width: u32 = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    let height: u32 = u32::from_be_bytes([header[8], header[9], header[10], header[11]]);
+    let channels: u8 = header[12];
+    if width == 0 || height == 0 || tga::decoded_size(width as usize, height as usize, 4).is_err() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            tga::Error::InvalidDimensions.to_string(),
+        ));
+    }
+    if channels != 3 && channels != 4 {
+        return Err(invalid_data("invalid channel count"));
+    }
+
+    let (width, height) = (width as usize, height as usize);
+    // Grows with the data read, so that a truncated file does not allocate the whole image.
+    let mut pixels: Vec<[u8; 4]> = Vec::new();
+    let mut index = [[0u8; 4]; 64];
+    let mut previous: [u8; 4] = [0, 0, 0, 255];
+    let mut byte = [0u8; 1];
+    while pixels.len() < width * height {
+        reader.read_exact(&mut byte)?;
+        let op: u8 = byte[0];
+        let mut rgba: [u8; 4] = previous;
+        if op == OP_RGB {
+            reader.read_exact(&mut rgba[0..3])?;
+        } else if op == OP_RGBA {
+            reader.read_exact(&mut rgba)?;
+        } else {
+            match op & TAG_MASK {
+                OP_INDEX => rgba = index[(op & 0x3f) as usize],
+                OP_DIFF => {
+                    rgba[0] = previous[0].wrapping_add((op >> 4) & 0x03).wrapping_sub(2);
+                    rgba[1] = previous[1].wrapping_add((op >> 2) & 0x03).wrapping_sub(2);
+                    rgba[2] = previous[2].wrapping_add(op & 0x03).wrapping_sub(2);
+                }
+                OP_LUMA => {
+                    reader.read_exact(&mut byte)?;
+                    let dg: u8 = (op & 0x3f).wrapping_sub(32);
+                    let dr_dg: u8 = (byte[0] >> 4).wrapping_sub(8);
+                    let db_dg: u8 = (byte[0] & 0x0f).wrapping_sub(8);
+                    rgba[0] = previous[0].wrapping_add(dg).wrapping_add(dr_dg);
+                    rgba[1] = previous[1].wrapping_add(dg);
+                    rgba[2] = previous[2].wrapping_add(dg).wrapping_add(db_dg);
+                }
+                _ => {
+                    let run: usize = (op & 0x3f) as usize + 1;
+                    let remaining: usize = width * height - pixels.len();
+                    pixels.extend(std::iter::repeat_n(previous, run.min(remaining)));
+                    continue;
+                }
+            }
+        }
+        index[hash(rgba)] = rgba;
+        pixels.push(rgba);
+        previous = rgba;
+    }
+
+    if vflip {
+        let flipped: Vec<[u8; 4]> = pixels.chunks(width).rev().flatten().copied().collect();
+        pixels = flipped;
+    }
+    return Ok(if channels == 4 {
+        tga::DynamicImage::Rgba(to_image(&pixels, width, height))
+    } else {
+        tga::DynamicImage::Rgb(to_image(&pixels, width, height))
+    });
+}
+
+fn to_image<T: PixelChannels>(pixels: &[[u8; 4]], width: usize, height: usize) -> tga::Image<T> {
+    let mut img: tga::Image<T> = tga::Image::new(width as u32, height as u32);
+    for (pixel, rgba) in img.data.iter_mut().zip(pixels) {
+        *pixel = T::from_channels(&rgba[..T::BPP as usize]);
+    }
+    return img;
+}
+
+fn write_colour<W: io::Write>(
+    writer: &mut io::BufWriter<W>,
+    rgba: [u8; 4],
+    previous: [u8; 4],
+) -> io::Result<()> {
+    if rgba[3] != previous[3] {
+        writer.write_all(&[OP_RGBA, rgba[0], rgba[1], rgba[2], rgba[3]])?;
+        return Ok(());
+    }
+    let dr: i8 = rgba[0].wrapping_sub(previous[0]) as i8;
+    let dg: i8 = rgba[1].wrapping_sub(previous[1]) as i8;
+    let db: i8 = rgba[2].wrapping_sub(previous[2]) as i8;
+    let dr_dg: i8 = dr.wrapping_sub(dg);
+    let db_dg: i8 = db.wrapping_sub(dg);
+    if (-2..=1).contains(&dr) && (-2..=1).contains(&dg) && (-2..=1).contains(&db) {
+        writer.write_all(&[OP_DIFF
+            | ((dr + 2) as u8) << 4
+            | ((dg + 2) as u8) << 2
+            | (db + 2) as u8])?;
+    } else if (-32..=31).contains(&dg) && (-8..=7).contains(&dr_dg) && (-8..=7).contains(&db_dg) {
+        writer.write_all(&[
+            OP_LUMA | (dg + 32) as u8,
+            ((dr_dg + 8) as u8) << 4 | (db_dg + 8) as u8,
+        ])?;
+    } else {
+        writer.write_all(&[OP_RGB, rgba[0], rgba[1], rgba[2]])?;
+    }
+    Ok(())
+}
+
+fn hash(rgba: [u8; 4]) -> usize {
+    let [r, g, b, a] = rgba.map(|channel| channel as usize);
+    return (r * 3 + g * 5 + b * 7 + a * 11) % 64;
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+}
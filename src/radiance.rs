@@ -0,0 +1,250 @@
+use crate::tga;
+use std::io::{self, prelude::*};
+use std::mem;
+
+// Radiance RGBE (.hdr) reading and writing. With `vflip`, the first image row is the bottom of
+// the picture, as in the drawers output.
+
+const MIN_RLE_WIDTH: usize = 8;
+const MAX_RLE_WIDTH: usize = 0x7fff;
+const MAX_RUN: usize = 127;
+
+pub fn write<W: io::Write>(
+    img: &tga::Image<tga::RgbF32>,
+    writer: &mut io::BufWriter<W>,
+    vflip: bool,
+) -> io::Result<()> {
+    writer.write_all(b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n")?;
+    let y_axis: &str = if vflip { "+Y" } else { "-Y" };
+    writeln!(writer, "{} {} +X {}", y_axis, img.height, img.width)?;
+
+    let width: usize = img.width as usize;
+    let mut scanline: Vec<[u8; 4]> = Vec::with_capacity(width);
+    for row in img.data.chunks(width.max(1)) {
+        scanline.clear();
+        scanline.extend(row.iter().map(|pixel| to_rgbe(*pixel)));
+        if !(MIN_RLE_WIDTH..=MAX_RLE_WIDTH).contains(&width) {
+            for rgbe in &scanline {
+                writer.write_all(rgbe)?;
+            }
+            continue;
+        }
+        writer.write_all(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8])?;
+        for channel in 0..4usize {
+            let values: Vec<u8> = scanline.iter().map(|rgbe| rgbe[channel]).collect();
+            write_rle_channel(writer, &values)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn read<R: io::BufRead>(reader: &mut R, vflip: bool) -> io::Result<tga::Image<tga::RgbF32>> {
+    let mut line: String = String::new();
+    reader.read_line(&mut line)?;
+    if !line.starts_with("#?") {
+        return Err(invalid_data("missing Radiance signature"));
+    }
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid_data("unterminated header"));
+        }
+        let header_line: &str = line.trim();
+        if header_line.is_empty() {
+            break;
+        }
+        if let Some(format) = header_line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(invalid_data("unsupported pixel format"));
+            }
+        }
+    }
+
+    line.clear();
+    reader.read_line(&mut line)?;
+    let resolution: Vec<&str> = line.split_whitespace().collect();
+    let (bottom_up, height, width) = match resolution.as_slice() {
+        [y_axis @ ("-Y" | "+Y"), height, "+X", width] => (*y_axis == "+Y", *height, *width),
+        _ => return Err(invalid_data("unsupported resolution line")),
+    };
+    let height: u32 = height
+        .parse::<u32>()
+        .map_err(|_| invalid_data("invalid height"))?;
+    let width: u32 = width
+        .parse::<u32>()
+        .map_err(|_| invalid_data("invalid width"))?;
+    let pixel_size: usize = mem::size_of::<tga::RgbF32>();
+    if tga::decoded_size(width as usize, height as usize, pixel_size).is_err() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            tga::Error::InvalidDimensions.to_string(),
+        ));
+    }
+
+    let mut img: tga::Image<tga::RgbF32> = tga::Image::new(width, height);
+    let mut scanline: Vec<[u8; 4]> = vec![[0u8; 4]; width as usize];
+    for y in 0..height as usize {
+        read_scanline(reader, &mut scanline)?;
+        let row: usize = if bottom_up == vflip {
+            y
+        } else {
+            height as usize - 1 - y
+        };
+        let start: usize = row * width as usize;
+        for (pixel, rgbe) in img.data[start..start + width as usize]
+            .iter_mut()
+            .zip(&scanline)
+        {
+            *pixel = from_rgbe(*rgbe);
+        }
+    }
+    return Ok(img);
+}
+
+fn read_scanline<R: io::BufRead>(reader: &mut R, scanline: &mut [[u8; 4]]) -> io::Result<()> {
+    let width: usize = scanline.len();
+    if width == 0 {
+        return Ok(());
+    }
+    let mut first = [0u8; 4];
+    reader.read_exact(&mut first)?;
+    let is_rle: bool = (MIN_RLE_WIDTH..=MAX_RLE_WIDTH).contains(&width)
+        && first[0] == 2
+        && first[1] == 2
+        && first[2] & 0x80 == 0;
+    if !is_rle {
+        scanline[0] = first;
+        return read_flat_scanline(reader, scanline);
+    }
+    if ((first[2] as usize) << 8 | first[3] as usize) != width {
+        return Err(invalid_data("scanline width mismatch"));
+    }
+
+    for channel in 0..4usize {
+        let mut x: usize = 0;
+        while x < width {
+            let mut count = [0u8; 1];
+            reader.read_exact(&mut count)?;
+            let (run, is_repeat) = if count[0] > 128 {
+                ((count[0] - 128) as usize, true)
+            } else {
+                (count[0] as usize, false)
+            };
+            if run == 0 || x + run > width {
+                return Err(invalid_data("bad run length"));
+            }
+            if is_repeat {
+                let mut value = [0u8; 1];
+                reader.read_exact(&mut value)?;
+                for pixel in &mut scanline[x..x + run] {
+                    pixel[channel] = value[0];
+                }
+            } else {
+                let mut values = vec![0u8; run];
+                reader.read_exact(&mut values)?;
+                for (pixel, value) in scanline[x..x + run].iter_mut().zip(values) {
+                    pixel[channel] = value;
+                }
+            }
+            x += run;
+        }
+    }
+    Ok(())
+}
+
+// Uncompressed pixels, possibly using the original Radiance run encoding where (1, 1, 1, n)
+// repeats the previous pixel. The first pixel has already been read.
+fn read_flat_scanline<R: io::BufRead>(reader: &mut R, scanline: &mut [[u8; 4]]) -> io::Result<()> {
+    let mut x: usize = 1;
+    let mut shift: u32 = 0;
+    while x < scanline.len() {
+        let mut rgbe = [0u8; 4];
+        reader.read_exact(&mut rgbe)?;
+        if rgbe[0] == 1 && rgbe[1] == 1 && rgbe[2] == 1 {
+            let run: usize = (rgbe[3] as usize) << shift;
+            if x + run > scanline.len() {
+                return Err(invalid_data("bad run length"));
+            }
+            let previous: [u8; 4] = scanline[x - 1];
+            scanline[x..x + run].fill(previous);
+            x += run;
+            shift += 8;
+        } else {
+            scanline[x] = rgbe;
+            x += 1;
+            shift = 0;
+        }
+    }
+    Ok(())
+}
+
+fn write_rle_channel<W: io::Write>(writer: &mut io::BufWriter<W>, values: &[u8]) -> io::Result<()> {
+    let mut x: usize = 0;
+    while x < values.len() {
+        let run: usize = run_length(&values[x..]);
+        if run > 2 {
+            writer.write_all(&[128 + run as u8, values[x]])?;
+            x += run;
+            continue;
+        }
+        // Literal values up to the next run worth encoding.
+        let mut end: usize = x;
+        while end < values.len() && end - x < MAX_RUN && run_length(&values[end..]) <= 2 {
+            end += 1;
+        }
+        writer.write_all(&[(end - x) as u8])?;
+        writer.write_all(&values[x..end])?;
+        x = end;
+    }
+    Ok(())
+}
+
+fn run_length(values: &[u8]) -> usize {
+    return values
+        .iter()
+        .take(MAX_RUN)
+        .take_while(|&&value| value == values[0])
+        .count();
+}
+
+fn to_rgbe(colour: tga::RgbF32) -> [u8; 4] {
+    let max: f32 = colour.r.max(colour.g).max(colour.b);
+    if max.is_nan() || max < 1e-32f32 {
+        return [0u8; 4];
+    }
+    // The exponent byte holds -128 to 127, infinite and larger values saturate at the top.
+    let exponent: i32 = if max.is_finite() {
+        (max.log2().floor() as i32 + 1).clamp(-128, 127)
+    } else {
+        127
+    };
+    let scale: f32 = 256f32 / (exponent as f32).exp2();
+    let channel = |c: f32| -> u8 { (c.max(0f32) * scale).min(255f32) as u8 };
+    return [
+        channel(colour.r),
+        channel(colour.g),
+        channel(colour.b),
+        (exponent + 128) as u8,
+    ];
+}
+
+fn from_rgbe(rgbe: [u8; 4]) -> tga::RgbF32 {
+    if rgbe[3] == 0 {
+        return tga::RgbF32 {
+            r: 0f32,
+            g: 0f32,
+            b: 0f32,
+        };
+    }
+    let scale: f32 = ((rgbe[3] as i32 - 136) as f32).exp2();
+    return tga::RgbF32 {
+        r: (rgbe[0] as f32 + 0.5f32) * scale,
+        g: (rgbe[1] as f32 + 0.5f32) * scale,
+        b: (rgbe[2] as f32 + 0.5f32) * scale,
+    };
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+}
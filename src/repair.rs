@@ -0,0 +1,537 @@
+use crate::linear_algebra;
+use crate::mesh::{Mesh, Point3D, Triangle};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+// Validation and repair of meshes coming from untrusted files. `Mesh::validate` reports the
+// problems, and each repair fixes one kind of them, returning how many elements it changed.
+// They are meant to be run in this order:
+// remove_invalid_triangles, weld_vertices, remove_degenerate_triangles,
+// remove_duplicate_triangles, orient_consistently, fill_holes and remove_unreferenced_vertices.
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValidationReport {
+    // Triangles with a position or material index past the end of its array.
+    pub out_of_range_triangles: Vec<usize>,
+    // Triangles with a normal or texture index past the end of its array, as in files without
+    // normals or UVs, whose indices default to 0. The attribute is taken as missing, the drawers
+    // shading with the face normal instead.
+    pub missing_attributes: Vec<usize>,
+    // Positions with a NaN or infinite coordinate.
+    pub non_finite_vertices: Vec<usize>,
+    // Triangles repeating a vertex, or whose corners are aligned.
+    pub degenerate_triangles: Vec<usize>,
+    // Triangles over the same three vertices as an earlier one, whatever their winding.
+    pub duplicate_triangles: Vec<usize>,
+    // Vertices at the same position as an earlier one, paired with the first of them.
+    pub duplicate_vertices: Vec<[usize; 2]>,
+    pub unreferenced_vertices: Vec<usize>,
+    // Edges whose two triangles run along them in the same direction, one of them being flipped.
+    pub inconsistent_edges: Vec<[usize; 2]>,
+    // Edges shared by more than two triangles.
+    pub non_manifold_edges: Vec<[usize; 2]>,
+    // Edges with a single triangle, on the border of an open surface or of a hole.
+    pub boundary_edges: usize,
+}
+
+impl ValidationReport {
+    // Duplicate and unreferenced vertices, missing attributes, non-manifold and boundary edges do
+    // not make a mesh invalid: UV seams duplicate positions on purpose, and open surfaces are
+    // legitimate.
+    pub fn is_valid(&self) -> bool {
+        return self.out_of_range_triangles.is_empty()
+            && self.non_finite_vertices.is_empty()
+            && self.degenerate_triangles.is_empty()
+            && self.duplicate_triangles.is_empty()
+            && self.inconsistent_edges.is_empty();
+    }
+}
+
+// Triangles of an undirected edge, with whether each runs along it from the lower vertex index.
+type EdgeMap = HashMap<[usize; 2], Vec<(usize, bool)>>;
+
+impl Mesh {
+    pub fn validate(&self) -> ValidationReport {
+        let mut report: ValidationReport = ValidationReport {
+            non_finite_vertices: (0..self.v_positions.xs.len())
+                .filter(|&v| !is_finite(self.v_positions.get_at(v)))
+                .collect(),
+            ..Default::default()
+        };
+
+        let mut first_at: HashMap<[u32; 3], usize> = HashMap::new();
+        for v in 0..self.v_positions.xs.len() {
+            let p: Point3D<f32> = self.v_positions.get_at(v);
+            if is_finite(p) {
+                // Adding 0 turns -0 into +0, so that both have the same bits.
+                let key: [u32; 3] = [p.x, p.y, p.z].map(|c| (c + 0f32).to_bits());
+                match first_at.get(&key) {
+                    Some(&first) => report.duplicate_vertices.push([v, first]),
+                    None => {
+                        first_at.insert(key, v);
+                    }
+                }
+            }
+        }
+
+        let mut referenced: Vec<bool> = vec![false; self.v_positions.xs.len()];
+        let mut vertex_sets: HashSet<[usize; 3]> = HashSet::new();
+        for (t, triangle) in self.triangles.iter().enumerate() {
+            if !self.triangle_is_in_range(triangle) {
+                report.out_of_range_triangles.push(t);
+                continue;
+            }
+            if !self.attributes_are_in_range(triangle) {
+                report.missing_attributes.push(t);
+            }
+            for &vertex in &triangle.vertices {
+                referenced[vertex] = true;
+            }
+            if self.triangle_is_degenerate(triangle) {
+                report.degenerate_triangles.push(t);
+            }
+            let mut vertex_set: [usize; 3] = triangle.vertices;
+            vertex_set.sort();
+            if !vertex_sets.insert(vertex_set) {
+                report.duplicate_triangles.push(t);
+            }
+        }
+        report.unreferenced_vertices = (0..referenced.len()).filter(|&v| !referenced[v]).collect();
+
+        for (edge, triangles) in self.edge_map() {
+            match triangles.as_slice() {
+                [_] => report.boundary_edges += 1,
+                [(_, first), (_, second)] if first == second => {
+                    report.inconsistent_edges.push(edge)
+                }
+                [_, _] => (),
+                _ => report.non_manifold_edges.push(edge),
+            }
+        }
+        report.inconsistent_edges.sort();
+        report.non_manifold_edges.sort();
+        return report;
+    }
+
+    // Removes the triangles with an out of range position or material index or a non-finite
+    // corner, which cannot be drawn. Out of range normal and texture indices are left alone.
+    pub fn remove_invalid_triangles(&mut self) -> usize {
+        let count: usize = self.triangles.len();
+        let triangles: Vec<Triangle> = self
+            .triangles
+            .iter()
+            .filter(|triangle| {
+                self.triangle_is_in_range(triangle)
+                    && triangle
+                        .vertices
+                        .iter()
+                        .all(|&vertex| is_finite(self.v_positions.get_at(vertex)))
+            })
+            .copied()
+            .collect();
+        self.triangles = triangles;
+        return count - self.triangles.len();
+    }
+
+    // Makes the triangles use the first of any group of positions within `epsilon` of it. Normal
+    // and texture indices are kept, so UV seams survive. The positions left over are only dropped
+    // by `remove_unreferenced_vertices`. Returns the number of positions merged away.
+    pub fn weld_vertices(&mut self, epsilon: f32) -> usize {
+        let vertex_count: usize = self.v_positions.xs.len();
+        let cell_size: f32 = epsilon.max(f32::MIN_POSITIVE);
+        let cell = |p: Point3D<f32>| -> [i64; 3] {
+            return [p.x, p.y, p.z].map(|c| (c / cell_size).floor() as i64);
+        };
+
+        let mut grid: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+        let mut remap: Vec<usize> = (0..vertex_count).collect();
+        let mut welded: usize = 0;
+        for (v, welded_to) in remap.iter_mut().enumerate() {
+            let p: Point3D<f32> = self.v_positions.get_at(v);
+            if !is_finite(p) {
+                continue;
+            }
+            let [x, y, z] = cell(p);
+            let mut target: Option<usize> = None;
+            for neighbour in (-1..=1i64).flat_map(|i| {
+                (-1..=1i64).flat_map(move |j| (-1..=1i64).map(move |k| [x + i, y + j, z + k]))
+            }) {
+                for &w in grid.get(&neighbour).into_iter().flatten() {
+                    let offset: Point3D<f32> =
+                        linear_algebra::subtract(p, self.v_positions.get_at(w));
+                    if linear_algebra::norm(offset) <= epsilon && target.is_none_or(|t| w < t) {
+                        target = Some(w);
+                    }
+                }
+            }
+            match target {
+                Some(w) => {
+                    *welded_to = w;
+                    welded += 1;
+                }
+                None => grid.entry([x, y, z]).or_default().push(v),
+            }
+        }
+
+        for triangle in &mut self.triangles {
+            for vertex in triangle.vertices.iter_mut() {
+                if *vertex < vertex_count {
+                    *vertex = remap[*vertex];
+                }
+            }
+        }
+        return welded;
+    }
+
+    pub fn remove_degenerate_triangles(&mut self) -> usize {
+        let count: usize = self.triangles.len();
+        let triangles: Vec<Triangle> = self
+            .triangles
+            .iter()
+            .filter(|triangle| {
+                !self.triangle_is_in_range(triangle) || !self.triangle_is_degenerate(triangle)
+            })
+            .copied()
+            .collect();
+        self.triangles = triangles;
+        return count - self.triangles.len();
+    }
+
+    // Keeps the first of the triangles over the same three vertices.
+    pub fn remove_duplicate_triangles(&mut self) -> usize {
+        let count: usize = self.triangles.len();
+        let mut vertex_sets: HashSet<[usize; 3]> = HashSet::new();
+        self.triangles.retain(|triangle| {
+            let mut vertex_set: [usize; 3] = triangle.vertices;
+            vertex_set.sort();
+            return vertex_sets.insert(vertex_set);
+        });
+        return count - self.triangles.len();
+    }
+
+    // Flips triangles so that neighbours across every two-triangle edge agree within each
+    // connected component. Closed components are made to enclose a positive volume, facing
+    // outwards, and open ones keep the winding of most of their triangles. Returns the number of
+    // triangles flipped.
+    pub fn orient_consistently(&mut self) -> usize {
+        let edges: EdgeMap = self.edge_map();
+        let mut neighbours: Vec<Vec<(usize, [usize; 2])>> = vec![Vec::new(); self.triangles.len()];
+        let mut has_boundary: Vec<bool> = vec![false; self.triangles.len()];
+        for (&edge, triangles) in &edges {
+            match triangles.as_slice() {
+                [(t, _)] => has_boundary[*t] = true,
+                [(t, _), (u, _)] => {
+                    neighbours[*t].push((*u, edge));
+                    neighbours[*u].push((*t, edge));
+                }
+                _ => (),
+            }
+        }
+
+        let mut flipped: Vec<Option<bool>> = vec![None; self.triangles.len()];
+        let mut flip_count: usize = 0;
+        for seed in 0..self.triangles.len() {
+            if flipped[seed].is_some() || !self.triangle_is_in_range(&self.triangles[seed]) {
+                continue;
+            }
+            flipped[seed] = Some(false);
+            let mut component: Vec<usize> = vec![seed];
+            let mut queue: VecDeque<usize> = VecDeque::from([seed]);
+            while let Some(t) = queue.pop_front() {
+                for &(u, edge) in &neighbours[t] {
+                    if flipped[u].is_some() {
+                        continue;
+                    }
+                    let t_forward: bool =
+                        runs_forward(&self.triangles[t], edge) != flipped[t].unwrap_or(false);
+                    flipped[u] = Some(runs_forward(&self.triangles[u], edge) == t_forward);
+                    component.push(u);
+                    queue.push_back(u);
+                }
+            }
+
+            // Closed components face outwards, open ones keep the winding of most of their
+            // triangles.
+            let turn_inside_out: bool = if component.iter().all(|&t| !has_boundary[t]) {
+                let volume: f32 = component
+                    .iter()
+                    .map(|&t| {
+                        let volume: f32 = self.signed_volume(&self.triangles[t]);
+                        if flipped[t] == Some(true) {
+                            -volume
+                        } else {
+                            volume
+                        }
+                    })
+                    .sum();
+                volume < 0f32
+            } else {
+                2 * component
+                    .iter()
+                    .filter(|&&t| flipped[t] == Some(true))
+                    .count()
+                    > component.len()
+            };
+            for t in component {
+                if (flipped[t] == Some(true)) != turn_inside_out {
+                    flip(&mut self.triangles[t]);
+                    flip_count += 1;
+                }
+            }
+        }
+        return flip_count;
+    }
+
+    // Closes the boundary loops of at most `max_edges` edges, with a single triangle for
+    // triangular holes and otherwise a fan around a new vertex at the centroid of the loop.
+    // Loops going through a vertex with several boundary edges leaving it are left open.
+    // Returns the number of holes filled.
+    pub fn fill_holes(&mut self, max_edges: usize) -> usize {
+        // Boundary half-edges by origin, as the triangle and corner they leave from.
+        let mut outgoing: HashMap<usize, Vec<(usize, usize)>> = HashMap::new();
+        for (edge, triangles) in self.edge_map() {
+            if let [(t, forward)] = triangles[..] {
+                let origin: usize = if forward { edge[0] } else { edge[1] };
+                let k: usize = (0..3usize)
+                    .find(|&k| self.triangles[t].vertices[k] == origin)
+                    .unwrap_or(0);
+                outgoing.entry(origin).or_default().push((t, k));
+            }
+        }
+
+        let mut visited: HashSet<usize> = HashSet::new();
+        let mut starts: Vec<usize> = outgoing.keys().copied().collect();
+        starts.sort();
+        let mut filled: usize = 0;
+        for start in starts {
+            if visited.contains(&start) {
+                continue;
+            }
+            // Corners along the loop, as (triangle, corner) of each boundary half-edge.
+            let mut corners: Vec<(usize, usize)> = Vec::new();
+            let mut vertex: usize = start;
+            let closed: bool = loop {
+                let leaving: &Vec<(usize, usize)> = match outgoing.get(&vertex) {
+                    Some(leaving) if leaving.len() == 1 && !visited.contains(&vertex) => leaving,
+                    _ => break false,
+                };
+                visited.insert(vertex);
+                let (t, k) = leaving[0];
+                corners.push((t, k));
+                vertex = self.triangles[t].vertices[(k + 1) % 3];
+                if vertex == start {
+                    break true;
+                }
+            };
+            if closed && corners.len() <= max_edges {
+                self.fill_hole(&corners);
+                filled += 1;
+            }
+        }
+        return filled;
+    }
+
+    // Drops the positions used by no triangle, renumbering the others. Normals and texture
+    // coordinates are left untouched.
+    pub fn remove_unreferenced_vertices(&mut self) -> usize {
+        let vertex_count: usize = self.v_positions.xs.len();
+        let mut referenced: Vec<bool> = vec![false; vertex_count];
+        for triangle in &self.triangles {
+            for &vertex in &triangle.vertices {
+                if vertex < vertex_count {
+                    referenced[vertex] = true;
+                }
+            }
+        }
+
+        let mut remap: Vec<usize> = vec![usize::MAX; vertex_count];
+        let mut kept: usize = 0;
+        for v in 0..vertex_count {
+            if referenced[v] {
+                remap[v] = kept;
+                self.v_positions.xs[kept] = self.v_positions.xs[v];
+                self.v_positions.ys[kept] = self.v_positions.ys[v];
+                self.v_positions.zs[kept] = self.v_positions.zs[v];
+                kept += 1;
+            }
+        }
+        self.v_positions.xs.truncate(kept);
+        self.v_positions.ys.truncate(kept);
+        self.v_positions.zs.truncate(kept);
+        // Out of range indices stay out of range.
+        for triangle in &mut self.triangles {
+            for vertex in triangle.vertices.iter_mut() {
+                *vertex = remap.get(*vertex).copied().unwrap_or(usize::MAX);
+            }
+        }
+        return vertex_count - kept;
+    }
+
+    fn triangle_is_in_range(&self, triangle: &Triangle) -> bool {
+        return triangle
+            .vertices
+            .iter()
+            .all(|&vertex| vertex < self.v_positions.xs.len())
+            && triangle.material < self.materials.len();
+    }
+
+    fn attributes_are_in_range(&self, triangle: &Triangle) -> bool {
+        return triangle
+            .normals
+            .iter()
+            .all(|&normal| normal < self.v_normals.xs.len())
+            && triangle
+                .textures
+                .iter()
+                .all(|&texture| texture < self.v_textures.us.len());
+    }
+
+    // Assumes the triangle is in range.
+    fn triangle_is_degenerate(&self, triangle: &Triangle) -> bool {
+        let [a, b, c] = triangle.vertices;
+        if a == b || b == c || c == a {
+            return true;
+        }
+        let [p, q, r] = triangle
+            .vertices
+            .map(|vertex| self.v_positions.get_at(vertex));
+        let (pq, pr) = (
+            linear_algebra::subtract(q, p),
+            linear_algebra::subtract(r, p),
+        );
+        let longest: f32 = [pq, pr, linear_algebra::subtract(r, q)]
+            .map(|edge| linear_algebra::dot_product(edge, edge))
+            .into_iter()
+            .fold(0f32, f32::max);
+        // Twice the area, relative to the longest edge, so that the test does not depend on scale.
+        let double_area: f32 = linear_algebra::norm(linear_algebra::cross_product(pq, pr));
+        return double_area.is_nan() || double_area <= f32::EPSILON * longest;
+    }
+
+    // Six times the signed volume of the tetrahedron from the origin to the triangle.
+    fn signed_volume(&self, triangle: &Triangle) -> f32 {
+        let [p, q, r] = triangle
+            .vertices
+            .map(|vertex| self.v_positions.get_at(vertex));
+        return linear_algebra::dot_product(p, linear_algebra::cross_product(q, r));
+    }
+
+    // Edges of the in range triangles not repeating a vertex.
+    fn edge_map(&self) -> EdgeMap {
+        let mut edges: EdgeMap = HashMap::new();
+        for (t, triangle) in self.triangles.iter().enumerate() {
+            let [a, b, c] = triangle.vertices;
+            if !self.triangle_is_in_range(triangle) || a == b || b == c || c == a {
+                continue;
+            }
+            for k in 0..3usize {
+                let (a, b) = (triangle.vertices[k], triangle.vertices[(k + 1) % 3]);
+                edges
+                    .entry([a.min(b), a.max(b)])
+                    .or_default()
+                    .push((t, a < b));
+            }
+        }
+        return edges;
+    }
+
+    fn fill_hole(&mut self, corners: &[(usize, usize)]) {
+        let material: usize = self.triangles[corners[0].0].material;
+        let loop_corners: Vec<(usize, usize, usize)> = corners
+            .iter()
+            .map(|&(t, k)| {
+                let triangle: &Triangle = &self.triangles[t];
+                (
+                    triangle.vertices[k],
+                    triangle.normals[k],
+                    triangle.textures[k],
+                )
+            })
+            .collect();
+        let new_triangle = |corners: [(usize, usize, usize); 3]| -> Triangle {
+            return Triangle {
+                vertices: corners.map(|c| c.0),
+                normals: corners.map(|c| c.1),
+                textures: corners.map(|c| c.2),
+                material,
+            };
+        };
+
+        // The filling triangles run along the loop backwards.
+        if let [a, b, c] = loop_corners[..] {
+            self.triangles.push(new_triangle([a, c, b]));
+            return;
+        }
+        let centroid: Point3D<f32> =
+            mean(loop_corners.iter().map(|c| self.v_positions.get_at(c.0)));
+        self.v_positions
+            .add_vector(centroid.x, centroid.y, centroid.z);
+        // Missing attributes stay missing, the centre reusing an out of range index.
+        let normal_index: usize = match loop_corners.iter().find(|c| c.1 >= self.v_normals.xs.len())
+        {
+            Some(c) => c.1,
+            None => {
+                let normal: Point3D<f32> = linear_algebra::normalized(mean(
+                    loop_corners.iter().map(|c| self.v_normals.get_at(c.1)),
+                ));
+                self.v_normals.add_vector(normal.x, normal.y, normal.z);
+                self.v_normals.xs.len() - 1
+            }
+        };
+        let texture_index: usize = match loop_corners
+            .iter()
+            .find(|c| c.2 >= self.v_textures.us.len())
+        {
+            Some(c) => c.2,
+            None => {
+                let uv: Point3D<f32> = mean(loop_corners.iter().map(|c| {
+                    let (u, v) = self.v_textures.get_at(c.2);
+                    Point3D {
+                        x: u,
+                        y: v,
+                        z: 0f32,
+                    }
+                }));
+                self.v_textures.add_vector(uv.x, uv.y);
+                self.v_textures.us.len() - 1
+            }
+        };
+        let centre: (usize, usize, usize) =
+            (self.v_positions.xs.len() - 1, normal_index, texture_index);
+        for i in 0..loop_corners.len() {
+            let (a, b) = (loop_corners[i], loop_corners[(i + 1) % loop_corners.len()]);
+            self.triangles.push(new_triangle([b, a, centre]));
+        }
+    }
+}
+
+fn mean<I: ExactSizeIterator<Item = Point3D<f32>>>(points: I) -> Point3D<f32> {
+    let count: f32 = points.len() as f32;
+    return points.fold(
+        Point3D {
+            x: 0f32,
+            y: 0f32,
+            z: 0f32,
+        },
+        |sum, p| Point3D {
+            x: sum.x + p.x / count,
+            y: sum.y + p.y / count,
+            z: sum.z + p.z / count,
+        },
+    );
+}
+
+fn is_finite(p: Point3D<f32>) -> bool {
+    return p.x.is_finite() && p.y.is_finite() && p.z.is_finite();
+}
+
+// Whether the triangle goes along `edge` from its lower vertex index to its higher one.
+fn runs_forward(triangle: &Triangle, edge: [usize; 2]) -> bool {
+    return (0..3usize)
+        .any(|k| triangle.vertices[k] == edge[0] && triangle.vertices[(k + 1) % 3] == edge[1]);
+}
+
+fn flip(triangle: &mut Triangle) {
+    triangle.vertices.swap(1, 2);
+    triangle.normals.swap(1, 2);
+    triangle.textures.swap(1, 2);
+}
@@ -0,0 +1,511 @@
+use crate::linear_algebra;
+use crate::mesh::{Coords2D, Coords3D, Mesh, Point3D, PolygonMesh, Triangle};
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::PI;
+
+// Loop subdivision of triangle meshes and Catmull-Clark subdivision of polygon meshes. Each level
+// adds a point on every edge, and for Catmull-Clark on every face, and moves the original vertices
+// towards the limit surface.
+//
+// Sharp edges stay sharp: boundaries, edges of more than two faces, the requested creases and the
+// edges whose faces turn by more than the crease angle. Their new points are the midpoints, a
+// vertex on two of them follows the curve they make, and a vertex on three or more is a corner
+// which does not move. Faces only meet across edges sharing their position indices, so meshes
+// duplicating positions along UV seams should be welded first, see `Mesh::weld_vertices`.
+//
+// UVs are interpolated linearly, which keeps seams and texture borders in place. Normals are
+// recomputed from the refined surface, smooth except across sharp edges.
+
+#[derive(Clone, Debug)]
+pub struct SubdivisionOptions {
+    pub levels: u32,
+    // Edges of the input whose faces turn by more than this, in degrees, are sharp. The default
+    // of 180 makes none sharp.
+    pub crease_angle: f32,
+    // Sharp edges, as pairs of position indices of the input.
+    pub creases: Vec<[usize; 2]>,
+}
+
+impl Default for SubdivisionOptions {
+    fn default() -> Self {
+        SubdivisionOptions {
+            levels: 1,
+            crease_angle: 180f32,
+            creases: Vec::new(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Scheme {
+    Loop,
+    CatmullClark,
+}
+
+#[derive(Clone, Debug)]
+struct Face {
+    vertices: Vec<usize>,
+    textures: Vec<usize>,
+    material: usize,
+}
+
+// A mesh between two levels, sharp edges being keyed by their sorted vertex indices.
+struct Level {
+    positions: Vec<Point3D<f32>>,
+    uvs: Vec<(f32, f32)>,
+    faces: Vec<Face>,
+    sharp: HashSet<[usize; 2]>,
+}
+
+impl Mesh {
+    pub fn loop_subdivide(&self, options: &SubdivisionOptions) -> Mesh {
+        let faces: Vec<Face> = self
+            .triangles
+            .iter()
+            .map(|triangle| Face {
+                vertices: triangle.vertices.to_vec(),
+                textures: triangle.textures.to_vec(),
+                material: triangle.material,
+            })
+            .collect();
+        let level: Level = Level::new(&self.v_positions, &self.v_textures, faces, options);
+        return subdivide(level, Scheme::Loop, options.levels).to_mesh(self.materials.clone());
+    }
+}
+
+impl PolygonMesh {
+    // The first level turns every polygon into quads, one per corner.
+    pub fn catmull_clark(&self, options: &SubdivisionOptions) -> Mesh {
+        let faces: Vec<Face> = self
+            .polygons
+            .iter()
+            .map(|polygon| Face {
+                vertices: polygon.vertices.clone(),
+                textures: polygon.textures.clone(),
+                material: polygon.material,
+            })
+            .collect();
+        let level: Level = Level::new(&self.v_positions, &self.v_textures, faces, options);
+        return subdivide(level, Scheme::CatmullClark, options.levels)
+            .to_mesh(self.materials.clone());
+    }
+}
+
+fn subdivide(mut level: Level, scheme: Scheme, levels: u32) -> Level {
+    for _ in 0..levels {
+        level = level.refine(scheme);
+    }
+    return level;
+}
+
+impl Level {
+    fn new(
+        positions: &Coords3D,
+        textures: &Coords2D,
+        faces: Vec<Face>,
+        options: &SubdivisionOptions,
+    ) -> Self {
+        let mut level: Level = Level {
+            positions: (0..positions.xs.len())
+                .map(|v| positions.get_at(v))
+                .collect(),
+            uvs: (0..textures.us.len()).map(|t| textures.get_at(t)).collect(),
+            faces,
+            sharp: options
+                .creases
+                .iter()
+                .map(|&[a, b]| edge_key(a, b))
+                .collect(),
+        };
+        let crease_cos: f32 = options.crease_angle.to_radians().cos();
+        let normals: Vec<Point3D<f32>> = level.face_normals();
+        for (edge, faces) in level.edge_faces() {
+            let is_crease: bool = faces.len() == 2 && {
+                let (n1, n2) = (normals[faces[0]], normals[faces[1]]);
+                linear_algebra::dot_product(n1, n2)
+                    < crease_cos * linear_algebra::norm(n1) * linear_algebra::norm(n2)
+            };
+            if faces.len() != 2 || is_crease {
+                level.sharp.insert(edge);
+            }
+        }
+        return level;
+    }
+
+    fn refine(&self, scheme: Scheme) -> Level {
+        let edges: HashMap<[usize; 2], Vec<usize>> = self.edge_faces();
+        let mut edge_keys: Vec<[usize; 2]> = edges.keys().copied().collect();
+        edge_keys.sort();
+        let vertex_count: usize = self.positions.len();
+        let edge_points: HashMap<[usize; 2], usize> = edge_keys
+            .iter()
+            .enumerate()
+            .map(|(i, &edge)| (edge, vertex_count + i))
+            .collect();
+
+        let face_points: Vec<Point3D<f32>> = self
+            .faces
+            .iter()
+            .map(|face| mean(face.vertices.iter().map(|&v| self.positions[v]).collect()))
+            .collect();
+
+        let mut neighbours: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+        for &[a, b] in &edge_keys {
+            neighbours[a].push(b);
+            neighbours[b].push(a);
+        }
+        let mut vertex_faces: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+        for (f, face) in self.faces.iter().enumerate() {
+            for &v in &face.vertices {
+                vertex_faces[v].push(f);
+            }
+        }
+
+        let mut positions: Vec<Point3D<f32>> = Vec::with_capacity(
+            vertex_count
+                + edge_keys.len()
+                + self.faces.len() * (scheme == Scheme::CatmullClark) as usize,
+        );
+        for v in 0..vertex_count {
+            positions.push(self.vertex_point(
+                v,
+                &neighbours[v],
+                &vertex_faces[v],
+                &face_points,
+                scheme,
+            ));
+        }
+        for edge in &edge_keys {
+            positions.push(self.edge_point(*edge, &edges[edge], &face_points, scheme));
+        }
+        let face_point_base: usize = positions.len();
+        if scheme == Scheme::CatmullClark {
+            positions.extend_from_slice(&face_points);
+        }
+
+        // Corner UVs keep their index, new ones are shared by the corners of the same texture
+        // edge.
+        let mut uvs: Vec<(f32, f32)> = self.uvs.clone();
+        let mut edge_uvs: HashMap<[usize; 2], usize> = HashMap::new();
+        let mut edge_uv = |uvs: &mut Vec<(f32, f32)>, a: usize, b: usize| -> usize {
+            return *edge_uvs.entry(edge_key(a, b)).or_insert_with(|| {
+                let (p, q) = (uv_at(&self.uvs, a), uv_at(&self.uvs, b));
+                uvs.push(((p.0 + q.0) / 2f32, (p.1 + q.1) / 2f32));
+                uvs.len() - 1
+            });
+        };
+
+        let mut faces: Vec<Face> = Vec::new();
+        for (f, face) in self.faces.iter().enumerate() {
+            let n: usize = face.vertices.len();
+            let mut mid_vertices: Vec<usize> = Vec::with_capacity(n);
+            let mut mid_textures: Vec<usize> = Vec::with_capacity(n);
+            for i in 0..n {
+                let j: usize = (i + 1) % n;
+                mid_vertices.push(edge_points[&edge_key(face.vertices[i], face.vertices[j])]);
+                mid_textures.push(edge_uv(&mut uvs, face.textures[i], face.textures[j]));
+            }
+            let corner = |vertices: Vec<usize>, textures: Vec<usize>| -> Face {
+                return Face {
+                    vertices,
+                    textures,
+                    material: face.material,
+                };
+            };
+            match scheme {
+                Scheme::Loop => {
+                    let (v, t) = (&face.vertices, &face.textures);
+                    let (m, u) = (&mid_vertices, &mid_textures);
+                    faces.push(corner(vec![v[0], m[0], m[2]], vec![t[0], u[0], u[2]]));
+                    faces.push(corner(vec![m[0], v[1], m[1]], vec![u[0], t[1], u[1]]));
+                    faces.push(corner(vec![m[2], m[1], v[2]], vec![u[2], u[1], t[2]]));
+                    faces.push(corner(m.clone(), u.clone()));
+                }
+                Scheme::CatmullClark => {
+                    let centre: (f32, f32) = face
+                        .textures
+                        .iter()
+                        .map(|&t| uv_at(&self.uvs, t))
+                        .fold((0f32, 0f32), |sum, uv| {
+                            (sum.0 + uv.0 / n as f32, sum.1 + uv.1 / n as f32)
+                        });
+                    uvs.push(centre);
+                    let centre_texture: usize = uvs.len() - 1;
+                    for i in 0..n {
+                        let previous: usize = (i + n - 1) % n;
+                        faces.push(corner(
+                            vec![
+                                face.vertices[i],
+                                mid_vertices[i],
+                                face_point_base + f,
+                                mid_vertices[previous],
+                            ],
+                            vec![
+                                face.textures[i],
+                                mid_textures[i],
+                                centre_texture,
+                                mid_textures[previous],
+                            ],
+                        ));
+                    }
+                }
+            }
+        }
+
+        let mut sharp: HashSet<[usize; 2]> = HashSet::new();
+        for &[a, b] in &self.sharp {
+            if let Some(&middle) = edge_points.get(&[a, b]) {
+                sharp.insert(edge_key(a, middle));
+                sharp.insert(edge_key(middle, b));
+            }
+        }
+        return Level {
+            positions,
+            uvs,
+            faces,
+            sharp,
+        };
+    }
+
+    fn vertex_point(
+        &self,
+        v: usize,
+        neighbours: &[usize],
+        faces: &[usize],
+        face_points: &[Point3D<f32>],
+        scheme: Scheme,
+    ) -> Point3D<f32> {
+        let p: Point3D<f32> = self.positions[v];
+        let sharp: Vec<usize> = neighbours
+            .iter()
+            .copied()
+            .filter(|&w| self.sharp.contains(&edge_key(v, w)))
+            .collect();
+        if neighbours.is_empty() || sharp.len() > 2 {
+            return p;
+        }
+        if sharp.len() == 2 {
+            let (a, b) = (self.positions[sharp[0]], self.positions[sharp[1]]);
+            return combine(&[(0.75f32, p), (0.125f32, a), (0.125f32, b)]);
+        }
+
+        let n: f32 = neighbours.len() as f32;
+        let ring: Point3D<f32> = mean(neighbours.iter().map(|&w| self.positions[w]).collect());
+        match scheme {
+            Scheme::Loop => {
+                let beta: f32 =
+                    (0.625f32 - (0.375f32 + 0.25f32 * (2f32 * PI / n).cos()).powi(2)) / n;
+                return combine(&[(1f32 - n * beta, p), (n * beta, ring)]);
+            }
+            Scheme::CatmullClark => {
+                // (F + 2R + (n - 3)P) / n, with R the mean of the edge midpoints.
+                let face_mean: Point3D<f32> = mean(faces.iter().map(|&f| face_points[f]).collect());
+                return combine(&[(1f32 / n, face_mean), (1f32 / n, ring), ((n - 2f32) / n, p)]);
+            }
+        }
+    }
+
+    fn edge_point(
+        &self,
+        [a, b]: [usize; 2],
+        faces: &[usize],
+        face_points: &[Point3D<f32>],
+        scheme: Scheme,
+    ) -> Point3D<f32> {
+        let (p, q) = (self.positions[a], self.positions[b]);
+        if self.sharp.contains(&[a, b]) || faces.len() != 2 {
+            return combine(&[(0.5f32, p), (0.5f32, q)]);
+        }
+        let (f1, f2) = (faces[0], faces[1]);
+        match scheme {
+            Scheme::Loop => {
+                let opposite = |f: usize| -> Point3D<f32> {
+                    let face: &Face = &self.faces[f];
+                    let c: usize = face
+                        .vertices
+                        .iter()
+                        .copied()
+                        .find(|&v| v != a && v != b)
+                        .unwrap_or(a);
+                    return self.positions[c];
+                };
+                return combine(&[
+                    (0.375f32, p),
+                    (0.375f32, q),
+                    (0.125f32, opposite(f1)),
+                    (0.125f32, opposite(f2)),
+                ]);
+            }
+            Scheme::CatmullClark => {
+                return combine(&[
+                    (0.25f32, p),
+                    (0.25f32, q),
+                    (0.25f32, face_points[f1]),
+                    (0.25f32, face_points[f2]),
+                ]);
+            }
+        }
+    }
+
+    fn edge_faces(&self) -> HashMap<[usize; 2], Vec<usize>> {
+        let mut edges: HashMap<[usize; 2], Vec<usize>> = HashMap::new();
+        for (f, face) in self.faces.iter().enumerate() {
+            let n: usize = face.vertices.len();
+            for i in 0..n {
+                let edge: [usize; 2] = edge_key(face.vertices[i], face.vertices[(i + 1) % n]);
+                edges.entry(edge).or_default().push(f);
+            }
+        }
+        return edges;
+    }
+
+    // Sum of the cross products along the face, twice its area for a planar polygon.
+    fn face_normals(&self) -> Vec<Point3D<f32>> {
+        return self
+            .faces
+            .iter()
+            .map(|face| {
+                let n: usize = face.vertices.len();
+                let terms: Vec<(f32, Point3D<f32>)> = (0..n)
+                    .map(|i| {
+                        let p: Point3D<f32> = self.positions[face.vertices[i]];
+                        let q: Point3D<f32> = self.positions[face.vertices[(i + 1) % n]];
+                        (1f32, linear_algebra::cross_product(p, q))
+                    })
+                    .collect();
+                combine(&terms)
+            })
+            .collect();
+    }
+
+    // Polygons are split into fans. Corners around a vertex share a normal unless a sharp edge
+    // separates them.
+    fn to_mesh(&self, materials: Vec<crate::mesh::Material>) -> Mesh {
+        let face_normals: Vec<Point3D<f32>> = self.face_normals();
+        let mut first_corner: Vec<usize> = Vec::with_capacity(self.faces.len());
+        let mut corner_count: usize = 0;
+        for face in &self.faces {
+            first_corner.push(corner_count);
+            corner_count += face.vertices.len();
+        }
+        let corner_of = |f: usize, v: usize| -> usize {
+            let i: usize = self.faces[f].vertices.iter().position(|&w| w == v).unwrap();
+            return first_corner[f] + i;
+        };
+
+        let mut groups: Vec<usize> = (0..corner_count).collect();
+        for ([a, b], faces) in self.edge_faces() {
+            if faces.len() == 2 && !self.sharp.contains(&[a, b]) {
+                for v in [a, b] {
+                    let (x, y) = (
+                        find(&mut groups, corner_of(faces[0], v)),
+                        find(&mut groups, corner_of(faces[1], v)),
+                    );
+                    groups[x] = y;
+                }
+            }
+        }
+
+        let mut sums: HashMap<usize, Point3D<f32>> = HashMap::new();
+        for (f, face) in self.faces.iter().enumerate() {
+            for i in 0..face.vertices.len() {
+                let group: usize = find(&mut groups, first_corner[f] + i);
+                let sum: &mut Point3D<f32> = sums.entry(group).or_insert(Point3D {
+                    x: 0f32,
+                    y: 0f32,
+                    z: 0f32,
+                });
+                *sum = combine(&[(1f32, *sum), (1f32, face_normals[f])]);
+            }
+        }
+        let mut v_normals: Coords3D = Coords3D {
+            xs: Vec::new(),
+            ys: Vec::new(),
+            zs: Vec::new(),
+        };
+        let mut normal_index: HashMap<usize, usize> = HashMap::new();
+        let mut corner_normals: Vec<usize> = Vec::with_capacity(corner_count);
+        for corner in 0..corner_count {
+            let group: usize = find(&mut groups, corner);
+            let index: usize = *normal_index.entry(group).or_insert_with(|| {
+                let n: Point3D<f32> = linear_algebra::normalized(sums[&group]);
+                v_normals.add_vector(n.x, n.y, n.z);
+                v_normals.xs.len() - 1
+            });
+            corner_normals.push(index);
+        }
+
+        let mut triangles: Vec<Triangle> = Vec::new();
+        for (f, face) in self.faces.iter().enumerate() {
+            for i in 2..face.vertices.len() {
+                let corners: [usize; 3] = [0, i - 1, i];
+                triangles.push(Triangle {
+                    vertices: corners.map(|k| face.vertices[k]),
+                    normals: corners.map(|k| corner_normals[first_corner[f] + k]),
+                    textures: corners.map(|k| face.textures[k]),
+                    material: face.material,
+                });
+            }
+        }
+        let mut v_positions: Coords3D = Coords3D {
+            xs: Vec::new(),
+            ys: Vec::new(),
+            zs: Vec::new(),
+        };
+        for p in &self.positions {
+            v_positions.add_vector(p.x, p.y, p.z);
+        }
+        let mut v_textures: Coords2D = Coords2D {
+            us: Vec::new(),
+            vs: Vec::new(),
+        };
+        for &(u, v) in &self.uvs {
+            v_textures.add_vector(u, v);
+        }
+        return Mesh {
+            v_positions,
+            v_normals,
+            v_textures,
+            triangles,
+            materials,
+        };
+    }
+}
+
+fn edge_key(a: usize, b: usize) -> [usize; 2] {
+    return [a.min(b), a.max(b)];
+}
+
+// Texture indices past the end of the UVs, as in meshes without any, read as the origin.
+fn uv_at(uvs: &[(f32, f32)], t: usize) -> (f32, f32) {
+    return uvs.get(t).copied().unwrap_or((0f32, 0f32));
+}
+
+fn find(parents: &mut [usize], mut x: usize) -> usize {
+    while parents[x] != x {
+        parents[x] = parents[parents[x]];
+        x = parents[x];
+    }
+    return x;
+}
+
+fn combine(terms: &[(f32, Point3D<f32>)]) -> Point3D<f32> {
+    return terms.iter().fold(
+        Point3D {
+            x: 0f32,
+            y: 0f32,
+            z: 0f32,
+        },
+        |sum, &(weight, p)| Point3D {
+            x: sum.x + weight * p.x,
+            y: sum.y + weight * p.y,
+            z: sum.z + weight * p.z,
+        },
+    );
+}
+
+fn mean(points: Vec<Point3D<f32>>) -> Point3D<f32> {
+    let weight: f32 = 1f32 / points.len().max(1) as f32;
+    let terms: Vec<(f32, Point3D<f32>)> = points.into_iter().map(|p| (weight, p)).collect();
+    return combine(&terms);
+}
@@ -0,0 +1,105 @@
+use crate::drawers::{self, FlatPolygon, Segment2D, Viewport};
+use crate::mesh::{Mesh, Point2D};
+use crate::tga;
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+};
+
+pub struct Document {
+    pub width: u32,
+    pub height: u32,
+    elements: Vec<String>,
+}
+
+impl Document {
+    pub fn new(width: u32, height: u32) -> Self {
+        Document {
+            width,
+            height,
+            elements: Vec::new(),
+        }
+    }
+
+    // Polygons are painted in the given order, so they should already be depth sorted.
+    pub fn add_polygons(&mut self, polygons: &[FlatPolygon]) {
+        for polygon in polygons {
+            let mut points: String = String::new();
+            for vertex in &polygon.vertices {
+                let (x, y) = self.to_svg_space(vertex);
+                let _ = write!(points, "{:.2},{:.2} ", x, y);
+            }
+            let fill: String = hex_color(polygon.color);
+            self.elements.push(format!(
+                "<polygon points=\"{}\" fill=\"{}\" stroke=\"{}\" stroke-width=\"0.5\"/>",
+                points.trim_end(),
+                fill,
+                fill
+            ));
+        }
+    }
+
+    // All segments go into a single path with one subpath each.
+    pub fn add_segments(&mut self, segments: &[Segment2D], color: tga::Rgb, stroke_width: f32) {
+        if segments.is_empty() {
+            return;
+        }
+        let mut path: String = String::new();
+        for segment in segments {
+            let (x0, y0) = self.to_svg_space(&segment.start);
+            let (x1, y1) = self.to_svg_space(&segment.end);
+            let _ = write!(path, "M{:.2} {:.2}L{:.2} {:.2}", x0, y0, x1, y1);
+        }
+        self.elements.push(format!(
+            "<path d=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" stroke-linecap=\"round\"/>",
+            path,
+            hex_color(color),
+            stroke_width
+        ));
+    }
+
+    pub fn write<W: io::Write>(&self, writer: &mut io::BufWriter<W>) -> io::Result<()> {
+        writeln!(writer, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(
+            writer,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">",
+            self.width, self.height, self.width, self.height
+        )?;
+        for element in &self.elements {
+            writeln!(writer, "{}", element)?;
+        }
+        writeln!(writer, "</svg>")?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    // The drawers put the origin at the bottom left corner, SVG puts it at the top left one.
+    fn to_svg_space(&self, point: &Point2D<f32>) -> (f32, f32) {
+        return (point.x, self.height as f32 - point.y);
+    }
+}
+
+pub fn wireframe(mesh: &Mesh, color: tga::Rgb, filled: bool, viewport: Viewport) -> Document {
+    let mut document: Document = new_document(mesh, filled, viewport);
+    document.add_segments(&drawers::wireframe_segments(mesh, viewport), color, 1f32);
+    return document;
+}
+
+pub fn hidden_line(mesh: &Mesh, color: tga::Rgb, filled: bool, viewport: Viewport) -> Document {
+    let mut document: Document = new_document(mesh, filled, viewport);
+    document.add_segments(&drawers::hidden_line_segments(mesh, viewport), color, 1f32);
+    return document;
+}
+
+fn new_document(mesh: &Mesh, filled: bool, viewport: Viewport) -> Document {
+    let mut document: Document = Document::new(viewport.width, viewport.height);
+    if filled {
+        document.add_polygons(&drawers::flat_shaded_polygons(mesh, viewport));
+    }
+    return document;
+}
+
+fn hex_color(color: tga::Rgb) -> String {
+    let (r, g, b) = (color.r, color.g, color.b);
+    return format!("#{:02x}{:02x}{:02x}", r, g, b);
+}
@@ -1,9 +1,13 @@
+use crate::tonemap;
 use std::{
+    ffi::OsString,
     fmt,
+    fs::File,
     io::{self, prelude::*},
     iter::Iterator,
     ops::Shl,
-    slice::Iter,
+    path::{Path, PathBuf},
+    slice::{Chunks, ChunksMut, Iter, IterMut},
 };
 
 pub trait ColorSpace {
@@ -13,12 +17,14 @@ pub trait ColorSpace {
 
 #[derive(Copy, Clone, Debug)]
 #[repr(packed)]
+#[allow(clippy::repr_packed_without_abi)]
 pub struct Grayscale {
     pub i: u8,
 }
 
 #[derive(Copy, Clone, Debug)]
 #[repr(packed)]
+#[allow(clippy::repr_packed_without_abi)]
 pub struct Rgb {
     pub b: u8,
     pub g: u8,
@@ -27,6 +33,7 @@ pub struct Rgb {
 
 #[derive(Copy, Clone, Debug)]
 #[repr(packed)]
+#[allow(clippy::repr_packed_without_abi)]
 pub struct Rgba {
     pub b: u8,
     pub g: u8,
@@ -34,6 +41,16 @@ pub struct Rgba {
     pub a: u8,
 }
 
+// Linear, unbounded colour for HDR rendering. It has no TGA encoding and goes through
+// `tonemap::tone_map` before being written.
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub struct RgbF32 {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+}
+
 impl ColorSpace for Grayscale {
     fn new() -> Self {
         Grayscale { i: 0 }
@@ -60,10 +77,76 @@ impl ColorSpace for Rgba {
     const BPP: u8 = 4;
 }
 
+impl ColorSpace for RgbF32 {
+    fn new() -> Self {
+        RgbF32 {
+            r: 0f32,
+            g: 0f32,
+            b: 0f32,
+        }
+    }
+    const BPP: u8 = 12;
+}
+
+#[derive(Clone)]
 pub struct Image<T: ColorSpace> {
     pub data: Vec<T>,
-    pub width: u16,
-    pub height: u16,
+    pub width: u32,
+    pub height: u32,
+}
+
+// 8 bit pixels, whose channels are listed in the usual grey, RGB or RGBA order, whatever their
+// order in memory.
+pub trait PixelChannels: ColorSpace + Copy {
+    fn push_channels(&self, bytes: &mut Vec<u8>);
+    // `channels` holds exactly BPP values.
+    fn from_channels(channels: &[u8]) -> Self;
+}
+
+impl PixelChannels for Grayscale {
+    fn push_channels(&self, bytes: &mut Vec<u8>) {
+        bytes.push(self.i);
+    }
+
+    fn from_channels(channels: &[u8]) -> Self {
+        Grayscale { i: channels[0] }
+    }
+}
+
+impl PixelChannels for Rgb {
+    fn push_channels(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&[self.r, self.g, self.b]);
+    }
+
+    fn from_channels(channels: &[u8]) -> Self {
+        Rgb {
+            b: channels[2],
+            g: channels[1],
+            r: channels[0],
+        }
+    }
+}
+
+impl PixelChannels for Rgba {
+    fn push_channels(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&[self.r, self.g, self.b, self.a]);
+    }
+
+    fn from_channels(channels: &[u8]) -> Self {
+        Rgba {
+            b: channels[2],
+            g: channels[1],
+            r: channels[0],
+            a: channels[3],
+        }
+    }
+}
+
+// Image whose pixel format is only known at runtime, as returned by the decoders.
+pub enum DynamicImage {
+    Grayscale(Image<Grayscale>),
+    Rgb(Image<Rgb>),
+    Rgba(Image<Rgba>),
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -81,11 +164,29 @@ impl fmt::Display for Error {
     }
 }
 
+// Decoders refuse headers asking for more decoded pixel bytes than this, so that a forged file
+// cannot request an impossible allocation.
+pub(crate) const MAX_DECODED_BYTES: usize = 1 << 30;
+
+// Bytes taken by `width` x `height` pixels of `bytes_per_pixel` bytes, unless that overflows or
+// exceeds MAX_DECODED_BYTES.
+pub(crate) fn decoded_size(
+    width: usize,
+    height: usize,
+    bytes_per_pixel: usize,
+) -> Result<usize, Error> {
+    return width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(bytes_per_pixel))
+        .filter(|&size| size <= MAX_DECODED_BYTES)
+        .ok_or(Error::InvalidDimensions);
+}
+
 unsafe fn any_as_u8_slice<T: Sized>(p: &T) -> &[u8] {
     std::slice::from_raw_parts((p as *const T) as *const u8, std::mem::size_of::<T>())
 }
 
-struct PixelsIter<'a, T: ColorSpace> {
+pub struct PixelsIter<'a, T: ColorSpace> {
     iter: Iter<'a, T>,
 }
 
@@ -95,26 +196,51 @@ impl<'a, T: ColorSpace> Iterator for PixelsIter<'a, T> {
     fn next(&mut self) -> Option<Self::Item> {
         self.iter.next()
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, T: ColorSpace> DoubleEndedIterator for PixelsIter<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back()
+    }
 }
 
-//impl<'a, T: ColorSpace> IntoIterator for Image<T> {
-//    type Item = &'a T;
-//    type IntoIter = Iterator<'a, T>;
-//
-//    fn into_iter(self) -> Self::IntoIter {
-//        self.data.into_iter()
-//    }
-//}
+impl<'a, T: ColorSpace> ExactSizeIterator for PixelsIter<'a, T> {}
 
-//impl<'a, T: ColorSpace> Iterator for PixelsIter<'a, T> {
-//    type Item = T;
-//
-//    fn next(&mut self) -> Option<Self::Item> {
-//    }
-//}
+impl<'a, T: ColorSpace> IntoIterator for &'a Image<T> {
+    type Item = &'a T;
+    type IntoIter = PixelsIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        PixelsIter {
+            iter: self.data.iter(),
+        }
+    }
+}
+
+impl<'a, T: ColorSpace> IntoIterator for &'a mut Image<T> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.data.iter_mut()
+    }
+}
+
+impl<T: ColorSpace> IntoIterator for Image<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.data.into_iter()
+    }
+}
 
 impl<T: ColorSpace + Copy> Image<T> {
-    pub fn new(width: u16, height: u16) -> Self {
+    pub fn new(width: u32, height: u32) -> Self {
         Image {
             width,
             height,
@@ -122,18 +248,145 @@ impl<T: ColorSpace + Copy> Image<T> {
         }
     }
 
-    pub fn set(&mut self, x: u16, y: u16, color: T) -> Result<(), Error> {
-        //if x >= self.width || y >= self.height {
-        //    println!("Invalid dimensions: {}, {}", x, y);
-        //    return Err(Error::InvalidDimensions);
-        //}
+    pub fn set(&mut self, x: u32, y: u32, color: T) -> Result<(), Error> {
+        if x >= self.width || y >= self.height {
+            return Err(Error::InvalidDimensions);
+        }
         let ix = self.pixel_offset(x, y);
         self.data[ix] = color;
         Ok(())
     }
 
-    fn pixel_offset(&self, x: u16, y: u16) -> usize {
-        (y as usize * self.width as usize + x as usize).into()
+    pub fn get(&self, x: u32, y: u32) -> Option<&T> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        return self.data.get(self.pixel_offset(x, y));
+    }
+
+    pub fn get_mut(&mut self, x: u32, y: u32) -> Option<&mut T> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let ix: usize = self.pixel_offset(x, y);
+        return self.data.get_mut(ix);
+    }
+
+    pub fn fill(&mut self, color: T) {
+        self.data.fill(color);
+    }
+
+    pub fn pixels(&self) -> PixelsIter<'_, T> {
+        return self.into_iter();
+    }
+
+    pub fn pixels_mut(&mut self) -> IterMut<'_, T> {
+        return self.data.iter_mut();
+    }
+
+    // Rows in memory order, which is bottom to top for the drawers output.
+    pub fn rows(&self) -> Chunks<'_, T> {
+        return self.data.chunks(self.width.max(1) as usize);
+    }
+
+    pub fn rows_mut(&mut self) -> ChunksMut<'_, T> {
+        return self.data.chunks_mut(self.width.max(1) as usize);
+    }
+
+    // Rectangle of the image starting at (x, y), which must fit inside it.
+    pub fn view(&self, x: u32, y: u32, width: u32, height: u32) -> Result<SubImage<'_, T>, Error> {
+        if x as usize + width as usize > self.width as usize
+            || y as usize + height as usize > self.height as usize
+        {
+            return Err(Error::InvalidDimensions);
+        }
+        return Ok(SubImage {
+            image: self,
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    pub fn view_mut(
+        &mut self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<SubImageMut<'_, T>, Error> {
+        if x as usize + width as usize > self.width as usize
+            || y as usize + height as usize > self.height as usize
+        {
+            return Err(Error::InvalidDimensions);
+        }
+        return Ok(SubImageMut {
+            image: self,
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    // Mirrors left and right.
+    pub fn flip_horizontal(&mut self) {
+        for row in self.rows_mut() {
+            row.reverse();
+        }
+    }
+
+    // Mirrors top and bottom.
+    pub fn flip_vertical(&mut self) {
+        let width: usize = self.width as usize;
+        let height: usize = self.height as usize;
+        for y in 0..height / 2 {
+            let (top, bottom) = self.data.split_at_mut((height - 1 - y) * width);
+            top[y * width..(y + 1) * width].swap_with_slice(&mut bottom[..width]);
+        }
+    }
+
+    pub fn transpose(&self) -> Image<T> {
+        let mut transposed: Image<T> = Image::new(self.height, self.width);
+        for (y, row) in self.rows().enumerate() {
+            for (x, pixel) in row.iter().enumerate() {
+                transposed.data[x * self.height as usize + y] = *pixel;
+            }
+        }
+        return transposed;
+    }
+
+    // Copies `src` with its first pixel at (x, y), dropping whatever falls outside this image.
+    pub fn blit(&mut self, src: &Image<T>, x: i64, y: i64) {
+        let x_start: i64 = x.max(0);
+        let y_start: i64 = y.max(0);
+        let x_end: i64 = (x + src.width as i64).min(self.width as i64);
+        let y_end: i64 = (y + src.height as i64).min(self.height as i64);
+        if x_start >= x_end || y_start >= y_end {
+            return;
+        }
+        for dst_y in y_start..y_end {
+            let src_y: usize = (dst_y - y) as usize;
+            let src_x: usize = (x_start - x) as usize;
+            let len: usize = (x_end - x_start) as usize;
+            let src_offset: usize = src_y * src.width as usize + src_x;
+            let dst_offset: usize = dst_y as usize * self.width as usize + x_start as usize;
+            self.data[dst_offset..dst_offset + len]
+                .copy_from_slice(&src.data[src_offset..src_offset + len]);
+        }
+    }
+
+    pub fn convert<U: ColorSpace + Copy + From<T>>(&self) -> Image<U> {
+        return Image {
+            data: self.data.iter().map(|pixel| U::from(*pixel)).collect(),
+            width: self.width,
+            height: self.height,
+        };
+    }
+
+    fn pixel_offset(&self, x: u32, y: u32) -> usize {
+        y as usize * self.width as usize + x as usize
     }
 
     fn data_vec(&self) -> Vec<u8> {
@@ -150,11 +403,29 @@ impl<T: ColorSpace + Copy> Image<T> {
         vflip: bool,
         rle: bool,
     ) -> io::Result<()> {
+        if T::BPP > Rgba::BPP {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "TGA only stores 8 bits per channel, tone map the image first",
+            ));
+        }
+        if self.width > MAX_TGA_DIMENSION || self.height > MAX_TGA_DIMENSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{}: {}x{} exceeds the TGA limit of {} pixels, use write_tiles",
+                    Error::InvalidDimensions,
+                    self.width,
+                    self.height,
+                    MAX_TGA_DIMENSION
+                ),
+            ));
+        }
         let header = Header {
             idlength: 0,
             bitsperpixel: T::BPP.shl(3),
-            width: self.width,
-            height: self.height,
+            width: self.width as u16,
+            height: self.height as u16,
             colormaptype: 0,
             datatypecode: if T::BPP == Grayscale::BPP {
                 if rle {
@@ -175,9 +446,13 @@ impl<T: ColorSpace + Copy> Image<T> {
 
         writer.write_all(unsafe { any_as_u8_slice(&header) })?;
         if !rle {
-            writer.write_all(&self.data_vec().as_slice())?;
+            writer.write_all(self.data_vec().as_slice())?;
         } else {
-            todo!();
+            // Packets stop at the end of each row, as the specification recommends.
+            let row_size: usize = self.width as usize * T::BPP as usize;
+            for row in self.data_vec().chunks(row_size.max(1)) {
+                write_rle_row(writer, row, T::BPP as usize)?;
+            }
         }
 
         writer.write_all(&DEVELOPER_AREA_REF)?;
@@ -187,15 +462,308 @@ impl<T: ColorSpace + Copy> Image<T> {
 
         Ok(())
     }
+
+    // Splits the image into TGA files of at most `tile_width` x `tile_height` pixels, named
+    // `<prefix>_<column>_<row>.tga` with row 0 at the top of the picture. Returns their paths.
+    pub fn write_tiles<P: AsRef<Path>>(
+        &self,
+        prefix: P,
+        tile_width: u16,
+        tile_height: u16,
+        vflip: bool,
+        rle: bool,
+    ) -> io::Result<Vec<PathBuf>> {
+        if tile_width == 0 || tile_height == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                Error::InvalidDimensions.to_string(),
+            ));
+        }
+        let columns: u32 = self.width.div_ceil(tile_width as u32);
+        let rows: u32 = self.height.div_ceil(tile_height as u32);
+        let mut paths: Vec<PathBuf> = Vec::with_capacity((columns * rows) as usize);
+        for tile_y in 0..rows {
+            for tile_x in 0..columns {
+                let x: u32 = tile_x * tile_width as u32;
+                let y: u32 = tile_y * tile_height as u32;
+                let tile: Image<T> = self
+                    .view(
+                        x,
+                        y,
+                        (tile_width as u32).min(self.width - x),
+                        (tile_height as u32).min(self.height - y),
+                    )
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?
+                    .to_image();
+                let row: u32 = if vflip { rows - 1 - tile_y } else { tile_y };
+                let mut path: OsString = prefix.as_ref().as_os_str().to_owned();
+                path.push(format!("_{}_{}.tga", tile_x, row));
+                let path: PathBuf = PathBuf::from(path);
+                let mut writer: io::BufWriter<File> = io::BufWriter::new(File::create(&path)?);
+                tile.write(&mut writer, vflip, rle)?;
+                paths.push(path);
+            }
+        }
+        return Ok(paths);
+    }
+}
+
+// Borrowed rectangle of an image, with coordinates relative to its corner.
+pub struct SubImage<'a, T: ColorSpace> {
+    image: &'a Image<T>,
+    x: u32,
+    y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl<'a, T: ColorSpace + Copy> SubImage<'a, T> {
+    pub fn get(&self, x: u32, y: u32) -> Option<&'a T> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        return self.image.get(self.x + x, self.y + y);
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &'a [T]> + '_ {
+        let image: &'a Image<T> = self.image;
+        return (0..self.height as usize).map(move |y| {
+            let start: usize = image.pixel_offset(self.x, self.y + y as u32);
+            &image.data[start..start + self.width as usize]
+        });
+    }
+
+    pub fn to_image(&self) -> Image<T> {
+        return Image {
+            data: self.rows().flatten().copied().collect(),
+            width: self.width,
+            height: self.height,
+        };
+    }
+}
+
+pub struct SubImageMut<'a, T: ColorSpace> {
+    image: &'a mut Image<T>,
+    x: u32,
+    y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl<'a, T: ColorSpace + Copy> SubImageMut<'a, T> {
+    pub fn get(&self, x: u32, y: u32) -> Option<&T> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        return self.image.get(self.x + x, self.y + y);
+    }
+
+    pub fn get_mut(&mut self, x: u32, y: u32) -> Option<&mut T> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        return self.image.get_mut(self.x + x, self.y + y);
+    }
+
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [T]> + '_ {
+        let (x, width) = (self.x as usize, self.width as usize);
+        let image_width: usize = self.image.width.max(1) as usize;
+        let first: usize = self.y as usize * image_width;
+        let last: usize = first + self.height as usize * image_width;
+        return self.image.data[first..last]
+            .chunks_mut(image_width)
+            .map(move |row| &mut row[x..x + width]);
+    }
+
+    pub fn fill(&mut self, color: T) {
+        for row in self.rows_mut() {
+            row.fill(color);
+        }
+    }
+
+    pub fn to_image(&self) -> Image<T> {
+        let mut image: Image<T> = Image::new(self.width, self.height);
+        for y in 0..self.height {
+            let start: usize = self.image.pixel_offset(self.x, self.y + y);
+            let row: usize = image.pixel_offset(0, y);
+            image.data[row..row + self.width as usize]
+                .copy_from_slice(&self.image.data[start..start + self.width as usize]);
+        }
+        return image;
+    }
+}
+
+impl<T: ColorSpace + Copy, U: ColorSpace + Copy + From<T>> From<&Image<T>> for Image<U> {
+    fn from(image: &Image<T>) -> Self {
+        image.convert()
+    }
+}
+
+// 8 bit colours are sRGB encoded while RgbF32 is linear. Alpha is dropped when converting to an
+// opaque format, and set to opaque the other way around.
+
+fn luma(r: u8, g: u8, b: u8) -> u8 {
+    return (0.299f32 * r as f32 + 0.587f32 * g as f32 + 0.114f32 * b as f32).round() as u8;
+}
+
+fn decode_channel(c: u8) -> f32 {
+    return tonemap::srgb_decode(c as f32 / 255f32);
+}
+
+fn encode_channel(c: f32) -> u8 {
+    return (tonemap::srgb_encode(c) * 255f32).round() as u8;
+}
+
+impl From<Grayscale> for Rgb {
+    fn from(pixel: Grayscale) -> Self {
+        Rgb {
+            b: pixel.i,
+            g: pixel.i,
+            r: pixel.i,
+        }
+    }
+}
+
+impl From<Grayscale> for Rgba {
+    fn from(pixel: Grayscale) -> Self {
+        Rgba {
+            b: pixel.i,
+            g: pixel.i,
+            r: pixel.i,
+            a: 255,
+        }
+    }
+}
+
+impl From<Grayscale> for RgbF32 {
+    fn from(pixel: Grayscale) -> Self {
+        let i: f32 = decode_channel(pixel.i);
+        RgbF32 { r: i, g: i, b: i }
+    }
+}
+
+impl From<Rgb> for Grayscale {
+    fn from(pixel: Rgb) -> Self {
+        Grayscale {
+            i: luma(pixel.r, pixel.g, pixel.b),
+        }
+    }
+}
+
+impl From<Rgb> for Rgba {
+    fn from(pixel: Rgb) -> Self {
+        Rgba {
+            b: pixel.b,
+            g: pixel.g,
+            r: pixel.r,
+            a: 255,
+        }
+    }
+}
+
+impl From<Rgb> for RgbF32 {
+    fn from(pixel: Rgb) -> Self {
+        RgbF32 {
+            r: decode_channel(pixel.r),
+            g: decode_channel(pixel.g),
+            b: decode_channel(pixel.b),
+        }
+    }
+}
+
+impl From<Rgba> for Grayscale {
+    fn from(pixel: Rgba) -> Self {
+        Grayscale {
+            i: luma(pixel.r, pixel.g, pixel.b),
+        }
+    }
 }
 
+impl From<Rgba> for Rgb {
+    fn from(pixel: Rgba) -> Self {
+        Rgb {
+            b: pixel.b,
+            g: pixel.g,
+            r: pixel.r,
+        }
+    }
+}
+
+impl From<Rgba> for RgbF32 {
+    fn from(pixel: Rgba) -> Self {
+        RgbF32::from(Rgb::from(pixel))
+    }
+}
+
+impl From<RgbF32> for Grayscale {
+    fn from(pixel: RgbF32) -> Self {
+        Grayscale::from(Rgb::from(pixel))
+    }
+}
+
+impl From<RgbF32> for Rgb {
+    fn from(pixel: RgbF32) -> Self {
+        Rgb {
+            b: encode_channel(pixel.b),
+            g: encode_channel(pixel.g),
+            r: encode_channel(pixel.r),
+        }
+    }
+}
+
+impl From<RgbF32> for Rgba {
+    fn from(pixel: RgbF32) -> Self {
+        Rgba::from(Rgb::from(pixel))
+    }
+}
+
+const MAX_TGA_DIMENSION: u32 = u16::MAX as u32;
+const MAX_RLE_PACKET: usize = 128;
 const DEVELOPER_AREA_REF: [u8; 4] = [0, 0, 0, 0];
 const EXTENSION_AREA_REF: [u8; 4] = [0, 0, 0, 0];
 const FOOTER: &[u8; 18] = b"TRUEVISION-XFILE.\0";
 
+// Run-length packets of repeated pixels, and raw packets for the pixels between runs, each of at
+// most 128 pixels.
+fn write_rle_row<W: io::Write>(
+    writer: &mut io::BufWriter<W>,
+    row: &[u8],
+    bytes_per_pixel: usize,
+) -> io::Result<()> {
+    let pixels: Vec<&[u8]> = row.chunks_exact(bytes_per_pixel).collect();
+    let mut start: usize = 0;
+    while start < pixels.len() {
+        let run: usize = pixels[start..]
+            .iter()
+            .take(MAX_RLE_PACKET)
+            .take_while(|&&pixel| pixel == pixels[start])
+            .count();
+        if run > 1 {
+            writer.write_all(&[0x80 | (run - 1) as u8])?;
+            writer.write_all(pixels[start])?;
+            start += run;
+            continue;
+        }
+        // Raw pixels up to the next pair of equal ones.
+        let mut end: usize = start + 1;
+        while end < pixels.len()
+            && end - start < MAX_RLE_PACKET
+            && pixels.get(end + 1) != Some(&pixels[end])
+        {
+            end += 1;
+        }
+        writer.write_all(&[(end - start - 1) as u8])?;
+        for pixel in &pixels[start..end] {
+            writer.write_all(pixel)?;
+        }
+        start = end;
+    }
+    Ok(())
+}
+
 #[derive(Default)]
 #[repr(packed)]
-#[allow(dead_code)]
+#[allow(dead_code, clippy::repr_packed_without_abi)]
 struct Header {
     idlength: u8,
     colormaptype: u8,
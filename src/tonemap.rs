@@ -0,0 +1,81 @@
+use crate::tga;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ToneMapOperator {
+    // Plain clamp to [0, 1].
+    Clamp,
+    Reinhard,
+    // Narkowicz's fit of the ACES filmic reference curve.
+    AcesFilmic,
+    // John Hable's filmic curve from Uncharted 2.
+    Uncharted2,
+}
+
+// Converts an HDR render to displayable 8 bit sRGB. `exposure` is in stops, each one doubling
+// the scene radiance before the tone curve is applied.
+pub fn tone_map(
+    img: &tga::Image<tga::RgbF32>,
+    exposure: f32,
+    operator: ToneMapOperator,
+) -> tga::Image<tga::Rgb> {
+    let scale: f32 = exposure.exp2();
+    let mut ldr_img: tga::Image<tga::Rgb> = tga::Image::new(img.width, img.height);
+    for (ldr, hdr) in ldr_img.data.iter_mut().zip(&img.data) {
+        let channel = |c: f32| -> u8 {
+            let display: f32 = srgb_encode(tone_curve(c * scale, operator));
+            (display * 255f32).round().clamp(0f32, 255f32) as u8
+        };
+        *ldr = tga::Rgb {
+            b: channel(hdr.b),
+            g: channel(hdr.g),
+            r: channel(hdr.r),
+        };
+    }
+    return ldr_img;
+}
+
+pub fn tone_curve(x: f32, operator: ToneMapOperator) -> f32 {
+    let x: f32 = x.max(0f32);
+    return match operator {
+        ToneMapOperator::Clamp => x.min(1f32),
+        ToneMapOperator::Reinhard => x / (1f32 + x),
+        ToneMapOperator::AcesFilmic => ((x * (2.51f32 * x + 0.03f32))
+            / (x * (2.43f32 * x + 0.59f32) + 0.14f32))
+            .clamp(0f32, 1f32),
+        ToneMapOperator::Uncharted2 => {
+            const EXPOSURE_BIAS: f32 = 2f32;
+            const WHITE_POINT: f32 = 11.2f32;
+            uncharted2_partial(x * EXPOSURE_BIAS) / uncharted2_partial(WHITE_POINT)
+        }
+    };
+}
+
+// sRGB transfer function, from linear light to encoded values, both in [0, 1].
+pub fn srgb_encode(linear: f32) -> f32 {
+    let linear: f32 = linear.clamp(0f32, 1f32);
+    return if linear <= 0.0031308f32 {
+        12.92f32 * linear
+    } else {
+        1.055f32 * linear.powf(1f32 / 2.4f32) - 0.055f32
+    };
+}
+
+// Inverse of `srgb_encode`.
+pub fn srgb_decode(encoded: f32) -> f32 {
+    let encoded: f32 = encoded.clamp(0f32, 1f32);
+    return if encoded <= 0.04045f32 {
+        encoded / 12.92f32
+    } else {
+        ((encoded + 0.055f32) / 1.055f32).powf(2.4f32)
+    };
+}
+
+fn uncharted2_partial(x: f32) -> f32 {
+    const A: f32 = 0.15f32;
+    const B: f32 = 0.50f32;
+    const C: f32 = 0.10f32;
+    const D: f32 = 0.20f32;
+    const E: f32 = 0.02f32;
+    const F: f32 = 0.30f32;
+    return ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F;
+}
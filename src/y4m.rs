@@ -0,0 +1,161 @@
+use crate::tga;
+use std::io;
+
+// YUV4MPEG2 (.y4m) streaming, e.g. `rust-renderer | ffmpeg -i - out.mp4`. Frames are converted to
+// limited range Y'CbCr. With `vflip`, the first image row is the bottom of the picture, as in the
+// drawers output.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Chroma {
+    // Chroma averaged over 2x2 pixel blocks, centred siting.
+    C420,
+    C444,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorMatrix {
+    Bt601,
+    Bt709,
+}
+
+impl ColorMatrix {
+    // Red and blue luma weights.
+    fn coefficients(&self) -> (f32, f32) {
+        return match self {
+            ColorMatrix::Bt601 => (0.299, 0.114),
+            ColorMatrix::Bt709 => (0.2126, 0.0722),
+        };
+    }
+}
+
+pub struct Y4mWriter<W: io::Write> {
+    writer: W,
+    width: u32,
+    height: u32,
+    chroma: Chroma,
+    matrix: ColorMatrix,
+}
+
+impl<W: io::Write> Y4mWriter<W> {
+    // Writes the stream header. The frame rate is the fraction `numerator / denominator`.
+    pub fn new(
+        mut writer: W,
+        width: u32,
+        height: u32,
+        frame_rate: (u32, u32),
+        chroma: Chroma,
+        matrix: ColorMatrix,
+    ) -> io::Result<Self> {
+        if width == 0 || height == 0 || frame_rate.0 == 0 || frame_rate.1 == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                tga::Error::InvalidDimensions.to_string(),
+            ));
+        }
+        let colorspace: &str = match chroma {
+            Chroma::C420 => "C420jpeg XYSCSS=420JPEG",
+            Chroma::C444 => "C444 XYSCSS=444",
+        };
+        writeln!(
+            writer,
+            "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 {} XCOLORRANGE=LIMITED",
+            width, height, frame_rate.0, frame_rate.1, colorspace
+        )?;
+        return Ok(Y4mWriter {
+            writer,
+            width,
+            height,
+            chroma,
+            matrix,
+        });
+    }
+
+    pub fn write_frame(&mut self, img: &tga::Image<tga::Rgb>, vflip: bool) -> io::Result<()> {
+        if img.width != self.width || img.height != self.height {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                tga::Error::InvalidDimensions.to_string(),
+            ));
+        }
+        let width: usize = self.width as usize;
+        let height: usize = self.height as usize;
+        let mut luma: Vec<u8> = Vec::with_capacity(width * height);
+        let mut cb: Vec<f32> = Vec::with_capacity(width * height);
+        let mut cr: Vec<f32> = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let row: usize = if vflip { height - 1 - y } else { y };
+            for pixel in &img.data[row * width..(row + 1) * width] {
+                let (y_value, cb_value, cr_value) = rgb_to_ycbcr(*pixel, self.matrix);
+                luma.push(quantise(16f32 + 219f32 * y_value));
+                cb.push(cb_value);
+                cr.push(cr_value);
+            }
+        }
+
+        self.writer.write_all(b"FRAME\n")?;
+        self.writer.write_all(&luma)?;
+        match self.chroma {
+            Chroma::C444 => {
+                for plane in [&cb, &cr] {
+                    let bytes: Vec<u8> = plane.iter().map(|c| quantise_chroma(*c)).collect();
+                    self.writer.write_all(&bytes)?;
+                }
+            }
+            Chroma::C420 => {
+                for plane in [&cb, &cr] {
+                    self.writer.write_all(&subsample(plane, width, height))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        return self.writer.flush();
+    }
+
+    pub fn into_inner(self) -> W {
+        return self.writer;
+    }
+}
+
+// Luma in [0, 1] and chroma differences in [-0.5, 0.5].
+fn rgb_to_ycbcr(pixel: tga::Rgb, matrix: ColorMatrix) -> (f32, f32, f32) {
+    let (kr, kb) = matrix.coefficients();
+    let r: f32 = pixel.r as f32 / 255f32;
+    let g: f32 = pixel.g as f32 / 255f32;
+    let b: f32 = pixel.b as f32 / 255f32;
+    let y: f32 = kr * r + (1f32 - kr - kb) * g + kb * b;
+    let cb: f32 = (b - y) / (2f32 * (1f32 - kb));
+    let cr: f32 = (r - y) / (2f32 * (1f32 - kr));
+    return (y, cb, cr);
+}
+
+// Averages 2x2 blocks, odd trailing rows and columns averaging what is left.
+fn subsample(plane: &[f32], width: usize, height: usize) -> Vec<u8> {
+    let chroma_width: usize = width.div_ceil(2);
+    let chroma_height: usize = height.div_ceil(2);
+    let mut subsampled: Vec<u8> = Vec::with_capacity(chroma_width * chroma_height);
+    for cy in 0..chroma_height {
+        for cx in 0..chroma_width {
+            let mut sum: f32 = 0f32;
+            let mut count: f32 = 0f32;
+            for y in 2 * cy..(2 * cy + 2).min(height) {
+                for x in 2 * cx..(2 * cx + 2).min(width) {
+                    sum += plane[y * width + x];
+                    count += 1f32;
+                }
+            }
+            subsampled.push(quantise_chroma(sum / count));
+        }
+    }
+    return subsampled;
+}
+
+fn quantise_chroma(c: f32) -> u8 {
+    return quantise(128f32 + 224f32 * c);
+}
+
+fn quantise(value: f32) -> u8 {
+    return value.round().clamp(0f32, 255f32) as u8;
+}
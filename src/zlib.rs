@@ -0,0 +1,633 @@
+// zlib (RFC 1950) container around deflate (RFC 1951). The compressor uses LZ77 matching and the
+// fixed Huffman codes, falling back to stored blocks where compression does not pay off. The
+// decompressor handles all three block types.
+
+use std::io;
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+const MAX_BLOCK_SIZE: usize = 65535;
+const NO_POSITION: usize = usize::MAX;
+
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+#[derive(Copy, Clone, Debug)]
+enum Token {
+    Literal(u8),
+    Match { length: u16, distance: u16 },
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    bit_buffer: u32,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        BitWriter {
+            bytes: Vec::new(),
+            bit_buffer: 0,
+            bit_count: 0,
+        }
+    }
+
+    // Writes the `count` low bits of `bits`, least significant first.
+    fn write_bits(&mut self, bits: u32, count: u32) {
+        self.bit_buffer |= bits << self.bit_count;
+        self.bit_count += count;
+        while self.bit_count >= 8 {
+            self.bytes.push(self.bit_buffer as u8);
+            self.bit_buffer >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    // Huffman codes are packed starting from their most significant bit.
+    fn write_code(&mut self, code: u32, length: u32) {
+        self.write_bits(reverse_bits(code, length), length);
+    }
+
+    fn align_to_byte(&mut self) {
+        if self.bit_count > 0 {
+            self.write_bits(0, 8 - self.bit_count);
+        }
+    }
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    const MODULO: u32 = 65521;
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+    // 5552 is the largest run that cannot overflow the sums before the modulo.
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MODULO;
+        b %= MODULO;
+    }
+    return (b << 16) | a;
+}
+
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut writer: BitWriter = BitWriter::new();
+    // Deflate with a 32K window, default compression level.
+    writer.bytes.extend_from_slice(&[0x78, 0x9c]);
+
+    let mut matcher: Matcher = Matcher::new(data);
+    let mut block_start: usize = 0;
+    loop {
+        let block_end: usize = (block_start + MAX_BLOCK_SIZE).min(data.len());
+        let is_final: bool = block_end == data.len();
+        let tokens: Vec<Token> = matcher.tokenize(block_start, block_end);
+
+        let stored_bits: usize = 32 + 8 * (block_end - block_start) + 8;
+        if fixed_huffman_bits(&tokens) < stored_bits {
+            write_fixed_block(&mut writer, &tokens, is_final);
+        } else {
+            write_stored_block(&mut writer, &data[block_start..block_end], is_final);
+        }
+
+        block_start = block_end;
+        if is_final {
+            break;
+        }
+    }
+
+    writer.align_to_byte();
+    writer.bytes.extend_from_slice(&adler32(data).to_be_bytes());
+    return writer.bytes;
+}
+
+// Greedy LZ77 match finder over hash chains of 3 byte prefixes.
+struct Matcher<'a> {
+    data: &'a [u8],
+    head: Vec<usize>,
+    previous: Vec<usize>,
+    next_to_insert: usize,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Matcher {
+            data,
+            head: vec![NO_POSITION; 1 << HASH_BITS],
+            previous: vec![NO_POSITION; WINDOW_SIZE],
+            next_to_insert: 0,
+        }
+    }
+
+    fn tokenize(&mut self, start: usize, end: usize) -> Vec<Token> {
+        let mut tokens: Vec<Token> = Vec::new();
+        let mut position: usize = start;
+        while position < end {
+            self.insert_up_to(position);
+            let (length, distance) = self.longest_match(position, end);
+            if length >= MIN_MATCH {
+                tokens.push(Token::Match {
+                    length: length as u16,
+                    distance: distance as u16,
+                });
+                position += length;
+            } else {
+                tokens.push(Token::Literal(self.data[position]));
+                position += 1;
+            }
+        }
+        self.insert_up_to(end);
+        return tokens;
+    }
+
+    fn insert_up_to(&mut self, position: usize) {
+        while self.next_to_insert < position {
+            let i: usize = self.next_to_insert;
+            if i + MIN_MATCH <= self.data.len() {
+                let hash: usize = self.hash(i);
+                self.previous[i % WINDOW_SIZE] = self.head[hash];
+                self.head[hash] = i;
+            }
+            self.next_to_insert += 1;
+        }
+    }
+
+    fn longest_match(&self, position: usize, end: usize) -> (usize, usize) {
+        if position + MIN_MATCH > end {
+            return (0, 0);
+        }
+        let max_length: usize = MAX_MATCH.min(end - position);
+        let mut best: (usize, usize) = (0, 0);
+        let mut candidate: usize = self.head[self.hash(position)];
+        let mut chain: usize = 0;
+        while candidate != NO_POSITION && chain < MAX_CHAIN {
+            let distance: usize = position - candidate;
+            if distance > WINDOW_SIZE - 1 {
+                break;
+            }
+            let length: usize = self.data[candidate..]
+                .iter()
+                .zip(&self.data[position..position + max_length])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if length > best.0 {
+                best = (length, distance);
+                if length == max_length {
+                    break;
+                }
+            }
+            let next: usize = self.previous[candidate % WINDOW_SIZE];
+            // Slots of the ring buffer get reused, chains must keep going back in time.
+            if next == NO_POSITION || next >= candidate {
+                break;
+            }
+            candidate = next;
+            chain += 1;
+        }
+        return best;
+    }
+
+    fn hash(&self, position: usize) -> usize {
+        let bytes: u32 = (self.data[position] as u32) << 16
+            | (self.data[position + 1] as u32) << 8
+            | self.data[position + 2] as u32;
+        return (bytes.wrapping_mul(0x9e3779b1) >> (32 - HASH_BITS)) as usize;
+    }
+}
+
+fn write_stored_block(writer: &mut BitWriter, data: &[u8], is_final: bool) {
+    writer.write_bits(is_final as u32, 1);
+    writer.write_bits(0b00, 2);
+    writer.align_to_byte();
+    let length: u16 = data.len() as u16;
+    writer.bytes.extend_from_slice(&length.to_le_bytes());
+    writer.bytes.extend_from_slice(&(!length).to_le_bytes());
+    writer.bytes.extend_from_slice(data);
+}
+
+fn write_fixed_block(writer: &mut BitWriter, tokens: &[Token], is_final: bool) {
+    writer.write_bits(is_final as u32, 1);
+    writer.write_bits(0b01, 2);
+    for token in tokens {
+        match *token {
+            Token::Literal(byte) => write_fixed_literal_length(writer, byte as u16),
+            Token::Match { length, distance } => {
+                let length_index: usize = bucket_index(&LENGTH_BASES, length);
+                write_fixed_literal_length(writer, 257 + length_index as u16);
+                writer.write_bits(
+                    (length - LENGTH_BASES[length_index]) as u32,
+                    LENGTH_EXTRA_BITS[length_index] as u32,
+                );
+                let distance_index: usize = bucket_index(&DISTANCE_BASES, distance);
+                writer.write_code(distance_index as u32, 5);
+                writer.write_bits(
+                    (distance - DISTANCE_BASES[distance_index]) as u32,
+                    DISTANCE_EXTRA_BITS[distance_index] as u32,
+                );
+            }
+        }
+    }
+    write_fixed_literal_length(writer, 256);
+}
+
+fn write_fixed_literal_length(writer: &mut BitWriter, symbol: u16) {
+    let (code, length) = fixed_literal_length_code(symbol);
+    writer.write_code(code, length);
+}
+
+fn fixed_literal_length_code(symbol: u16) -> (u32, u32) {
+    let symbol: u32 = symbol as u32;
+    return match symbol {
+        0..=143 => (0x30 + symbol, 8),
+        144..=255 => (0x190 + symbol - 144, 9),
+        256..=279 => (symbol - 256, 7),
+        _ => (0xc0 + symbol - 280, 8),
+    };
+}
+
+fn fixed_huffman_bits(tokens: &[Token]) -> usize {
+    let mut bits: usize = 3 + 7;
+    for token in tokens {
+        bits += match *token {
+            Token::Literal(byte) => fixed_literal_length_code(byte as u16).1 as usize,
+            Token::Match { length, distance } => {
+                let length_index: usize = bucket_index(&LENGTH_BASES, length);
+                let distance_index: usize = bucket_index(&DISTANCE_BASES, distance);
+                fixed_literal_length_code(257 + length_index as u16).1 as usize
+                    + LENGTH_EXTRA_BITS[length_index] as usize
+                    + 5
+                    + DISTANCE_EXTRA_BITS[distance_index] as usize
+            }
+        };
+    }
+    return bits;
+}
+
+// Index of the last base not greater than `value`.
+fn bucket_index(bases: &[u16], value: u16) -> usize {
+    return bases.partition_point(|&base| base <= value) - 1;
+}
+
+fn reverse_bits(code: u32, length: u32) -> u32 {
+    return code.reverse_bits() >> (32 - length);
+}
+
+// Canonical Huffman decoding table: number of codes of each length, and symbols ordered by code.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Self> {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for length in 1..16usize {
+            offsets[length] = offsets[length - 1] + counts[length - 1];
+        }
+        let mut symbols: Vec<u16> = vec![0u16; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        return Ok(Huffman { counts, symbols });
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> io::Result<u16> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for length in 1..16usize {
+            code |= reader.read_bits(1)? as i32;
+            let count: i32 = self.counts[length] as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + (code - first)) as usize]);
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+        return Err(invalid_data("invalid Huffman code"));
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    bit_buffer: u32,
+    bit_count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        BitReader {
+            bytes,
+            position: 0,
+            bit_buffer: 0,
+            bit_count: 0,
+        }
+    }
+
+    fn read_bits(&mut self, count: u32) -> io::Result<u32> {
+        while self.bit_count < count {
+            let byte: u8 = *self
+                .bytes
+                .get(self.position)
+                .ok_or_else(|| invalid_data("truncated deflate stream"))?;
+            self.position += 1;
+            self.bit_buffer |= (byte as u32) << self.bit_count;
+            self.bit_count += 8;
+        }
+        let bits: u32 = self.bit_buffer & ((1u64 << count) - 1) as u32;
+        self.bit_buffer >>= count;
+        self.bit_count -= count;
+        return Ok(bits);
+    }
+
+    fn align_to_byte(&mut self) {
+        self.bit_buffer = 0;
+        self.bit_count = 0;
+    }
+}
+
+// Fails once the output would grow past `max_output` bytes, so a small stream cannot expand
+// into an arbitrarily large allocation.
+pub fn decompress(data: &[u8], max_output: usize) -> io::Result<Vec<u8>> {
+    if data.len() < 6
+        || data[0] & 0x0f != 8
+        || !(data[0] as u16 * 256 + data[1] as u16).is_multiple_of(31)
+    {
+        return Err(invalid_data("invalid zlib header"));
+    }
+    if data[1] & 0x20 != 0 {
+        return Err(invalid_data("preset dictionaries are not supported"));
+    }
+
+    let mut reader: BitReader = BitReader::new(&data[2..]);
+    let mut output: Vec<u8> = Vec::new();
+    loop {
+        let is_final: bool = reader.read_bits(1)? == 1;
+        match reader.read_bits(2)? {
+            0b00 => inflate_stored_block(&mut reader, &mut output, max_output)?,
+            0b01 => {
+                let (literal_lengths, distances) = fixed_huffman_tables()?;
+                inflate_huffman_block(
+                    &mut reader,
+                    &mut output,
+                    &literal_lengths,
+                    &distances,
+                    max_output,
+                )?
+            }
+            0b10 => {
+                let (literal_lengths, distances) = read_dynamic_huffman_tables(&mut reader)?;
+                inflate_huffman_block(
+                    &mut reader,
+                    &mut output,
+                    &literal_lengths,
+                    &distances,
+                    max_output,
+                )?
+            }
+            _ => return Err(invalid_data("invalid deflate block type")),
+        }
+        if is_final {
+            break;
+        }
+    }
+
+    let checksum_start: usize = 2 + reader.position;
+    let checksum: Option<&[u8]> = data.get(checksum_start..checksum_start + 4);
+    match checksum {
+        Some(checksum) if u32::from_be_bytes(checksum.try_into().unwrap()) == adler32(&output) => {
+            Ok(output)
+        }
+        _ => Err(invalid_data("zlib checksum mismatch")),
+    }
+}
+
+fn inflate_stored_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    max_output: usize,
+) -> io::Result<()> {
+    reader.align_to_byte();
+    let header: &[u8] = reader
+        .bytes
+        .get(reader.position..reader.position + 4)
+        .ok_or_else(|| invalid_data("truncated stored block"))?;
+    let length: u16 = u16::from_le_bytes([header[0], header[1]]);
+    if length != !u16::from_le_bytes([header[2], header[3]]) {
+        return Err(invalid_data("corrupt stored block length"));
+    }
+    let start: usize = reader.position + 4;
+    let block: &[u8] = reader
+        .bytes
+        .get(start..start + length as usize)
+        .ok_or_else(|| invalid_data("truncated stored block"))?;
+    if output.len() + block.len() > max_output {
+        return Err(output_too_large());
+    }
+    output.extend_from_slice(block);
+    reader.position = start + length as usize;
+    Ok(())
+}
+
+fn fixed_huffman_tables() -> io::Result<(Huffman, Huffman)> {
+    let mut lengths = [0u8; 288];
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        *length = fixed_literal_length_code(symbol as u16).1 as u8;
+    }
+    return Ok((Huffman::new(&lengths)?, Huffman::new(&[5u8; 30])?));
+}
+
+fn read_dynamic_huffman_tables(reader: &mut BitReader) -> io::Result<(Huffman, Huffman)> {
+    const CODE_LENGTH_ORDER: [usize; 19] = [
+        16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+    ];
+    let literal_length_count: usize = reader.read_bits(5)? as usize + 257;
+    let distance_count: usize = reader.read_bits(5)? as usize + 1;
+    let code_length_count: usize = reader.read_bits(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for &symbol in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_lengths[symbol] = reader.read_bits(3)? as u8;
+    }
+    let code_length_huffman: Huffman = Huffman::new(&code_lengths)?;
+
+    let mut lengths: Vec<u8> = Vec::with_capacity(literal_length_count + distance_count);
+    while lengths.len() < literal_length_count + distance_count {
+        let symbol: u16 = code_length_huffman.decode(reader)?;
+        let (value, repeat): (u8, u32) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous: u8 = *lengths
+                    .last()
+                    .ok_or_else(|| invalid_data("repeat without a previous length"))?;
+                (previous, 3 + reader.read_bits(2)?)
+            }
+            17 => (0, 3 + reader.read_bits(3)?),
+            _ => (0, 11 + reader.read_bits(7)?),
+        };
+        for _ in 0..repeat {
+            lengths.push(value);
+        }
+    }
+    if lengths.len() != literal_length_count + distance_count {
+        return Err(invalid_data("code lengths overflow"));
+    }
+    return Ok((
+        Huffman::new(&lengths[..literal_length_count])?,
+        Huffman::new(&lengths[literal_length_count..])?,
+    ));
+}
+
+fn inflate_huffman_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    literal_lengths: &Huffman,
+    distances: &Huffman,
+    max_output: usize,
+) -> io::Result<()> {
+    loop {
+        let symbol: u16 = literal_lengths.decode(reader)?;
+        match symbol {
+            0..=255 => {
+                if output.len() == max_output {
+                    return Err(output_too_large());
+                }
+                output.push(symbol as u8)
+            }
+            256 => return Ok(()),
+            257..=285 => {
+                let length_index: usize = (symbol - 257) as usize;
+                let length: usize = LENGTH_BASES[length_index] as usize
+                    + reader.read_bits(LENGTH_EXTRA_BITS[length_index] as u32)? as usize;
+                let distance_index: usize = distances.decode(reader)? as usize;
+                if distance_index >= DISTANCE_BASES.len() {
+                    return Err(invalid_data("invalid distance code"));
+                }
+                let distance: usize = DISTANCE_BASES[distance_index] as usize
+                    + reader.read_bits(DISTANCE_EXTRA_BITS[distance_index] as u32)? as usize;
+                if distance > output.len() {
+                    return Err(invalid_data("distance past the start of the output"));
+                }
+                if output.len() + length > max_output {
+                    return Err(output_too_large());
+                }
+                let start: usize = output.len() - distance;
+                for i in 0..length {
+                    output.push(output[start + i]);
+                }
+            }
+            _ => return Err(invalid_data("invalid literal/length code")),
+        }
+    }
+}
+
+fn output_too_large() -> io::Error {
+    return invalid_data("decompressed data larger than expected");
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn random_bytes(count: usize) -> Vec<u8> {
+        let mut rng: StdRng = StdRng::seed_from_u64(3);
+        return (0..count).map(|_| rng.gen()).collect();
+    }
+
+    fn block_type(stream: &[u8]) -> u8 {
+        return (stream[2] >> 1) & 0b11;
+    }
+
+    #[test]
+    fn adler32_matches_known_values() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+        // Long enough for the sums to wrap around the modulus.
+        assert_eq!(adler32(&[0xff; 6000]), 0xa49759ea);
+    }
+
+    #[test]
+    fn round_trips_stored_and_fixed_blocks() {
+        // Incompressible data is stored, split over several blocks.
+        let noise: Vec<u8> = random_bytes(70000);
+        let stream: Vec<u8> = compress(&noise);
+        assert_eq!(block_type(&stream), 0b00);
+        assert_eq!(decompress(&stream, noise.len()).unwrap(), noise);
+
+        // Matches overlapping their own output, and distances up to the window size.
+        let far: Vec<u8> = random_bytes(1000);
+        let mut repeated: Vec<u8> = far.clone();
+        repeated.extend(b"abcabcabcabcd".repeat(2400));
+        repeated.extend(&far);
+        let stream: Vec<u8> = compress(&repeated);
+        assert_eq!(block_type(&stream), 0b01);
+        assert_eq!(decompress(&stream, repeated.len()).unwrap(), repeated);
+
+        assert_eq!(decompress(&compress(&[]), 0).unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn output_is_bounded() {
+        // A few kilobytes expanding to a megabyte.
+        let zeros: Vec<u8> = vec![0u8; 1 << 20];
+        let stream: Vec<u8> = compress(&zeros);
+        assert!(stream.len() < 1 << 13);
+        assert!(decompress(&stream, zeros.len()).is_ok());
+        let error: io::Error = decompress(&stream, zeros.len() - 1).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let noise: Vec<u8> = random_bytes(1000);
+        let stream: Vec<u8> = compress(&noise);
+        assert!(decompress(&stream, noise.len() - 1).is_err());
+    }
+
+    #[test]
+    fn corrupt_streams_are_rejected() {
+        let data: Vec<u8> = b"corrupt streams are rejected".repeat(4);
+        let mut stream: Vec<u8> = compress(&data);
+        let last: usize = stream.len() - 1;
+        stream[last] ^= 1;
+        assert!(decompress(&stream, data.len()).is_err());
+        stream[last] ^= 1;
+        stream[0] = 0x79;
+        assert!(decompress(&stream, data.len()).is_err());
+        // A fixed block starting with a match of distance 1.
+        let error: io::Error =
+            decompress(&[0x78, 0x01, 0x03, 0x02, 0x00, 0, 0, 0, 0], 16).unwrap_err();
+        assert_eq!(error.to_string(), "distance past the start of the output");
+    }
+}
@@ -0,0 +1,227 @@
+#![allow(clippy::needless_return)]
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust_renderer::bounding_box::BoundingBox3D;
+use rust_renderer::bvh::{Bvh, NearestPoint, RayHit};
+use rust_renderer::linear_algebra;
+use rust_renderer::mesh::{Mesh, Point3D, Triangle};
+use rust_renderer::primitives;
+
+const QUERY_COUNT: usize = 2000;
+
+fn random_point(rng: &mut StdRng, extent: f32) -> Point3D<f32> {
+    return Point3D {
+        x: rng.gen_range(-extent..extent),
+        y: rng.gen_range(-extent..extent),
+        z: rng.gen_range(-extent..extent),
+    };
+}
+
+fn corners(mesh: &Mesh, t: usize) -> [Point3D<f32>; 3] {
+    return mesh.triangles[t]
+        .vertices
+        .map(|v| mesh.v_positions.get_at(v));
+}
+
+// Möller-Trumbore on every triangle, both sides hit.
+fn brute_force_hit(
+    mesh: &Mesh,
+    origin: Point3D<f32>,
+    direction: Point3D<f32>,
+    t_max: f32,
+) -> Option<f32> {
+    let mut closest: Option<f32> = None;
+    for t in 0..mesh.triangles.len() {
+        let [a, b, c] = corners(mesh, t);
+        let ab: Point3D<f32> = linear_algebra::subtract(b, a);
+        let ac: Point3D<f32> = linear_algebra::subtract(c, a);
+        let p: Point3D<f32> = linear_algebra::cross_product(direction, ac);
+        let determinant: f32 = linear_algebra::dot_product(ab, p);
+        if determinant == 0f32 {
+            continue;
+        }
+        let s: Point3D<f32> = linear_algebra::subtract(origin, a);
+        let u: f32 = linear_algebra::dot_product(s, p) / determinant;
+        let q: Point3D<f32> = linear_algebra::cross_product(s, ab);
+        let v: f32 = linear_algebra::dot_product(direction, q) / determinant;
+        let distance: f32 = linear_algebra::dot_product(ac, q) / determinant;
+        if u >= 0f32
+            && v >= 0f32
+            && u + v <= 1f32
+            && (0f32..=t_max).contains(&distance)
+            && closest.is_none_or(|closest| distance < closest)
+        {
+            closest = Some(distance);
+        }
+    }
+    return closest;
+}
+
+fn distance_to_segment(p: Point3D<f32>, a: Point3D<f32>, b: Point3D<f32>) -> f32 {
+    let ab: Point3D<f32> = linear_algebra::subtract(b, a);
+    let ap: Point3D<f32> = linear_algebra::subtract(p, a);
+    let t: f32 = (linear_algebra::dot_product(ap, ab) / linear_algebra::dot_product(ab, ab))
+        .clamp(0f32, 1f32);
+    let closest: Point3D<f32> = Point3D {
+        x: a.x + t * ab.x,
+        y: a.y + t * ab.y,
+        z: a.z + t * ab.z,
+    };
+    return linear_algebra::norm(linear_algebra::subtract(p, closest));
+}
+
+// Distance to the plane of the triangle when `p` projects inside it, to its nearest edge
+// otherwise.
+fn brute_force_distance(mesh: &Mesh, p: Point3D<f32>) -> f32 {
+    let mut best: f32 = f32::INFINITY;
+    for t in 0..mesh.triangles.len() {
+        let [a, b, c] = corners(mesh, t);
+        let normal: Point3D<f32> = linear_algebra::normalized(linear_algebra::cross_product(
+            linear_algebra::subtract(b, a),
+            linear_algebra::subtract(c, a),
+        ));
+        let inside: bool = [(a, b), (b, c), (c, a)].iter().all(|&(from, to)| {
+            let edge_normal: Point3D<f32> = linear_algebra::cross_product(
+                linear_algebra::subtract(to, from),
+                linear_algebra::subtract(p, from),
+            );
+            return linear_algebra::dot_product(edge_normal, normal) >= 0f32;
+        });
+        let distance: f32 = if inside {
+            linear_algebra::dot_product(linear_algebra::subtract(p, a), normal).abs()
+        } else {
+            distance_to_segment(p, a, b)
+                .min(distance_to_segment(p, b, c))
+                .min(distance_to_segment(p, c, a))
+        };
+        best = best.min(distance);
+    }
+    return best;
+}
+
+fn check_queries(mesh: &Mesh, bvh: &Bvh, rng: &mut StdRng, query_count: usize) {
+    for _ in 0..query_count {
+        let origin: Point3D<f32> = random_point(rng, 2f32);
+        let direction: Point3D<f32> = linear_algebra::normalized(random_point(rng, 1f32));
+        let t_max: f32 = rng.gen_range(0.5f32..4f32);
+        let hit: Option<RayHit> = bvh.closest_hit(mesh, origin, direction, 0f32, t_max);
+        let expected: Option<f32> = brute_force_hit(mesh, origin, direction, t_max);
+        match (hit, expected) {
+            (Some(hit), Some(expected)) => {
+                assert!((hit.t - expected).abs() < 1e-4f32, "{} {expected}", hit.t);
+                let [a, b, c] = corners(mesh, hit.triangle);
+                let weights: Point3D<f32> = hit.barycentric;
+                let point: Point3D<f32> = Point3D {
+                    x: weights.x * a.x + weights.y * b.x + weights.z * c.x,
+                    y: weights.x * a.y + weights.y * b.y + weights.z * c.y,
+                    z: weights.x * a.z + weights.y * b.z + weights.z * c.z,
+                };
+                let along: Point3D<f32> = Point3D {
+                    x: origin.x + hit.t * direction.x,
+                    y: origin.y + hit.t * direction.y,
+                    z: origin.z + hit.t * direction.z,
+                };
+                assert!(linear_algebra::norm(linear_algebra::subtract(point, along)) < 1e-4f32);
+            }
+            (None, None) => {}
+            (hit, expected) => panic!("{hit:?} {expected:?}"),
+        }
+        assert_eq!(
+            bvh.any_hit(mesh, origin, direction, 0f32, t_max),
+            hit.is_some()
+        );
+
+        let p: Point3D<f32> = random_point(rng, 2f32);
+        let nearest: NearestPoint = bvh.nearest_point(mesh, p).unwrap();
+        let expected: f32 = brute_force_distance(mesh, p);
+        assert!(
+            (nearest.distance - expected).abs() < 1e-5f32,
+            "{} {expected}",
+            nearest.distance
+        );
+        let [a, b, c] = corners(mesh, nearest.triangle);
+        assert!(
+            (linear_algebra::norm(linear_algebra::subtract(nearest.point, p)) - nearest.distance)
+                .abs()
+                < 1e-5f32
+        );
+        let normal: Point3D<f32> = linear_algebra::normalized(linear_algebra::cross_product(
+            linear_algebra::subtract(b, a),
+            linear_algebra::subtract(c, a),
+        ));
+        let offset: f32 =
+            linear_algebra::dot_product(linear_algebra::subtract(nearest.point, a), normal);
+        assert!(offset.abs() < 1e-5f32, "{offset}");
+    }
+}
+
+#[test]
+fn queries_match_brute_force() {
+    let mesh: Mesh = primitives::torus(1f32, 0.35f32, 32, 16);
+    let bvh: Bvh = Bvh::new(&mesh);
+    let mut rng: StdRng = StdRng::seed_from_u64(7);
+    check_queries(&mesh, &bvh, &mut rng, QUERY_COUNT);
+}
+
+#[test]
+fn refit_follows_moved_vertices() {
+    let mut mesh: Mesh = primitives::torus(1f32, 0.35f32, 32, 16);
+    let mut bvh: Bvh = Bvh::new(&mesh);
+    // Twists the torus around y by an angle growing with the height, and lifts it.
+    for v in 0..mesh.v_positions.xs.len() {
+        let p: Point3D<f32> = mesh.v_positions.get_at(v);
+        let (sin, cos) = (2f32 * p.y).sin_cos();
+        mesh.v_positions.xs[v] = cos * p.x + sin * p.z;
+        mesh.v_positions.zs[v] = -sin * p.x + cos * p.z;
+        mesh.v_positions.ys[v] = 1.5f32 * p.y + 0.25f32;
+    }
+    bvh.refit(&mesh);
+    let bounds: BoundingBox3D = bvh.bounds();
+    assert!((bounds.max_y - (1.5f32 * 0.35f32 + 0.25f32)).abs() < 1e-5f32);
+    let mut rng: StdRng = StdRng::seed_from_u64(11);
+    check_queries(&mesh, &bvh, &mut rng, QUERY_COUNT / 4);
+}
+
+#[test]
+fn triangles_without_finite_corners_are_skipped() {
+    let mut mesh: Mesh = primitives::torus(1f32, 0.35f32, 16, 8);
+    let nan: usize = mesh.v_positions.xs.len();
+    mesh.v_positions.add_vector(f32::NAN, f32::NAN, f32::NAN);
+    for _ in 0..32 {
+        mesh.triangles.push(Triangle {
+            vertices: [nan; 3],
+            normals: [0; 3],
+            textures: [0; 3],
+            material: 0,
+        });
+    }
+    let bvh: Bvh = Bvh::new(&mesh);
+    let p: Point3D<f32> = Point3D {
+        x: 0f32,
+        y: 2f32,
+        z: 0f32,
+    };
+    let nearest: NearestPoint = bvh.nearest_point(&mesh, p).unwrap();
+    assert!(nearest.triangle < mesh.triangles.len() - 32);
+    let hit: Option<RayHit> = bvh.closest_hit(
+        &mesh,
+        p,
+        Point3D {
+            x: 1f32,
+            y: -2f32,
+            z: 0f32,
+        },
+        0f32,
+        f32::INFINITY,
+    );
+    assert!(hit.is_some_and(|hit| hit.triangle < mesh.triangles.len() - 32));
+
+    // Nothing but empty boxes.
+    let torus_triangles: usize = mesh.triangles.len() - 32;
+    mesh.triangles.drain(..torus_triangles);
+    let bvh: Bvh = Bvh::new(&mesh);
+    assert!(bvh.bounds().is_empty());
+    assert!(bvh.nearest_point(&mesh, p).is_none());
+    assert!(!bvh.any_hit(&mesh, p, p, 0f32, f32::INFINITY));
+}
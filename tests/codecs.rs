@@ -0,0 +1,560 @@
+#![allow(clippy::needless_return)]
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust_renderer::gif::{self, Frame, GifOptions, Quantizer};
+use rust_renderer::netpbm::{self, Encoding};
+use rust_renderer::tga::{DynamicImage, Grayscale, Image, PixelChannels, Rgb, RgbF32, Rgba};
+use rust_renderer::y4m::{Chroma, ColorMatrix, Y4mWriter};
+use rust_renderer::{bmp, pfm, qoi, radiance};
+use std::io::{self, BufWriter, Cursor};
+
+fn encode<F: FnOnce(&mut BufWriter<&mut Vec<u8>>) -> io::Result<()>>(write: F) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::new();
+    write(&mut BufWriter::new(&mut bytes)).unwrap();
+    return bytes;
+}
+
+// Values over several orders of magnitude, varying along both axes.
+fn float_image(width: u32, height: u32) -> Image<RgbF32> {
+    let mut img: Image<RgbF32> = Image::new(width, height);
+    for (i, pixel) in img.data.iter_mut().enumerate() {
+        let t: f32 = i as f32;
+        *pixel = RgbF32 {
+            r: 0.001f32 * (t + 1f32),
+            g: (t * 0.37f32).sin().abs() * 40f32,
+            b: 1000f32 / (t + 1f32),
+        };
+    }
+    return img;
+}
+
+// Random 8 bit channels, every third row being flat.
+fn test_image<T: PixelChannels>(width: u32, height: u32) -> Image<T> {
+    let mut img: Image<T> = Image::new(width, height);
+    let mut rng: StdRng = StdRng::seed_from_u64(13);
+    for (i, pixel) in img.data.iter_mut().enumerate() {
+        let channels: [u8; 4] = if (i / width as usize) % 3 == 1 {
+            [40, 80, 120, 160]
+        } else {
+            rng.gen()
+        };
+        *pixel = T::from_channels(&channels[..T::BPP as usize]);
+    }
+    return img;
+}
+
+fn channels<T: PixelChannels>(img: &Image<T>) -> (u8, Vec<u8>) {
+    let mut bytes: Vec<u8> = Vec::new();
+    for pixel in &img.data {
+        pixel.push_channels(&mut bytes);
+    }
+    return (T::BPP, bytes);
+}
+
+fn dynamic_channels(img: &DynamicImage) -> (u8, Vec<u8>) {
+    return match img {
+        DynamicImage::Grayscale(img) => channels(img),
+        DynamicImage::Rgb(img) => channels(img),
+        DynamicImage::Rgba(img) => channels(img),
+    };
+}
+
+fn assert_invalid_data<T>(result: io::Result<T>) {
+    match result {
+        Err(e) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+        Ok(_) => panic!("the header was accepted"),
+    }
+}
+
+#[test]
+fn pfm_round_trip() {
+    let img: Image<RgbF32> = float_image(7, 5);
+    for vflip in [false, true] {
+        let bytes: Vec<u8> = encode(|writer| pfm::write(&img, writer, vflip));
+        let decoded: Image<RgbF32> = pfm::read(&mut Cursor::new(&bytes), vflip).unwrap();
+        assert_eq!((decoded.width, decoded.height), (7, 5));
+        for (a, b) in decoded.data.iter().zip(&img.data) {
+            assert_eq!((a.r, a.g, a.b), (b.r, b.g, b.b));
+        }
+    }
+}
+
+#[test]
+fn pfm_reads_big_endian_greyscale() {
+    let mut bytes: Vec<u8> = b"Pf\n2 1\n1.0\n".to_vec();
+    bytes.extend(0.5f32.to_be_bytes());
+    bytes.extend(2f32.to_be_bytes());
+    let img: Image<RgbF32> = pfm::read(&mut Cursor::new(&bytes), true).unwrap();
+    assert_eq!(
+        (img.data[0].r, img.data[0].g, img.data[0].b),
+        (0.5, 0.5, 0.5)
+    );
+    assert_eq!(
+        (img.data[1].r, img.data[1].g, img.data[1].b),
+        (2.0, 2.0, 2.0)
+    );
+}
+
+#[test]
+fn radiance_round_trip() {
+    // Narrower than 8 pixels, scanlines are written flat, otherwise run length encoded.
+    for width in [5, 40] {
+        let img: Image<RgbF32> = float_image(width, 6);
+        for vflip in [false, true] {
+            let bytes: Vec<u8> = encode(|writer| radiance::write(&img, writer, vflip));
+            let decoded: Image<RgbF32> = radiance::read(&mut Cursor::new(&bytes), vflip).unwrap();
+            assert_eq!((decoded.width, decoded.height), (width, 6));
+            for (a, b) in decoded.data.iter().zip(&img.data) {
+                // The shared exponent leaves 8 bits of mantissa to the largest channel.
+                let max: f32 = b.r.max(b.g).max(b.b);
+                for (x, y) in [(a.r, b.r), (a.g, b.g), (a.b, b.b)] {
+                    assert!((x - y).abs() <= max / 128f32, "{x} {y}");
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn oversized_float_headers_are_rejected() {
+    assert_invalid_data(pfm::read(
+        &mut Cursor::new(b"PF\n100000 100000\n-1\n"),
+        false,
+    ));
+    assert_invalid_data(radiance::read(
+        &mut Cursor::new(b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 100000 +X 100000\n"),
+        false,
+    ));
+}
+
+fn check_netpbm_round_trip<T: PixelChannels>(encoding: Encoding) {
+    let img: Image<T> = test_image(9, 7);
+    for vflip in [false, true] {
+        let bytes: Vec<u8> = encode(|writer| img.write_netpbm(writer, vflip, encoding));
+        let decoded: DynamicImage = netpbm::read(&mut Cursor::new(&bytes), vflip).unwrap();
+        assert_eq!(dynamic_channels(&decoded), channels(&img));
+    }
+}
+
+#[test]
+fn netpbm_round_trip() {
+    for encoding in [Encoding::Ascii, Encoding::Binary] {
+        check_netpbm_round_trip::<Grayscale>(encoding);
+        check_netpbm_round_trip::<Rgb>(encoding);
+    }
+    check_netpbm_round_trip::<Rgba>(Encoding::Binary);
+
+    let img: Image<Rgba> = test_image(2, 2);
+    let error: io::Error = img
+        .write_netpbm(&mut BufWriter::new(Vec::new()), false, Encoding::Ascii)
+        .unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn netpbm_known_bytes() {
+    // Samples are written in RGB order, top row first without `vflip`.
+    let mut img: Image<Rgb> = Image::new(1, 2);
+    img.data[0] = Rgb { r: 1, g: 2, b: 3 };
+    img.data[1] = Rgb { r: 4, g: 5, b: 6 };
+    let bytes: Vec<u8> = encode(|writer| img.write_netpbm(writer, false, Encoding::Binary));
+    assert_eq!(bytes, b"P6\n1 2\n255\n\x01\x02\x03\x04\x05\x06");
+    let bytes: Vec<u8> = encode(|writer| img.write_netpbm(writer, true, Encoding::Ascii));
+    assert_eq!(bytes, b"P3\n1 2\n255\n4 5 6\n1 2 3\n");
+
+    // Comments, 16 bit plain samples rescaled to 8 bits.
+    let img: DynamicImage = netpbm::read(
+        &mut Cursor::new(b"P2 # comment\n3 1\n65535\n0 32768 65535\n"),
+        false,
+    )
+    .unwrap();
+    assert_eq!(dynamic_channels(&img), (1, vec![0, 127, 255]));
+    // Binary 16 bit samples are big endian.
+    let img: DynamicImage =
+        netpbm::read(&mut Cursor::new(b"P5\n2 1\n1023\n\x03\xff\x00\x00"), false).unwrap();
+    assert_eq!(dynamic_channels(&img), (1, vec![255, 0]));
+    // Greyscale with alpha PAM expands to RGBA.
+    let img: DynamicImage = netpbm::read(
+        &mut Cursor::new(b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 2\nMAXVAL 255\nENDHDR\n\x10\x80"),
+        false,
+    )
+    .unwrap();
+    assert_eq!(dynamic_channels(&img), (4, vec![16, 16, 16, 128]));
+
+    assert_invalid_data(netpbm::read(
+        &mut Cursor::new(b"P6\n100000 100000\n255\n"),
+        false,
+    ));
+}
+
+// Greyscale images have no encoding of their own and come back as RGB.
+fn grey_to_rgb(img: &Image<Grayscale>) -> Image<Rgb> {
+    let mut rgb: Image<Rgb> = Image::new(img.width, img.height);
+    for (rgb, grey) in rgb.data.iter_mut().zip(&img.data) {
+        *rgb = Rgb {
+            r: grey.i,
+            g: grey.i,
+            b: grey.i,
+        };
+    }
+    return rgb;
+}
+
+fn check_bmp_and_qoi_round_trip<T: PixelChannels>(img: &Image<T>, expected: &(u8, Vec<u8>)) {
+    for vflip in [false, true] {
+        let bytes: Vec<u8> = encode(|writer| img.write_bmp(writer, vflip));
+        let decoded: DynamicImage = bmp::read(&mut Cursor::new(&bytes), vflip).unwrap();
+        assert_eq!(&dynamic_channels(&decoded), expected);
+        let bytes: Vec<u8> = encode(|writer| img.write_qoi(writer, vflip));
+        let decoded: DynamicImage = qoi::read(&mut Cursor::new(&bytes), vflip).unwrap();
+        assert_eq!(&dynamic_channels(&decoded), expected);
+    }
+}
+
+#[test]
+fn bmp_and_qoi_round_trip() {
+    // Odd widths exercise the bitmap row padding, long flat rows the QOI runs.
+    for width in [7, 70] {
+        let grey: Image<Grayscale> = test_image(width, 6);
+        check_bmp_and_qoi_round_trip(&grey, &channels(&grey_to_rgb(&grey)));
+        let rgb: Image<Rgb> = test_image(width, 6);
+        check_bmp_and_qoi_round_trip(&rgb, &channels(&rgb));
+        let rgba: Image<Rgba> = test_image(width, 6);
+        check_bmp_and_qoi_round_trip(&rgba, &channels(&rgba));
+    }
+}
+
+#[test]
+fn bmp_known_bytes() {
+    // BGR samples, rows stored bottom-up and padded to 4 bytes.
+    let mut img: Image<Rgb> = Image::new(1, 2);
+    img.data[0] = Rgb { r: 1, g: 2, b: 3 };
+    img.data[1] = Rgb { r: 4, g: 5, b: 6 };
+    let bytes: Vec<u8> = encode(|writer| img.write_bmp(writer, false));
+    assert_eq!(&bytes[..2], b"BM");
+    assert_eq!(u32::from_le_bytes(bytes[2..6].try_into().unwrap()), 62);
+    assert_eq!(u32::from_le_bytes(bytes[10..14].try_into().unwrap()), 54);
+    assert_eq!(&bytes[54..], &[6, 5, 4, 0, 3, 2, 1, 0]);
+}
+
+#[test]
+fn qoi_known_bytes() {
+    let pixels: [[u8; 4]; 5] = [
+        // Equal to the initial previous pixel: a run.
+        [0, 0, 0, 255],
+        // Green difference too large for OP_DIFF: OP_LUMA.
+        [1, 2, 3, 255],
+        [0, 0, 0, 255],
+        // Seen before: OP_INDEX.
+        [1, 2, 3, 255],
+        // Alpha change: OP_RGBA.
+        [1, 2, 3, 0],
+    ];
+    let mut img: Image<Rgba> = Image::new(5, 1);
+    for (pixel, [r, g, b, a]) in img.data.iter_mut().zip(pixels) {
+        *pixel = Rgba { r, g, b, a };
+    }
+    let bytes: Vec<u8> = encode(|writer| img.write_qoi(writer, false));
+    let mut expected: Vec<u8> = b"qoif\0\0\0\x05\0\0\0\x01\x04\0".to_vec();
+    expected.extend([0xc0, 0xa2, 0x79, 0x9e, 0x97, 0x17, 0xff, 1, 2, 3, 0]);
+    expected.extend([0, 0, 0, 0, 0, 0, 0, 1]);
+    assert_eq!(bytes, expected);
+}
+
+// Variable width LZW decoding, codes packed least significant bit first.
+fn lzw_decode(data: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear_code: usize = 1 << min_code_size;
+    let initial_table: Vec<Vec<u8>> = (0..clear_code + 2).map(|i| vec![i as u8]).collect();
+    let mut table: Vec<Vec<u8>> = initial_table.clone();
+    let mut code_size: usize = min_code_size as usize + 1;
+    let mut previous: Option<Vec<u8>> = None;
+    let mut output: Vec<u8> = Vec::new();
+    let mut bit: usize = 0;
+    while bit + code_size <= data.len() * 8 {
+        let code: usize = (0..code_size)
+            .map(|i| ((data[(bit + i) / 8] >> ((bit + i) % 8)) as usize & 1) << i)
+            .sum();
+        bit += code_size;
+        if code == clear_code {
+            table = initial_table.clone();
+            code_size = min_code_size as usize + 1;
+            previous = None;
+            continue;
+        }
+        if code == clear_code + 1 {
+            return output;
+        }
+        let entry: Vec<u8> = match (table.get(code), &previous) {
+            (Some(entry), _) => entry.clone(),
+            (None, Some(previous)) if code == table.len() => {
+                [previous.as_slice(), &previous[..1]].concat()
+            }
+            _ => panic!("invalid LZW code {code}"),
+        };
+        output.extend_from_slice(&entry);
+        if let Some(previous) = previous {
+            if table.len() < 4096 {
+                table.push([previous.as_slice(), &entry[..1]].concat());
+                if table.len() == 1 << code_size && code_size < 12 {
+                    code_size += 1;
+                }
+            }
+        }
+        previous = Some(entry);
+    }
+    panic!("missing LZW end code");
+}
+
+struct DecodedGif {
+    width: u16,
+    height: u16,
+    loop_count: Option<u16>,
+    // Delay and colours of every frame.
+    frames: Vec<(u16, Vec<[u8; 3]>)>,
+}
+
+fn read_palette(bytes: &[u8], position: &mut usize, flags: u8) -> Option<Vec<[u8; 3]>> {
+    if flags & 0x80 == 0 {
+        return None;
+    }
+    let size: usize = 3 << ((flags & 0x07) + 1);
+    let palette: Vec<[u8; 3]> = bytes[*position..*position + size]
+        .chunks_exact(3)
+        .map(|rgb| [rgb[0], rgb[1], rgb[2]])
+        .collect();
+    *position += size;
+    return Some(palette);
+}
+
+fn read_sub_blocks(bytes: &[u8], position: &mut usize) -> Vec<u8> {
+    let mut data: Vec<u8> = Vec::new();
+    loop {
+        let length: usize = bytes[*position] as usize;
+        *position += 1;
+        if length == 0 {
+            return data;
+        }
+        data.extend_from_slice(&bytes[*position..*position + length]);
+        *position += length;
+    }
+}
+
+fn decode_gif(bytes: &[u8]) -> DecodedGif {
+    assert_eq!(&bytes[..6], b"GIF89a");
+    let mut gif: DecodedGif = DecodedGif {
+        width: u16::from_le_bytes([bytes[6], bytes[7]]),
+        height: u16::from_le_bytes([bytes[8], bytes[9]]),
+        loop_count: None,
+        frames: Vec::new(),
+    };
+    let mut position: usize = 13;
+    let global_palette: Option<Vec<[u8; 3]>> = read_palette(bytes, &mut position, bytes[10]);
+    let mut delay: u16 = 0;
+    loop {
+        let block: u8 = bytes[position];
+        // Extensions are identified by their label.
+        let label: u8 = if block == 0x21 {
+            bytes[position + 1]
+        } else {
+            0
+        };
+        position += if block == 0x21 { 2 } else { 1 };
+        match (block, label) {
+            (0x21, 0xff) => {
+                let application: Vec<u8> = read_sub_blocks(bytes, &mut position);
+                assert_eq!(application[..11], *b"NETSCAPE2.0");
+                gif.loop_count = Some(u16::from_le_bytes([application[12], application[13]]));
+            }
+            (0x21, 0xf9) => {
+                let control: Vec<u8> = read_sub_blocks(bytes, &mut position);
+                delay = u16::from_le_bytes([control[1], control[2]]);
+            }
+            (0x2c, _) => {
+                assert_eq!(
+                    u16::from_le_bytes([bytes[position + 4], bytes[position + 5]]),
+                    gif.width
+                );
+                let flags: u8 = bytes[position + 8];
+                position += 9;
+                let palette: Vec<[u8; 3]> = read_palette(bytes, &mut position, flags)
+                    .or(global_palette.clone())
+                    .unwrap();
+                let min_code_size: u8 = bytes[position];
+                position += 1;
+                let indices: Vec<u8> =
+                    lzw_decode(&read_sub_blocks(bytes, &mut position), min_code_size);
+                assert_eq!(indices.len(), gif.width as usize * gif.height as usize);
+                let colours: Vec<[u8; 3]> = indices.iter().map(|&i| palette[i as usize]).collect();
+                gif.frames.push((delay, colours));
+            }
+            (0x3b, _) => return gif,
+            (block, _) => panic!("unexpected block {block:#x}"),
+        }
+    }
+}
+
+fn rgb_colours(img: &Image<Rgb>, vflip: bool) -> Vec<[u8; 3]> {
+    let width: usize = img.width as usize;
+    let mut colours: Vec<[u8; 3]> = Vec::new();
+    for y in 0..img.height as usize {
+        let row: usize = if vflip {
+            img.height as usize - 1 - y
+        } else {
+            y
+        };
+        for pixel in &img.data[row * width..(row + 1) * width] {
+            colours.push([pixel.r, pixel.g, pixel.b]);
+        }
+    }
+    return colours;
+}
+
+// Frames with `colour_count` distinct colours, enough pixels for the LZW table to fill up and be
+// reset.
+fn gif_frames(colour_count: usize) -> Vec<Image<Rgb>> {
+    let mut rng: StdRng = StdRng::seed_from_u64(17);
+    let colours: Vec<Rgb> = (0..colour_count)
+        .map(|_| Rgb {
+            r: rng.gen(),
+            g: rng.gen(),
+            b: rng.gen(),
+        })
+        .collect();
+    let mut frames: Vec<Image<Rgb>> = Vec::new();
+    for _ in 0..2 {
+        let mut img: Image<Rgb> = Image::new(120, 90);
+        for pixel in img.data.iter_mut() {
+            *pixel = colours[rng.gen_range(0..colour_count)];
+        }
+        frames.push(img);
+    }
+    return frames;
+}
+
+#[test]
+fn gif_round_trip() {
+    // Up to 256 colours, the palette is exact.
+    let images: Vec<Image<Rgb>> = gif_frames(200);
+    let frames: Vec<Frame> = vec![
+        Frame {
+            image: &images[0],
+            delay: 4,
+        },
+        Frame {
+            image: &images[1],
+            delay: 25,
+        },
+    ];
+    for global_palette in [false, true] {
+        for vflip in [false, true] {
+            let options: GifOptions = GifOptions {
+                global_palette,
+                dither: false,
+                loop_count: Some(3),
+                ..Default::default()
+            };
+            let bytes: Vec<u8> = encode(|writer| gif::write(writer, &frames, &options, vflip));
+            let gif: DecodedGif = decode_gif(&bytes);
+            assert_eq!((gif.width, gif.height), (120, 90));
+            assert_eq!(gif.loop_count, Some(3));
+            assert_eq!(gif.frames.len(), 2);
+            for (decoded, frame) in gif.frames.iter().zip(&frames) {
+                assert_eq!(decoded.0, frame.delay);
+                assert!(decoded.1 == rgb_colours(frame.image, vflip));
+            }
+        }
+    }
+}
+
+#[test]
+fn gif_quantizes_to_nearby_colours() {
+    let images: Vec<Image<Rgb>> = gif_frames(5000);
+    let frames: Vec<Frame> = vec![Frame {
+        image: &images[0],
+        delay: 0,
+    }];
+    for quantizer in [Quantizer::MedianCut, Quantizer::Octree] {
+        for dither in [false, true] {
+            let options: GifOptions = GifOptions {
+                quantizer,
+                dither,
+                loop_count: None,
+                ..Default::default()
+            };
+            let bytes: Vec<u8> = encode(|writer| gif::write(writer, &frames, &options, false));
+            let gif: DecodedGif = decode_gif(&bytes);
+            assert_eq!(gif.loop_count, None);
+            let expected: Vec<[u8; 3]> = rgb_colours(&images[0], false);
+            let error: f64 = gif.frames[0]
+                .1
+                .iter()
+                .zip(&expected)
+                .map(|(a, b)| (0..3).map(|c| a[c].abs_diff(b[c]) as f64).sum::<f64>())
+                .sum::<f64>()
+                / (3 * expected.len()) as f64;
+            // Random colours spread over the whole cube, against 128 for random guesses.
+            assert!(error < 24f64, "{quantizer:?} {dither} {error}");
+        }
+    }
+}
+
+const RED: Rgb = Rgb { r: 255, g: 0, b: 0 };
+const WHITE: Rgb = Rgb {
+    r: 255,
+    g: 255,
+    b: 255,
+};
+
+#[test]
+fn y4m_known_bytes() {
+    // Full chroma, limited range: red is (81, 90, 240) with BT.601 and (63, 102, 240) with
+    // BT.709, white (235, 128, 128).
+    let mut img: Image<Rgb> = Image::new(2, 1);
+    img.data[0] = RED;
+    img.data[1] = WHITE;
+    for (matrix, red) in [
+        (ColorMatrix::Bt601, [81, 90, 240]),
+        (ColorMatrix::Bt709, [63, 102, 240]),
+    ] {
+        let mut writer: Y4mWriter<Vec<u8>> =
+            Y4mWriter::new(Vec::new(), 2, 1, (30, 1), Chroma::C444, matrix).unwrap();
+        writer.write_frame(&img, false).unwrap();
+        let mut expected: Vec<u8> =
+            b"YUV4MPEG2 W2 H1 F30:1 Ip A1:1 C444 XYSCSS=444 XCOLORRANGE=LIMITED\nFRAME\n".to_vec();
+        expected.extend([red[0], 235, red[1], 128, red[2], 128]);
+        assert_eq!(writer.into_inner(), expected);
+    }
+
+    // Chroma averaged over 2x2 blocks, the odd last row and column over what is left. With
+    // `vflip`, the red first image row is written last.
+    let mut img: Image<Rgb> = Image::new(3, 3);
+    img.data.fill(WHITE);
+    img.data[0] = RED;
+    let mut writer: Y4mWriter<Vec<u8>> =
+        Y4mWriter::new(Vec::new(), 3, 3, (25, 2), Chroma::C420, ColorMatrix::Bt601).unwrap();
+    writer.write_frame(&img, true).unwrap();
+    writer.write_frame(&img, false).unwrap();
+    let mut expected: Vec<u8> =
+        b"YUV4MPEG2 W3 H3 F25:2 Ip A1:1 C420jpeg XYSCSS=420JPEG XCOLORRANGE=LIMITED\n".to_vec();
+    expected.extend(b"FRAME\n");
+    expected.extend([235, 235, 235, 235, 235, 235, 81, 235, 235]);
+    expected.extend([128, 128, 109, 128]);
+    expected.extend([128, 128, 184, 128]);
+    expected.extend(b"FRAME\n");
+    expected.extend([81, 235, 235, 235, 235, 235, 235, 235, 235]);
+    expected.extend([119, 128, 128, 128]);
+    expected.extend([156, 128, 128, 128]);
+    assert_eq!(writer.into_inner(), expected);
+}
+
+#[test]
+fn y4m_rejects_mismatched_frames() {
+    let result: io::Result<Y4mWriter<Vec<u8>>> =
+        Y4mWriter::new(Vec::new(), 0, 4, (30, 1), Chroma::C420, ColorMatrix::Bt709);
+    assert_eq!(result.err().unwrap().kind(), io::ErrorKind::InvalidInput);
+    let mut writer: Y4mWriter<Vec<u8>> =
+        Y4mWriter::new(Vec::new(), 4, 4, (30, 1), Chroma::C420, ColorMatrix::Bt709).unwrap();
+    let error: io::Error = writer.write_frame(&Image::new(4, 3), false).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+}
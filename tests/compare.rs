@@ -0,0 +1,96 @@
+#![allow(clippy::needless_return)]
+
+use rust_renderer::compare::{self, Tolerance};
+use rust_renderer::drawers::{self, Viewport};
+use rust_renderer::obj_importer;
+use rust_renderer::tga::{Grayscale, Image, Rgb};
+
+fn render() -> Image<Rgb> {
+    return drawers::mesh(
+        obj_importer::obj_to_mesh("assets/input.obj"),
+        Viewport::default(),
+    );
+}
+
+// Flips a few pixels far apart from each other.
+fn with_isolated_noise(img: &Image<Rgb>) -> Image<Rgb> {
+    let mut noisy: Image<Rgb> = img.clone();
+    for k in 0..10u32 {
+        let pixel: &mut Rgb = noisy.get_mut(100 + 150 * k, 200 + 60 * k).unwrap();
+        *pixel = Rgb {
+            b: 255 - pixel.b,
+            g: 255 - pixel.g,
+            r: 255 - pixel.r,
+        };
+    }
+    return noisy;
+}
+
+#[test]
+fn identical_renders_match() {
+    let img: Image<Rgb> = render();
+    let comparison: compare::Comparison = compare::compare(&img, &render()).unwrap();
+    assert_eq!(comparison.max_error, 0);
+    assert_eq!(comparison.differing_pixels, 0);
+    assert!(comparison.psnr.is_infinite());
+    assert_eq!(comparison.ssim, 1f64);
+    compare::assert_images_similar(&img, &render(), &Tolerance::default());
+}
+
+#[test]
+fn shifted_render_is_similar_but_not_identical() {
+    let img: Image<Rgb> = render();
+    let mut shifted: Image<Rgb> = Image::new(img.width, img.height);
+    shifted.blit(&img, 1, 0);
+    let comparison: compare::Comparison = compare::compare(&img, &shifted).unwrap();
+    assert!(comparison.max_error > 0);
+    assert!(comparison.psnr.is_finite() && comparison.psnr > 30f64);
+    assert!(comparison.ssim < 1f64 && comparison.ssim > 0.9f64);
+}
+
+#[test]
+fn isolated_pixels_can_be_ignored() {
+    let img: Image<Rgb> = render();
+    let noisy: Image<Rgb> = with_isolated_noise(&img);
+    let diff: Image<Grayscale> = compare::difference(&img, &noisy).unwrap();
+    assert_eq!(diff.pixels().filter(|pixel| pixel.i > 0).count(), 10);
+    assert!(compare::without_isolated_pixels(&diff)
+        .pixels()
+        .all(|pixel| pixel.i == 0));
+    // Every measure, PSNR and SSIM included, ignores them.
+    let tolerance: Tolerance = Tolerance {
+        ignore_isolated_pixels: true,
+        ..Default::default()
+    };
+    let comparison: compare::Comparison =
+        compare::check_images_similar(&noisy, &img, &tolerance).unwrap();
+    assert_eq!(comparison.differing_pixels, 0);
+    assert!(comparison.psnr.is_infinite());
+    assert_eq!(comparison.ssim, 1f64);
+}
+
+#[test]
+#[should_panic(expected = "images differ")]
+fn isolated_pixels_fail_strict_comparison() {
+    let img: Image<Rgb> = render();
+    compare::assert_images_similar(&with_isolated_noise(&img), &img, &Tolerance::default());
+}
+
+#[test]
+fn heatmap_highlights_differences() {
+    let img: Image<Rgb> = render();
+    let diff: Image<Grayscale> = compare::difference(&img, &with_isolated_noise(&img)).unwrap();
+    let heat: Image<Rgb> = compare::heatmap(&diff);
+    assert_eq!((heat.width, heat.height), (img.width, img.height));
+    let hot: &Rgb = heat.get(100, 200).unwrap();
+    assert_eq!((hot.r, hot.g, hot.b), (255, 255, 255));
+    let cold: &Rgb = heat.get(0, 0).unwrap();
+    assert_eq!((cold.r, cold.g, cold.b), (0, 0, 0));
+}
+
+#[test]
+fn mismatched_dimensions_are_an_error() {
+    let a: Image<Grayscale> = Image::new(4, 4);
+    let b: Image<Grayscale> = Image::new(4, 5);
+    assert!(compare::compare(&a, &b).is_err());
+}
@@ -0,0 +1,124 @@
+#![allow(clippy::needless_return)]
+
+use rust_renderer::decimation::{DecimationOptions, LodChain};
+use rust_renderer::linear_algebra;
+use rust_renderer::mesh::{Coords3D, Mesh, Point3D};
+use rust_renderer::primitives;
+
+// Triangles of a mesh centred on the origin whose normal points towards it.
+fn inverted_triangles(mesh: &Mesh) -> usize {
+    return mesh
+        .triangles
+        .iter()
+        .filter(|triangle| {
+            let [p, q, r] = triangle.vertices.map(|v| mesh.v_positions.get_at(v));
+            let centroid: Point3D<f32> = Point3D {
+                x: (p.x + q.x + r.x) / 3f32,
+                y: (p.y + q.y + r.y) / 3f32,
+                z: (p.z + q.z + r.z) / 3f32,
+            };
+            let normal: Point3D<f32> = linear_algebra::cross_product(
+                linear_algebra::subtract(q, p),
+                linear_algebra::subtract(r, p),
+            );
+            return linear_algebra::dot_product(normal, centroid) < 0f32;
+        })
+        .count();
+}
+
+// With `split` set, every corner normal gets its own vertex, as in meshes whose vertices are split
+// along the UV seam and at the poles.
+fn sphere(split: bool) -> Mesh {
+    let mut sphere: Mesh = primitives::uv_sphere(1f32, 64, 32);
+    if split {
+        let mut positions: Vec<Point3D<f32>> = vec![
+            Point3D {
+                x: 0f32,
+                y: 0f32,
+                z: 0f32
+            };
+            sphere.v_normals.xs.len()
+        ];
+        for triangle in &mut sphere.triangles {
+            for k in 0..3 {
+                positions[triangle.normals[k]] = sphere.v_positions.get_at(triangle.vertices[k]);
+            }
+            triangle.vertices = triangle.normals;
+        }
+        sphere.v_positions = Coords3D {
+            xs: positions.iter().map(|p| p.x).collect(),
+            ys: positions.iter().map(|p| p.y).collect(),
+            zs: positions.iter().map(|p| p.z).collect(),
+        };
+    }
+    return sphere;
+}
+
+#[test]
+fn decimated_sphere_has_no_inverted_triangles() {
+    for split in [false, true] {
+        for target in [100, 200, 400] {
+            let decimated: Mesh = sphere(split).decimate(&DecimationOptions {
+                target_triangles: target,
+                ..DecimationOptions::default()
+            });
+            assert!(
+                decimated.triangles.len() <= target,
+                "split: {split}, target: {target}, triangles: {}",
+                decimated.triangles.len()
+            );
+            assert_eq!(
+                inverted_triangles(&decimated),
+                0,
+                "split: {split}, target: {target}"
+            );
+        }
+    }
+}
+
+// Split vertices decimate as seams, not as open boundaries holding on to their triangles.
+#[test]
+fn sphere_lod_chain_keeps_its_shape() {
+    for split in [false, true] {
+        let chain: LodChain = LodChain::new(&sphere(split), 6, 0.5);
+        assert_eq!(chain.levels.len(), 6, "split: {split}");
+        for level in &chain.levels {
+            assert!(
+                level.error < 0.25f32,
+                "split: {split}, triangles: {}, error: {}",
+                level.mesh.triangles.len(),
+                level.error
+            );
+            assert_eq!(inverted_triangles(&level.mesh), 0, "split: {split}");
+        }
+        let coarsest: &Mesh = &chain.levels[5].mesh;
+        assert!(coarsest.triangles.len() <= 3968 / 32, "split: {split}");
+    }
+}
+
+// A flat disc, its boundary a circle of 64 vertices: below 64 triangles the boundary gets cut,
+// which moves no point off the plane of the triangles, whatever the weight of the planes holding
+// the boundary.
+#[test]
+fn flat_disc_decimates_without_error() {
+    let mut disc: Mesh = primitives::plane(2f32, 2f32, 16, 16);
+    for v in 0..disc.v_positions.xs.len() {
+        let (x, y) = (disc.v_positions.xs[v], disc.v_positions.ys[v]);
+        disc.v_positions.xs[v] = x * (1f32 - y * y / 2f32).sqrt();
+        disc.v_positions.ys[v] = y * (1f32 - x * x / 2f32).sqrt();
+    }
+    let chain: LodChain = LodChain::new(&disc, 6, 0.5);
+    assert_eq!(chain.levels.len(), 6);
+    assert!(chain.levels[5].mesh.triangles.len() <= 16);
+    for level in &chain.levels {
+        assert!(level.error < 1e-5f32, "error: {}", level.error);
+    }
+    assert_eq!(chain.select(1000f32, 0.5f32), 5);
+
+    let decimated: Mesh = disc.decimate(&DecimationOptions {
+        max_error: 1e-4f32,
+        boundary_weight: 1000f32,
+        ..DecimationOptions::default()
+    });
+    assert!(decimated.triangles.len() < 64);
+}
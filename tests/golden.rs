@@ -0,0 +1,306 @@
+#![allow(clippy::needless_return)]
+
+// Reference render tests. Set UPDATE_GOLDEN_IMAGES=1 to regenerate the images under
+// tests/references after an intended change in the output. On failure, the render and a
+// difference heatmap are written to target/golden-diffs.
+
+use rust_renderer::compare::{self, Tolerance};
+use rust_renderer::drawers::{self, FeatureLineStyle, Viewport};
+use rust_renderer::mesh::{Coords2D, Coords3D, Material, Mesh, Point3D, Triangle};
+use rust_renderer::tga::{DynamicImage, Image, Rgb};
+use rust_renderer::{obj_importer, png, primitives};
+use std::{env, fs, fs::File, io::BufReader, path::PathBuf};
+
+const UPDATE_VAR: &str = "UPDATE_GOLDEN_IMAGES";
+
+// Rendering is deterministic, the slack only absorbs floating point differences between
+// platforms along triangle edges: a few hundred slightly off pixels out of the 1920x1080.
+const TOLERANCE: Tolerance = Tolerance {
+    max_error: 32,
+    mean_error: 0.05f64,
+    min_psnr: 40f64,
+    min_ssim: 0.995f64,
+    max_differing_pixels: 1000,
+    ignore_isolated_pixels: true,
+};
+
+fn check_golden(name: &str, actual: &Image<Rgb>) {
+    let root: PathBuf = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let reference: PathBuf = root.join("tests/references").join(format!("{}.png", name));
+    if env::var_os(UPDATE_VAR).is_some_and(|value| value != "0") {
+        fs::create_dir_all(reference.parent().unwrap()).unwrap();
+        actual.save(&reference).unwrap();
+        return;
+    }
+
+    let file: File = File::open(&reference).unwrap_or_else(|_| {
+        panic!(
+            "missing reference {}, run with {}=1 to create it",
+            reference.display(),
+            UPDATE_VAR
+        )
+    });
+    let expected: Image<Rgb> = match png::read(&mut BufReader::new(file), true).unwrap() {
+        DynamicImage::Rgb(img) => img,
+        DynamicImage::Grayscale(img) => img.convert(),
+        DynamicImage::Rgba(img) => img.convert(),
+    };
+    if let Err(message) = compare::check_images_similar(actual, &expected, &TOLERANCE) {
+        let artifacts: PathBuf = root.join("target/golden-diffs");
+        fs::create_dir_all(&artifacts).unwrap();
+        actual
+            .save(artifacts.join(format!("{}.actual.png", name)))
+            .unwrap();
+        if let Ok(diff) = compare::difference(actual, &expected) {
+            compare::heatmap(&diff)
+                .save(artifacts.join(format!("{}.diff.png", name)))
+                .unwrap();
+        }
+        panic!(
+            "{} does not match its reference: {}, see {}",
+            name,
+            message,
+            artifacts.display()
+        );
+    }
+}
+
+fn load(asset: &str) -> Mesh {
+    return obj_importer::obj_to_mesh(&format!("{}/assets/{}", env!("CARGO_MANIFEST_DIR"), asset));
+}
+
+// Regular octahedron with smooth normals, slightly rotated so that no face is edge-on.
+fn octahedron() -> Mesh {
+    let (c, s) = (0.5f32.cos(), 0.5f32.sin());
+    let corners: [[f32; 3]; 6] = [
+        [1f32, 0f32, 0f32],
+        [-1f32, 0f32, 0f32],
+        [0f32, 1f32, 0f32],
+        [0f32, -1f32, 0f32],
+        [0f32, 0f32, 1f32],
+        [0f32, 0f32, -1f32],
+    ];
+    let mut positions: Coords3D = Coords3D {
+        xs: Vec::new(),
+        ys: Vec::new(),
+        zs: Vec::new(),
+    };
+    let mut normals: Coords3D = positions.clone();
+    for [x, y, z] in corners {
+        // Rotation around the x then the y axis.
+        let (y, z) = (c * y - s * z, s * y + c * z);
+        let (x, z) = (c * x + s * z, -s * x + c * z);
+        positions.add_vector(x, y, z);
+        normals.add_vector(x, y, z);
+    }
+    let faces: [[usize; 3]; 8] = [
+        [0, 2, 4],
+        [2, 1, 4],
+        [1, 3, 4],
+        [3, 0, 4],
+        [2, 0, 5],
+        [1, 2, 5],
+        [3, 1, 5],
+        [0, 3, 5],
+    ];
+    let mut textures: Coords2D = Coords2D {
+        us: Vec::new(),
+        vs: Vec::new(),
+    };
+    textures.add_vector(0f32, 0f32);
+    return Mesh {
+        v_positions: positions,
+        v_normals: normals,
+        v_textures: textures,
+        triangles: faces
+            .iter()
+            .map(|&vertices| Triangle {
+                vertices,
+                normals: vertices,
+                textures: [0usize; 3],
+                material: 0,
+            })
+            .collect(),
+        materials: vec![Material::new("default")],
+    };
+}
+
+#[test]
+fn mesh_input() {
+    check_golden(
+        "mesh_input",
+        &drawers::mesh(load("input.obj"), Viewport::default()),
+    );
+}
+
+// The asset has a null normal, replaced by the normal of its face so that the shading shows.
+#[test]
+fn mesh_test_triangle() {
+    let mut triangle: Mesh = load("test_triangle.obj");
+    let normal: Point3D<f32> = triangle.triangle_normal(&triangle.triangles[0]);
+    triangle.v_normals.xs = vec![normal.x];
+    triangle.v_normals.ys = vec![normal.y];
+    triangle.v_normals.zs = vec![normal.z];
+    let img: Image<Rgb> = drawers::mesh(triangle, Viewport::default());
+    assert!(img
+        .data
+        .iter()
+        .any(|pixel| pixel.r > 0 || pixel.g > 0 || pixel.b > 0));
+    check_golden("mesh_test_triangle", &img);
+}
+
+#[test]
+fn mesh_octahedron() {
+    check_golden(
+        "mesh_octahedron",
+        &drawers::mesh(octahedron(), Viewport::default()),
+    );
+}
+
+// Without normals, as in OBJ files lacking `vn` lines, triangles are shaded with their face
+// normal.
+#[test]
+fn missing_normals_shade_flat() {
+    let mut flat: Mesh = octahedron();
+    let normals: Vec<Point3D<f32>> = flat
+        .triangles
+        .iter()
+        .map(|triangle| flat.triangle_normal(triangle))
+        .collect();
+    flat.v_normals.xs = normals.iter().map(|n| n.x).collect();
+    flat.v_normals.ys = normals.iter().map(|n| n.y).collect();
+    flat.v_normals.zs = normals.iter().map(|n| n.z).collect();
+    for (t, triangle) in flat.triangles.iter_mut().enumerate() {
+        triangle.normals = [t; 3];
+    }
+    let mut missing: Mesh = octahedron();
+    missing.v_normals.xs.clear();
+    missing.v_normals.ys.clear();
+    missing.v_normals.zs.clear();
+
+    let expected: Image<Rgb> = drawers::mesh(flat, Viewport::default());
+    let actual: Image<Rgb> = drawers::mesh(missing, Viewport::default());
+    compare::check_images_similar(&actual, &expected, &Tolerance::default()).unwrap();
+}
+
+#[test]
+fn mesh_capsule() {
+    check_golden(
+        "mesh_capsule",
+        &drawers::mesh(
+            primitives::capsule(0.4f32, 0.8f32, 24, 8),
+            Viewport::default(),
+        ),
+    );
+}
+
+#[test]
+fn wireframe_input() {
+    check_golden(
+        "wireframe_input",
+        &drawers::wireframe(load("input.obj"), Viewport::default()),
+    );
+}
+
+#[test]
+fn wireframe_test_triangle() {
+    check_golden(
+        "wireframe_test_triangle",
+        &drawers::wireframe(load("test_triangle.obj"), Viewport::default()),
+    );
+}
+
+#[test]
+fn wireframe_octahedron() {
+    check_golden(
+        "wireframe_octahedron",
+        &drawers::wireframe(octahedron(), Viewport::default()),
+    );
+}
+
+#[test]
+fn wireframe_icosphere() {
+    check_golden(
+        "wireframe_icosphere",
+        &drawers::wireframe(primitives::icosphere(1f32, 2), Viewport::default()),
+    );
+}
+
+// Lines in every octant from the centre, plus the image borders.
+#[test]
+fn lines() {
+    let mut img: Image<Rgb> = Image::new(drawers::WIDTH, drawers::HEIGHT);
+    let (cx, cy) = (drawers::WIDTH / 2, drawers::HEIGHT / 2);
+    let white: Rgb = Rgb {
+        b: 255,
+        g: 255,
+        r: 255,
+    };
+    for k in 0..16u32 {
+        let angle: f32 = k as f32 * std::f32::consts::PI / 8f32 + 0.1f32;
+        let x: u32 = (cx as f32 + 500f32 * angle.cos()) as u32;
+        let y: u32 = (cy as f32 + 500f32 * angle.sin()) as u32;
+        let color: Rgb = Rgb {
+            b: (k * 16) as u8,
+            g: 255 - (k * 16) as u8,
+            r: 255,
+        };
+        drawers::line(cx, cy, x, y, &mut img, color);
+    }
+    let (right, top) = (drawers::WIDTH - 1, drawers::HEIGHT - 1);
+    drawers::line(0, 0, right, 0, &mut img, white);
+    drawers::line(0, top, right, top, &mut img, white);
+    drawers::line(0, 0, 0, top, &mut img, white);
+    drawers::line(right, 0, right, top, &mut img, white);
+    check_golden("lines", &img);
+}
+
+// The slack must not let a wrong patch through, however small.
+#[test]
+fn tolerance_rejects_wrong_patches() {
+    let expected: Image<Rgb> = Image::new(drawers::WIDTH, drawers::HEIGHT);
+    let patch = |size: u32, value: u8| -> Image<Rgb> {
+        let mut actual: Image<Rgb> = expected.clone();
+        for y in 500..500 + size {
+            for x in 900..900 + size {
+                *actual.get_mut(x, y).unwrap() = Rgb {
+                    b: value,
+                    g: value,
+                    r: value,
+                };
+            }
+        }
+        return actual;
+    };
+    assert!(compare::check_images_similar(&patch(3, 8), &expected, &TOLERANCE).is_ok());
+    assert!(compare::check_images_similar(&patch(3, 255), &expected, &TOLERANCE).is_err());
+    assert!(compare::check_images_similar(&patch(40, 8), &expected, &TOLERANCE).is_err());
+}
+
+// Copies of a thick line crossing the image border are clipped to it rather than dropped, so the
+// line keeps its width up to the border.
+#[test]
+fn thick_lines_reach_the_border() {
+    let mut style: FeatureLineStyle = FeatureLineStyle::default();
+    style.boundary.width = 41;
+    // A sheared plane, its top and bottom boundaries slanted.
+    let mut plane: Mesh = primitives::plane(3f32, 1f32, 1, 1);
+    for v in 0..plane.v_positions.xs.len() {
+        plane.v_positions.ys[v] += plane.v_positions.xs[v] * 0.3f32;
+    }
+    let viewport: Viewport = Viewport {
+        width: 320,
+        height: 180,
+    };
+    let img: Image<Rgb> = drawers::feature_lines(plane, &style, viewport);
+    let lit = |y: u32| -> usize {
+        return (0..img.width)
+            .filter(|&x| img.data[(y * img.width + x) as usize].r > 0)
+            .count();
+    };
+    // One of the slanted boundaries runs out of the image through its first row, the copies of
+    // its thick line leaving dozens of pixels in each of the rows next to it.
+    for y in 0..6 {
+        assert!(lit(y) > 40, "row {y}: {} pixels", lit(y));
+    }
+}
@@ -0,0 +1,40 @@
+#![allow(clippy::needless_return)]
+
+use rust_renderer::mesh::PolygonMesh;
+use rust_renderer::obj_importer;
+use std::fs;
+use std::path::PathBuf;
+
+fn import(name: &str, content: &str) -> PolygonMesh {
+    let path: PathBuf =
+        std::env::temp_dir().join(format!("rust_renderer_{}_{name}.obj", std::process::id()));
+    fs::write(&path, content).unwrap();
+    let mesh: PolygonMesh = obj_importer::obj_to_polygon_mesh(path.to_str().unwrap());
+    fs::remove_file(&path).unwrap();
+    return mesh;
+}
+
+#[test]
+fn negative_indices_count_back_from_the_last_element() {
+    let mesh: PolygonMesh = import(
+        "negative",
+        "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nf 1/1 2/2 3/2\n\
+         v 1 1 0\nvn 0 0 1\nf -3//-1 -2//-1 -1//-1\n",
+    );
+    assert_eq!(mesh.polygons.len(), 2);
+    assert_eq!(mesh.polygons[0].vertices, vec![0, 1, 2]);
+    assert_eq!(mesh.polygons[0].textures, vec![0, 1, 1]);
+    assert_eq!(mesh.polygons[1].vertices, vec![1, 2, 3]);
+    assert_eq!(mesh.polygons[1].textures, vec![0, 0, 0]);
+    assert_eq!(mesh.polygons[1].normals, vec![0, 0, 0]);
+}
+
+#[test]
+fn faces_with_unreadable_indices_are_skipped() {
+    let mesh: PolygonMesh = import(
+        "unreadable",
+        "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 1 2\nf 1 2 x\nf -4 -3 -2\nf 1/0 2 3\nf 1 2 3\n",
+    );
+    assert_eq!(mesh.polygons.len(), 1);
+    assert_eq!(mesh.polygons[0].vertices, vec![0, 1, 2]);
+}
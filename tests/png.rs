@@ -0,0 +1,194 @@
+#![allow(clippy::needless_return)]
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust_renderer::png;
+use rust_renderer::tga::{DynamicImage, Grayscale, Image, PixelChannels, Rgb, Rgba};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Cursor};
+
+// The files under tests/images are written by an independent encoder, with every filter type
+// in turn and the image data split over two IDAT chunks.
+fn read(name: &str) -> DynamicImage {
+    let path: String = format!("{}/tests/images/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let mut reader: BufReader<File> = BufReader::new(File::open(path).unwrap());
+    return png::read(&mut reader, false).unwrap();
+}
+
+fn rgb_image(img: DynamicImage) -> Image<Rgb> {
+    match img {
+        DynamicImage::Rgb(img) => return img,
+        _ => panic!("expected an RGB image"),
+    }
+}
+
+fn rgba_image(img: DynamicImage) -> Image<Rgba> {
+    match img {
+        DynamicImage::Rgba(img) => return img,
+        _ => panic!("expected an RGBA image"),
+    }
+}
+
+fn check_rgb(img: &Image<Rgb>, expected: impl Fn(usize, usize) -> [u8; 3]) {
+    for y in 0..img.height as usize {
+        for x in 0..img.width as usize {
+            let pixel: Rgb = img.data[y * img.width as usize + x];
+            assert_eq!([pixel.r, pixel.g, pixel.b], expected(x, y), "({x}, {y})");
+        }
+    }
+}
+
+fn check_rgba(img: &Image<Rgba>, expected: impl Fn(usize, usize) -> [u8; 4]) {
+    for y in 0..img.height as usize {
+        for x in 0..img.width as usize {
+            let pixel: Rgba = img.data[y * img.width as usize + x];
+            let actual: [u8; 4] = [pixel.r, pixel.g, pixel.b, pixel.a];
+            assert_eq!(actual, expected(x, y), "({x}, {y})");
+        }
+    }
+}
+
+#[test]
+fn decodes_dynamic_huffman_blocks() {
+    let img: Image<Rgb> = rgb_image(read("dynamic_huffman.png"));
+    assert_eq!((img.width, img.height), (32, 32));
+    check_rgb(&img, |x, y| {
+        [(x * 8) as u8, (y * 8) as u8, (((x ^ y) * 8) & 255) as u8]
+    });
+}
+
+#[test]
+fn decodes_adam7_interlacing() {
+    let img: Image<Rgba> = rgba_image(read("adam7.png"));
+    assert_eq!((img.width, img.height), (13, 11));
+    check_rgba(&img, |x, y| {
+        [
+            (x * 19) as u8,
+            (y * 23) as u8,
+            ((x + y) * 7) as u8,
+            (255 - x * y) as u8,
+        ]
+    });
+}
+
+#[test]
+fn decodes_palette_with_transparency() {
+    // 4 bit indices, only the first four palette entries having an alpha.
+    let img: Image<Rgba> = rgba_image(read("palette_trns.png"));
+    assert_eq!((img.width, img.height), (10, 6));
+    check_rgba(&img, |x, y| {
+        let i: usize = (x + 2 * y) % 16;
+        let alpha: u8 = [0, 64, 128, 192].get(i).copied().unwrap_or(255);
+        [(i * 16) as u8, (255 - i * 16) as u8, (i * 8) as u8, alpha]
+    });
+}
+
+#[test]
+fn decodes_16_bit_samples_and_colour_key() {
+    // The key matches the full 16 bit samples of a single pixel, other pixels share its high
+    // bytes.
+    let img: Image<Rgba> = rgba_image(read("rgb16_trns.png"));
+    assert_eq!((img.width, img.height), (9, 7));
+    check_rgba(&img, |x, y| {
+        let is_key: bool = (x, y) == (4, 3);
+        let r: u16 = (x * 7000 + y) as u16;
+        let g: u16 = (y * 9000 + x) as u16;
+        let b: u16 = if is_key { 0x1234 } else { 0xabcd };
+        let alpha: u8 = if is_key { 0 } else { 255 };
+        [(r >> 8) as u8, (g >> 8) as u8, (b >> 8) as u8, alpha]
+    });
+}
+
+fn chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut bytes: Vec<u8> = (data.len() as u32).to_be_bytes().to_vec();
+    bytes.extend_from_slice(chunk_type);
+    bytes.extend_from_slice(data);
+    let mut crc_input: Vec<u8> = chunk_type.to_vec();
+    crc_input.extend_from_slice(data);
+    bytes.extend(png::crc32(&crc_input).to_be_bytes());
+    return bytes;
+}
+
+#[test]
+fn image_data_larger_than_the_header_is_rejected() {
+    // A 1x1 greyscale image needs 2 bytes, the stored block holds 1000.
+    let mut stream: Vec<u8> = vec![0x78, 0x01, 0x01, 0xe8, 0x03, 0x17, 0xfc];
+    stream.extend(vec![0u8; 1000]);
+    stream.extend([0u8; 4]);
+    let mut bytes: Vec<u8> = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+    bytes.extend(chunk(b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]));
+    bytes.extend(chunk(b"IDAT", &stream));
+    bytes.extend(chunk(b"IEND", &[]));
+    match png::read(&mut Cursor::new(&bytes), false) {
+        Err(e) => assert_eq!(e.kind(), io::ErrorKind::InvalidData),
+        Ok(_) => panic!("the image data was accepted"),
+    }
+}
+
+// A smooth gradient compresses into a fixed Huffman block, noise is stored.
+fn test_image<T: PixelChannels>(noisy: bool) -> Image<T> {
+    let mut img: Image<T> = Image::new(24, 16);
+    let mut rng: StdRng = StdRng::seed_from_u64(5);
+    for (i, pixel) in img.data.iter_mut().enumerate() {
+        let (x, y): (usize, usize) = (i % 24, i / 24);
+        let channels: [u8; 4] = if noisy {
+            rng.gen()
+        } else {
+            [
+                (x * 8) as u8,
+                (y * 12) as u8,
+                (x + y) as u8,
+                (255 - x) as u8,
+            ]
+        };
+        *pixel = T::from_channels(&channels[..T::BPP as usize]);
+    }
+    return img;
+}
+
+fn channels<T: PixelChannels>(img: &Image<T>) -> (u8, Vec<u8>) {
+    let mut bytes: Vec<u8> = Vec::new();
+    for pixel in &img.data {
+        pixel.push_channels(&mut bytes);
+    }
+    return (T::BPP, bytes);
+}
+
+// The reader is checked against independently encoded files above, so a round trip also
+// catches channels swapped by the writer.
+fn check_round_trip<T: PixelChannels>() {
+    for noisy in [false, true] {
+        let img: Image<T> = test_image(noisy);
+        for vflip in [false, true] {
+            let mut bytes: Vec<u8> = Vec::new();
+            img.write_png(&mut BufWriter::new(&mut bytes), vflip)
+                .unwrap();
+            // The first deflate block header follows the signature, IHDR, the IDAT length and
+            // type, and the zlib header.
+            let block_type: u8 = (bytes[8 + 25 + 8 + 2] >> 1) & 0b11;
+            assert_eq!(block_type, if noisy { 0b00 } else { 0b01 });
+
+            let decoded: DynamicImage = png::read(&mut Cursor::new(&bytes), vflip).unwrap();
+            let decoded: (u8, Vec<u8>) = match &decoded {
+                DynamicImage::Grayscale(img) => channels(img),
+                DynamicImage::Rgb(img) => channels(img),
+                DynamicImage::Rgba(img) => channels(img),
+            };
+            assert_eq!(decoded, channels(&img));
+        }
+    }
+}
+
+#[test]
+fn write_round_trips() {
+    check_round_trip::<Grayscale>();
+    check_round_trip::<Rgb>();
+    check_round_trip::<Rgba>();
+}
+
+#[test]
+fn crc32_matches_the_check_value() {
+    assert_eq!(png::crc32(b"123456789"), 0xcbf43926);
+    assert_eq!(png::crc32(b""), 0);
+    assert_eq!(png::crc32(b"IEND"), 0xae426082);
+}
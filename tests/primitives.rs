@@ -0,0 +1,116 @@
+#![allow(clippy::needless_return)]
+
+use rust_renderer::half_edge::HalfEdgeMesh;
+use rust_renderer::linear_algebra;
+use rust_renderer::mesh::{Mesh, Point3D};
+use rust_renderer::primitives;
+use std::f32::consts::PI;
+
+// Closed primitives with their expected volume, if simple enough.
+fn closed_primitives() -> Vec<(&'static str, Mesh, Option<f32>)> {
+    return vec![
+        ("cube", primitives::cube(2f32, 3), Some(8f32)),
+        (
+            "uv_sphere",
+            primitives::uv_sphere(1f32, 48, 24),
+            Some(4f32 / 3f32 * PI),
+        ),
+        (
+            "icosphere",
+            primitives::icosphere(1f32, 3),
+            Some(4f32 / 3f32 * PI),
+        ),
+        (
+            "cylinder",
+            primitives::cylinder(0.5f32, 2f32, 48, 3),
+            Some(PI * 0.5f32),
+        ),
+        (
+            "cone",
+            primitives::cone(0.5f32, 2f32, 48, 3),
+            Some(PI * 0.5f32 / 3f32),
+        ),
+        ("torus", primitives::torus(1f32, 0.25f32, 48, 24), None),
+        ("capsule", primitives::capsule(0.4f32, 0.8f32, 24, 8), None),
+        (
+            "arrow",
+            primitives::arrow(2f32, 0.1f32, 0.2f32, 0.5f32, 16),
+            None,
+        ),
+    ];
+}
+
+fn triangle_positions(mesh: &Mesh, vertices: [usize; 3]) -> [Point3D<f32>; 3] {
+    return vertices.map(|v| mesh.v_positions.get_at(v));
+}
+
+fn signed_volume(mesh: &Mesh) -> f32 {
+    return mesh
+        .triangles
+        .iter()
+        .map(|triangle| {
+            let [p, q, r] = triangle_positions(mesh, triangle.vertices);
+            return linear_algebra::dot_product(p, linear_algebra::cross_product(q, r)) / 6f32;
+        })
+        .sum();
+}
+
+#[test]
+fn closed_primitives_are_closed_manifolds() {
+    for (name, mesh, _) in closed_primitives() {
+        let half_edges: HalfEdgeMesh = HalfEdgeMesh::from_mesh(&mesh);
+        assert!(half_edges.is_manifold(), "{name}");
+        assert!(half_edges.non_manifold_vertices().is_empty(), "{name}");
+        assert!(half_edges.boundary_loops().is_empty(), "{name}");
+        // The torus has genus one, everything else is a sphere.
+        let euler: i64 = half_edges.vertex_count() as i64 - half_edges.half_edge_count() as i64 / 2
+            + half_edges.face_count() as i64;
+        assert_eq!(euler, if name == "torus" { 0 } else { 2 }, "{name}");
+    }
+}
+
+#[test]
+fn plane_has_a_single_boundary() {
+    let half_edges: HalfEdgeMesh = HalfEdgeMesh::from_mesh(&primitives::plane(2f32, 1f32, 4, 2));
+    assert!(half_edges.is_manifold());
+    let loops: Vec<Vec<usize>> = half_edges.boundary_loops();
+    assert_eq!(loops.len(), 1);
+    assert_eq!(loops[0].len(), 12);
+}
+
+// Counter-clockwise winding seen from outside, which the corner normals agree with.
+#[test]
+fn normals_agree_with_winding() {
+    let mut meshes: Vec<(&'static str, Mesh, Option<f32>)> = closed_primitives();
+    meshes.push(("plane", primitives::plane(2f32, 1f32, 4, 2), None));
+    for (name, mesh, volume) in meshes {
+        for triangle in &mesh.triangles {
+            let [p, q, r] = triangle_positions(&mesh, triangle.vertices);
+            let face_normal: Point3D<f32> =
+                linear_algebra::normalized(linear_algebra::cross_product(
+                    linear_algebra::subtract(q, p),
+                    linear_algebra::subtract(r, p),
+                ));
+            for normal in triangle.normals {
+                let cosine: f32 =
+                    linear_algebra::dot_product(face_normal, mesh.v_normals.get_at(normal));
+                assert!(cosine > 0f32, "{name}: {cosine}");
+            }
+        }
+        if let Some(volume) = volume {
+            let error: f32 = (signed_volume(&mesh) - volume).abs() / volume;
+            assert!(error < 0.02f32, "{name}: {}", signed_volume(&mesh));
+        }
+    }
+}
+
+#[test]
+fn icosphere_uvs_do_not_wrap() {
+    let mesh: Mesh = primitives::icosphere(1f32, 3);
+    for triangle in &mesh.triangles {
+        let us: [f32; 3] = triangle.textures.map(|t| mesh.v_textures.get_at(t).0);
+        let span: f32 = us.iter().cloned().fold(f32::NEG_INFINITY, f32::max)
+            - us.iter().cloned().fold(f32::INFINITY, f32::min);
+        assert!(span < 0.25f32, "{us:?}");
+    }
+}
@@ -0,0 +1,266 @@
+#![allow(clippy::needless_return)]
+
+use rust_renderer::mesh::{Coords2D, Coords3D, Material, Mesh, Point3D, Triangle};
+use rust_renderer::repair::ValidationReport;
+
+fn coords(points: &[[f32; 3]]) -> Coords3D {
+    let mut coords: Coords3D = Coords3D {
+        xs: Vec::new(),
+        ys: Vec::new(),
+        zs: Vec::new(),
+    };
+    for &[x, y, z] in points {
+        coords.add_vector(x, y, z);
+    }
+    return coords;
+}
+
+fn mesh(positions: &[[f32; 3]], triangles: &[[usize; 3]]) -> Mesh {
+    let mut textures: Coords2D = Coords2D {
+        us: Vec::new(),
+        vs: Vec::new(),
+    };
+    textures.add_vector(0f32, 0f32);
+    return Mesh {
+        v_positions: coords(positions),
+        v_normals: coords(&[[0f32, 0f32, 1f32]]),
+        v_textures: textures,
+        triangles: triangles
+            .iter()
+            .map(|&vertices| Triangle {
+                vertices,
+                normals: [0; 3],
+                textures: [0; 3],
+                material: 0,
+            })
+            .collect(),
+        materials: vec![Material::new("default")],
+    };
+}
+
+const CUBE_CORNERS: [[f32; 3]; 8] = [
+    [0f32, 0f32, 0f32],
+    [1f32, 0f32, 0f32],
+    [1f32, 1f32, 0f32],
+    [0f32, 1f32, 0f32],
+    [0f32, 0f32, 1f32],
+    [1f32, 0f32, 1f32],
+    [1f32, 1f32, 1f32],
+    [0f32, 1f32, 1f32],
+];
+
+// Two outward facing triangles per face, the bottom face first.
+const CUBE_TRIANGLES: [[usize; 3]; 12] = [
+    [0, 2, 1],
+    [0, 3, 2],
+    [4, 5, 6],
+    [4, 6, 7],
+    [0, 1, 5],
+    [0, 5, 4],
+    [1, 2, 6],
+    [1, 6, 5],
+    [2, 3, 7],
+    [2, 7, 6],
+    [3, 0, 4],
+    [3, 4, 7],
+];
+
+fn cube() -> Mesh {
+    return mesh(&CUBE_CORNERS, &CUBE_TRIANGLES);
+}
+
+// Every triangle with its own three positions, off by less than the welding distance.
+fn cube_soup() -> Mesh {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut triangles: Vec<[usize; 3]> = Vec::new();
+    for (t, triangle) in CUBE_TRIANGLES.iter().enumerate() {
+        let offset: f32 = t as f32 * 1e-6f32;
+        triangles.push([0, 1, 2].map(|k| positions.len() + k));
+        for &v in triangle {
+            positions.push(CUBE_CORNERS[v].map(|c| c + offset));
+        }
+    }
+    return mesh(&positions, &triangles);
+}
+
+fn volume(mesh: &Mesh) -> f32 {
+    return mesh
+        .triangles
+        .iter()
+        .map(|triangle| {
+            let [p, q, r] = triangle.vertices.map(|v| mesh.v_positions.get_at(v));
+            (p.x * (q.y * r.z - q.z * r.y)
+                + p.y * (q.z * r.x - q.x * r.z)
+                + p.z * (q.x * r.y - q.y * r.x))
+                / 6f32
+        })
+        .sum();
+}
+
+#[test]
+fn closed_cube_is_valid() {
+    let report: ValidationReport = cube().validate();
+    assert_eq!(report, ValidationReport::default());
+    assert!(report.is_valid());
+    assert!((volume(&cube()) - 1f32).abs() < 1e-6f32);
+}
+
+#[test]
+fn weld_joins_close_positions() {
+    let mut soup: Mesh = cube_soup();
+    let report: ValidationReport = soup.validate();
+    assert_eq!(report.boundary_edges, 36);
+    assert!(report.is_valid());
+
+    // Each corner is shared by 4 to 6 triangles, the first of them keeping its position.
+    assert_eq!(soup.weld_vertices(1e-3f32), 36 - 8);
+    assert_eq!(soup.remove_unreferenced_vertices(), 36 - 8);
+    assert_eq!(soup.v_positions.xs.len(), 8);
+    let report: ValidationReport = soup.validate();
+    assert_eq!(report.boundary_edges, 0);
+    assert!(report.is_valid());
+    assert!((volume(&soup) - 1f32).abs() < 1e-4f32);
+
+    // Nothing within a smaller distance.
+    assert_eq!(cube_soup().weld_vertices(1e-7f32), 0);
+}
+
+#[test]
+fn degenerate_and_duplicate_triangles_are_removed() {
+    let mut positions: Vec<[f32; 3]> = CUBE_CORNERS.to_vec();
+    // Halfway along the bottom front edge.
+    positions.push([0.5f32, 0f32, 0f32]);
+    let mut triangles: Vec<[usize; 3]> = CUBE_TRIANGLES.to_vec();
+    triangles.extend([[0, 0, 1], [0, 8, 1], [2, 0, 1], [4, 6, 5]]);
+    let mut mesh: Mesh = mesh(&positions, &triangles);
+
+    let report: ValidationReport = mesh.validate();
+    assert_eq!(report.degenerate_triangles, vec![12, 13]);
+    // Over the vertices of triangles 0 and 2, in either winding.
+    assert_eq!(report.duplicate_triangles, vec![14, 15]);
+    assert!(!report.is_valid());
+
+    assert_eq!(mesh.remove_degenerate_triangles(), 2);
+    assert_eq!(mesh.remove_duplicate_triangles(), 2);
+    assert_eq!(mesh.triangles.len(), 12);
+    assert_eq!(mesh.triangles[0].vertices, CUBE_TRIANGLES[0]);
+    assert_eq!(mesh.remove_unreferenced_vertices(), 1);
+    assert_eq!(mesh.validate(), ValidationReport::default());
+}
+
+#[test]
+fn orientation_follows_the_neighbours() {
+    let mut flipped: Mesh = cube();
+    flipped.triangles[3].vertices = [4, 7, 6];
+    let report: ValidationReport = flipped.validate();
+    assert_eq!(report.inconsistent_edges, vec![[4, 6], [4, 7], [6, 7]]);
+    assert!(!report.is_valid());
+    assert_eq!(flipped.orient_consistently(), 1);
+    assert_eq!(flipped.triangles[3].vertices, [4, 6, 7]);
+    assert!(flipped.validate().is_valid());
+
+    // An inside out cube is turned back to enclose a positive volume.
+    let mut inside_out: Mesh = cube();
+    for triangle in &mut inside_out.triangles {
+        triangle.vertices.swap(1, 2);
+    }
+    assert!(volume(&inside_out) < 0f32);
+    assert_eq!(inside_out.orient_consistently(), 12);
+    assert!((volume(&inside_out) - 1f32).abs() < 1e-6f32);
+    assert_eq!(inside_out.orient_consistently(), 0);
+}
+
+#[test]
+fn holes_are_filled_up_to_a_size() {
+    // The top face gone, a square hole.
+    let mut open: Mesh = cube();
+    open.triangles.drain(2..4);
+    assert_eq!(open.validate().boundary_edges, 4);
+    assert_eq!(open.fill_holes(3), 0);
+    assert_eq!(open.fill_holes(4), 1);
+    // A fan around the centre of the face.
+    assert_eq!(open.triangles.len(), 14);
+    let centre: Point3D<f32> = open.v_positions.get_at(8);
+    assert_eq!([centre.x, centre.y, centre.z], [0.5f32, 0.5f32, 1f32]);
+    let report: ValidationReport = open.validate();
+    assert_eq!(report.boundary_edges, 0);
+    assert!(report.is_valid());
+    assert!((volume(&open) - 1f32).abs() < 1e-6f32);
+
+    // A triangular hole takes a single triangle.
+    let mut open: Mesh = cube();
+    open.triangles.remove(2);
+    assert_eq!(open.fill_holes(8), 1);
+    assert_eq!(open.triangles.len(), 12);
+    assert_eq!(open.v_positions.xs.len(), 8);
+    assert!(open.validate().is_valid());
+    assert!((volume(&open) - 1f32).abs() < 1e-6f32);
+}
+
+#[test]
+fn unreferenced_vertices_are_dropped() {
+    let mut positions: Vec<[f32; 3]> = vec![[9f32, 9f32, 9f32]];
+    positions.extend(CUBE_CORNERS);
+    positions.insert(5, [8f32, 8f32, 8f32]);
+    // Corners 0 to 3 moved up by one, 4 to 7 by two.
+    let triangles: Vec<[usize; 3]> = CUBE_TRIANGLES
+        .iter()
+        .map(|triangle| triangle.map(|v| if v < 4 { v + 1 } else { v + 2 }))
+        .collect();
+    let mut mesh: Mesh = mesh(&positions, &triangles);
+    assert_eq!(mesh.validate().unreferenced_vertices, vec![0, 5]);
+
+    assert_eq!(mesh.remove_unreferenced_vertices(), 2);
+    for (triangle, expected) in mesh.triangles.iter().zip(CUBE_TRIANGLES) {
+        assert_eq!(triangle.vertices, expected);
+    }
+    for (v, corner) in CUBE_CORNERS.iter().enumerate() {
+        let p: Point3D<f32> = mesh.v_positions.get_at(v);
+        assert_eq!([p.x, p.y, p.z], *corner);
+    }
+}
+
+#[test]
+fn validation_reports_every_problem() {
+    let mut positions: Vec<[f32; 3]> = CUBE_CORNERS.to_vec();
+    positions.push([f32::NAN, 0f32, 0f32]);
+    positions.push([1f32, 1f32, 1f32]);
+    let mut mesh: Mesh = mesh(&positions, &CUBE_TRIANGLES);
+    // A corner past the positions.
+    mesh.triangles[1].vertices[2] = 12;
+    // A NaN corner, on a third triangle along the edge from 0 to 1.
+    mesh.triangles.push(Triangle {
+        vertices: [0, 1, 8],
+        normals: [0; 3],
+        textures: [0; 3],
+        material: 0,
+    });
+    // A normal past the normals.
+    mesh.triangles[5].normals = [0, 1, 0];
+    // A material past the materials.
+    mesh.triangles.push(Triangle {
+        vertices: [0, 3, 2],
+        normals: [0; 3],
+        textures: [0; 3],
+        material: 3,
+    });
+
+    let report: ValidationReport = mesh.validate();
+    assert_eq!(report.out_of_range_triangles, vec![1, 13]);
+    assert_eq!(report.missing_attributes, vec![5]);
+    assert_eq!(report.non_finite_vertices, vec![8]);
+    assert_eq!(report.degenerate_triangles, vec![12]);
+    assert_eq!(report.duplicate_vertices, vec![[9, 6]]);
+    assert_eq!(report.unreferenced_vertices, vec![9]);
+    assert_eq!(report.non_manifold_edges, vec![[0, 1]]);
+    assert!(!report.is_valid());
+
+    assert_eq!(mesh.remove_invalid_triangles(), 3);
+    assert_eq!(mesh.triangles.len(), 11);
+    assert_eq!(mesh.remove_unreferenced_vertices(), 2);
+    let report: ValidationReport = mesh.validate();
+    assert!(report.non_manifold_edges.is_empty());
+    // The hole left by the triangle past the positions.
+    assert_eq!(report.boundary_edges, 3);
+    assert!(report.is_valid());
+}
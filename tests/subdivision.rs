@@ -0,0 +1,366 @@
+#![allow(clippy::needless_return)]
+
+use rust_renderer::mesh::{Coords2D, Coords3D, Material, Mesh, Point3D, Polygon, PolygonMesh};
+use rust_renderer::subdivision::SubdivisionOptions;
+use std::collections::HashMap;
+
+fn coords(points: &[[f32; 3]]) -> Coords3D {
+    let mut coords: Coords3D = Coords3D {
+        xs: Vec::new(),
+        ys: Vec::new(),
+        zs: Vec::new(),
+    };
+    for &[x, y, z] in points {
+        coords.add_vector(x, y, z);
+    }
+    return coords;
+}
+
+fn uvs(points: &[[f32; 2]]) -> Coords2D {
+    let mut uvs: Coords2D = Coords2D {
+        us: Vec::new(),
+        vs: Vec::new(),
+    };
+    for &[u, v] in points {
+        uvs.add_vector(u, v);
+    }
+    return uvs;
+}
+
+// Faces are given as position indices, and as UV indices when `textures` is not empty.
+fn cage(positions: &[[f32; 3]], faces: &[Vec<usize>], textures: &[Vec<usize>]) -> PolygonMesh {
+    return PolygonMesh {
+        v_positions: coords(positions),
+        v_normals: coords(&[[0f32, 0f32, 1f32]]),
+        v_textures: uvs(&[[0f32, 0f32]]),
+        polygons: faces
+            .iter()
+            .enumerate()
+            .map(|(f, vertices)| Polygon {
+                vertices: vertices.clone(),
+                normals: vec![0; vertices.len()],
+                textures: textures
+                    .get(f)
+                    .cloned()
+                    .unwrap_or_else(|| vec![0; vertices.len()]),
+                material: 0,
+            })
+            .collect(),
+        materials: vec![Material::new("default")],
+    };
+}
+
+// Regular tetrahedron centred on the origin, with outward facing triangles.
+fn tetrahedron() -> Mesh {
+    let faces: Vec<Vec<usize>> = vec![vec![0, 1, 2], vec![0, 3, 1], vec![0, 2, 3], vec![1, 3, 2]];
+    let positions: [[f32; 3]; 4] = [
+        [1f32, 1f32, 1f32],
+        [1f32, -1f32, -1f32],
+        [-1f32, 1f32, -1f32],
+        [-1f32, -1f32, 1f32],
+    ];
+    return cage(&positions, &faces, &[]).triangulate();
+}
+
+// The cube from -1 to 1, corners numbered by their bits along x, y and z.
+fn cube_cage() -> PolygonMesh {
+    let positions: Vec<[f32; 3]> = (0..8)
+        .map(|i| [0, 1, 2].map(|bit| if i >> bit & 1 == 1 { 1f32 } else { -1f32 }))
+        .collect();
+    let faces: Vec<Vec<usize>> = vec![
+        vec![0, 2, 3, 1],
+        vec![4, 5, 7, 6],
+        vec![0, 1, 5, 4],
+        vec![2, 6, 7, 3],
+        vec![0, 4, 6, 2],
+        vec![1, 3, 7, 5],
+    ];
+    return cage(&positions, &faces, &[]);
+}
+
+fn point(p: [f32; 3]) -> Point3D<f32> {
+    return Point3D {
+        x: p[0],
+        y: p[1],
+        z: p[2],
+    };
+}
+
+fn combine(terms: &[(f32, Point3D<f32>)]) -> Point3D<f32> {
+    return terms.iter().fold(point([0f32; 3]), |sum, &(weight, p)| {
+        point([
+            sum.x + weight * p.x,
+            sum.y + weight * p.y,
+            sum.z + weight * p.z,
+        ])
+    });
+}
+
+fn assert_close(actual: Point3D<f32>, expected: Point3D<f32>) {
+    let distance: f32 = [
+        actual.x - expected.x,
+        actual.y - expected.y,
+        actual.z - expected.z,
+    ]
+    .iter()
+    .map(|d| d * d)
+    .sum::<f32>()
+    .sqrt();
+    assert!(distance < 1e-5f32, "{actual:?} {expected:?}");
+}
+
+fn neighbours(mesh: &Mesh, v: usize) -> Vec<usize> {
+    let mut neighbours: Vec<usize> = mesh
+        .triangles
+        .iter()
+        .filter(|triangle| triangle.vertices.contains(&v))
+        .flat_map(|triangle| triangle.vertices)
+        .filter(|&w| w != v)
+        .collect();
+    neighbours.sort();
+    neighbours.dedup();
+    return neighbours;
+}
+
+// Number of triangles along every edge.
+fn edge_uses(mesh: &Mesh) -> HashMap<[usize; 2], usize> {
+    let mut uses: HashMap<[usize; 2], usize> = HashMap::new();
+    for triangle in &mesh.triangles {
+        for k in 0..3 {
+            let (a, b) = (triangle.vertices[k], triangle.vertices[(k + 1) % 3]);
+            *uses.entry([a.min(b), a.max(b)]).or_default() += 1;
+        }
+    }
+    return uses;
+}
+
+fn levels(levels: u32) -> SubdivisionOptions {
+    return SubdivisionOptions {
+        levels,
+        ..Default::default()
+    };
+}
+
+#[test]
+fn loop_counts_and_stencils() {
+    let tetrahedron: Mesh = tetrahedron();
+    let once: Mesh = tetrahedron.loop_subdivide(&levels(1));
+    assert_eq!((once.v_positions.xs.len(), once.triangles.len()), (10, 16));
+    let twice: Mesh = tetrahedron.loop_subdivide(&levels(2));
+    assert_eq!(
+        (twice.v_positions.xs.len(), twice.triangles.len()),
+        (34, 64)
+    );
+
+    // Valence 3 vertices keep their index and move by beta = 3/16.
+    let p: Vec<Point3D<f32>> = (0..4).map(|v| tetrahedron.v_positions.get_at(v)).collect();
+    assert_close(
+        once.v_positions.get_at(0),
+        combine(&[
+            (7f32 / 16f32, p[0]),
+            (3f32 / 16f32, p[1]),
+            (3f32 / 16f32, p[2]),
+            (3f32 / 16f32, p[3]),
+        ]),
+    );
+    // Edge points follow the position indices of the sorted edges, 3/8 of each end and 1/8 of
+    // each opposite vertex.
+    assert_close(
+        once.v_positions.get_at(4),
+        combine(&[
+            (0.375f32, p[0]),
+            (0.375f32, p[1]),
+            (0.125f32, p[2]),
+            (0.125f32, p[3]),
+        ]),
+    );
+
+    // The new edge points are regular, with six neighbours weighted by beta = 1/16.
+    let ring: Vec<usize> = neighbours(&once, 4);
+    assert_eq!(ring.len(), 6);
+    let mut terms: Vec<(f32, Point3D<f32>)> = vec![(10f32 / 16f32, once.v_positions.get_at(4))];
+    terms.extend(
+        ring.iter()
+            .map(|&w| (1f32 / 16f32, once.v_positions.get_at(w))),
+    );
+    assert_close(twice.v_positions.get_at(4), combine(&terms));
+}
+
+#[test]
+fn loop_converges_to_the_limit() {
+    // The limit of a valence 3 vertex is 2/5 of it and 1/5 of each neighbour, p / 5 for a
+    // tetrahedron centred on the origin.
+    let tetrahedron: Mesh = tetrahedron();
+    let limit: Point3D<f32> = combine(&[(0.2f32, tetrahedron.v_positions.get_at(0))]);
+    let mut distance: f32 = f32::INFINITY;
+    for level in 1..6 {
+        let p: Point3D<f32> = tetrahedron
+            .loop_subdivide(&levels(level))
+            .v_positions
+            .get_at(0);
+        let d: f32 =
+            ((p.x - limit.x).powi(2) + (p.y - limit.y).powi(2) + (p.z - limit.z).powi(2)).sqrt();
+        assert!(d < distance * 0.7f32, "{level} {d} {distance}");
+        distance = d;
+    }
+    assert!(distance < 1e-2f32);
+}
+
+#[test]
+fn catmull_clark_counts_and_stencils() {
+    let cube: Mesh = cube_cage().catmull_clark(&levels(1));
+    // 24 quads split in two triangles, over the corners, edge points and face points.
+    assert_eq!(cube.triangles.len(), 48);
+    assert_eq!(cube.v_positions.xs.len(), 8 + 12 + 6);
+
+    // (F + 2R + (n - 3)P) / n with n = 3: F the mean of the face centres and R of the edge
+    // midpoints around the corner.
+    for v in 0..8 {
+        let corner: Point3D<f32> = cube_cage().v_positions.get_at(v);
+        let face_mean: Point3D<f32> = combine(&[(1f32 / 3f32, corner)]);
+        let edge_mean: Point3D<f32> = combine(&[(2f32 / 3f32, corner)]);
+        let expected: Point3D<f32> = combine(&[(1f32 / 3f32, face_mean), (2f32 / 3f32, edge_mean)]);
+        assert_close(cube.v_positions.get_at(v), expected);
+        assert_close(expected, combine(&[(5f32 / 9f32, corner)]));
+    }
+    // The mean of the edge ends and of the two face centres, the edge from corner 0 to 1 coming
+    // first.
+    assert_close(
+        cube.v_positions.get_at(8),
+        point([0f32, -0.75f32, -0.75f32]),
+    );
+    // Face points after the edge points, in face order.
+    assert_close(cube.v_positions.get_at(20), point([0f32, 0f32, -1f32]));
+}
+
+#[test]
+fn creases_keep_the_cube_shape() {
+    // Every edge sharp, given explicitly or through the crease angle: corners do not move and
+    // every point stays on the faces of the cube.
+    let creases: Vec<[usize; 2]> = cube_cage()
+        .polygons
+        .iter()
+        .flat_map(|polygon| (0..4).map(|i| [polygon.vertices[i], polygon.vertices[(i + 1) % 4]]))
+        .collect();
+    for options in [
+        SubdivisionOptions {
+            levels: 2,
+            creases,
+            ..Default::default()
+        },
+        SubdivisionOptions {
+            levels: 2,
+            crease_angle: 60f32,
+            ..Default::default()
+        },
+    ] {
+        let cube: Mesh = cube_cage().catmull_clark(&options);
+        for v in 0..8 {
+            assert_close(
+                cube.v_positions.get_at(v),
+                cube_cage().v_positions.get_at(v),
+            );
+        }
+        let mut on_edges: usize = 0;
+        for v in 0..cube.v_positions.xs.len() {
+            let p: Point3D<f32> = cube.v_positions.get_at(v);
+            let on_face: Vec<bool> = [p.x, p.y, p.z]
+                .iter()
+                .map(|c| (c.abs() - 1f32).abs() < 1e-6f32)
+                .collect();
+            assert!(on_face.contains(&true), "{p:?}");
+            // On a cube edge, and not rounded off.
+            if on_face.iter().filter(|&&on| on).count() >= 2 {
+                on_edges += 1;
+            }
+        }
+        // Corners, and three new points on each edge.
+        assert_eq!(on_edges, 8 + 12 * 3);
+        // Sharp edges split the normals along them, each of the 25 points of a face getting the
+        // flat normal of the face.
+        assert_eq!(cube.v_normals.xs.len(), 6 * 25);
+        for triangle in &cube.triangles {
+            let face_normal: Point3D<f32> = cube.triangle_normal(triangle);
+            for n in triangle.normals {
+                assert_close(cube.v_normals.get_at(n), face_normal);
+            }
+        }
+    }
+}
+
+#[test]
+fn boundaries_stay_in_place() {
+    // Without its top face, the cube is open along the square at z = 1.
+    let mut open: PolygonMesh = cube_cage();
+    open.polygons.remove(1);
+    let mesh: Mesh = open.catmull_clark(&levels(2));
+    let boundary: Vec<[usize; 2]> = edge_uses(&mesh)
+        .into_iter()
+        .filter(|&(_, uses)| uses == 1)
+        .map(|(edge, _)| edge)
+        .collect();
+    assert_eq!(boundary.len(), 16);
+    for [a, b] in boundary {
+        for v in [a, b] {
+            assert!((mesh.v_positions.get_at(v).z - 1f32).abs() < 1e-6f32);
+        }
+    }
+    // Boundary edge points are the midpoints, the edge from corner 4 to 5 being the ninth.
+    // Corners on two boundary edges follow the curve of the square.
+    let once: Mesh = open.catmull_clark(&levels(1));
+    assert_close(once.v_positions.get_at(8 + 8), point([0f32, -1f32, 1f32]));
+    assert_close(
+        once.v_positions.get_at(4),
+        point([-0.75f32, -0.75f32, 1f32]),
+    );
+}
+
+#[test]
+fn uvs_are_not_blended_across_seams() {
+    // Two triangles sharing the edge from position 1 to 2, each with its own UVs.
+    let positions: [[f32; 3]; 4] = [
+        [0f32, 0f32, 0f32],
+        [1f32, 0f32, 0f32],
+        [0f32, 1f32, 0f32],
+        [1f32, 1f32, 0f32],
+    ];
+    let mut mesh: PolygonMesh = cage(
+        &positions,
+        &[vec![0, 1, 2], vec![1, 3, 2]],
+        &[vec![0, 1, 2], vec![3, 4, 5]],
+    );
+    mesh.v_textures = uvs(&[
+        [0f32, 0f32],
+        [1f32, 0f32],
+        [0f32, 1f32],
+        [5f32, 0f32],
+        [6f32, 1f32],
+        [5f32, 1f32],
+    ]);
+    let once: Mesh = mesh.triangulate().loop_subdivide(&levels(1));
+    // Each side of the seam gets its own midpoint UV.
+    assert_eq!(once.v_textures.us.len(), 6 + 6);
+
+    // The seam midpoint comes third among the sorted edges.
+    let seam_point: usize = 4 + 2;
+    for triangle in &once.triangles {
+        let uvs: Vec<(f32, f32)> = triangle
+            .textures
+            .iter()
+            .map(|&t| once.v_textures.get_at(t))
+            .collect();
+        // Whole triangles on one side of the seam.
+        let left: bool = uvs[0].0 < 2f32;
+        assert!(uvs.iter().all(|uv| (uv.0 < 2f32) == left), "{uvs:?}");
+        for (&v, &uv) in triangle.vertices.iter().zip(&uvs) {
+            if v == seam_point {
+                let expected: (f32, f32) = if left {
+                    (0.5f32, 0.5f32)
+                } else {
+                    (5f32, 0.5f32)
+                };
+                assert_eq!(uv, expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,187 @@
+#![allow(clippy::needless_return)]
+
+use rust_renderer::linear_algebra::{self, Mat4};
+use rust_renderer::mesh::{Material, Mesh, Point3D, Triangle};
+use rust_renderer::primitives;
+
+fn point(x: f32, y: f32, z: f32) -> Point3D<f32> {
+    return Point3D { x, y, z };
+}
+
+fn assert_close(actual: Point3D<f32>, expected: Point3D<f32>, tolerance: f32) {
+    let distance: f32 = linear_algebra::norm(linear_algebra::subtract(actual, expected));
+    assert!(distance < tolerance, "{actual:?} {expected:?}");
+}
+
+fn volume(mesh: &Mesh) -> f32 {
+    return mesh
+        .triangles
+        .iter()
+        .map(|triangle| {
+            let [p, q, r] = triangle.vertices.map(|v| mesh.v_positions.get_at(v));
+            linear_algebra::dot_product(p, linear_algebra::cross_product(q, r)) / 6f32
+        })
+        .sum();
+}
+
+// Every corner normal on the outer side of its triangle.
+fn normals_face_out(mesh: &Mesh) -> bool {
+    return mesh.triangles.iter().all(|triangle| {
+        let face_normal: Point3D<f32> = mesh.triangle_normal(triangle);
+        triangle
+            .normals
+            .iter()
+            .all(|&n| linear_algebra::dot_product(face_normal, mesh.v_normals.get_at(n)) > 0f32)
+    });
+}
+
+#[test]
+fn inverse_undoes_the_matrix() {
+    let matrix: Mat4 = Mat4::translation(point(1f32, -2f32, 3f32))
+        .multiply(&Mat4::rotation(point(1f32, 2f32, 2f32), 0.7f32))
+        .multiply(&Mat4::scaling(point(2f32, 0.5f32, -3f32)));
+    let inverse: Mat4 = matrix.inverse().unwrap();
+    for product in [matrix.multiply(&inverse), inverse.multiply(&matrix)] {
+        for i in 0..4 {
+            for j in 0..4 {
+                let expected: f32 = if i == j { 1f32 } else { 0f32 };
+                assert!(
+                    (product.rows[i][j] - expected).abs() < 1e-5f32,
+                    "{product:?}"
+                );
+            }
+        }
+    }
+    let p: Point3D<f32> = point(0.3f32, 4f32, -1f32);
+    assert_close(
+        inverse.transform_point(matrix.transform_point(p)),
+        p,
+        1e-5f32,
+    );
+    assert!((matrix.determinant() * inverse.determinant() - 1f32).abs() < 1e-5f32);
+
+    // A scaling flattening the z axis has no inverse.
+    assert!(Mat4::scaling(point(1f32, 2f32, 0f32)).inverse().is_none());
+}
+
+#[test]
+fn normals_stay_perpendicular_under_non_uniform_scaling() {
+    // The unit sphere becomes an ellipsoid, whose normal at (x, y, z) is along
+    // (x / a^2, y / b^2, z / c^2).
+    let (a, b, c): (f32, f32, f32) = (3f32, 1f32, 0.5f32);
+    let mut mesh: Mesh = primitives::icosphere(1f32, 2);
+    mesh.transform(&Mat4::scaling(point(a, b, c)));
+    for triangle in &mesh.triangles {
+        for (&v, &n) in triangle.vertices.iter().zip(&triangle.normals) {
+            let p: Point3D<f32> = mesh.v_positions.get_at(v);
+            let expected: Point3D<f32> =
+                linear_algebra::normalized(point(p.x / (a * a), p.y / (b * b), p.z / (c * c)));
+            assert_close(mesh.v_normals.get_at(n), expected, 1e-5f32);
+        }
+    }
+    assert!(normals_face_out(&mesh));
+
+    // Transforming the normal like a direction leaves it slanted against the surface.
+    let p: Point3D<f32> = linear_algebra::normalized(point(1f32, 1f32, 1f32));
+    let tangent: Point3D<f32> = point(1f32, -1f32, 0f32);
+    let matrix: Mat4 = Mat4::scaling(point(a, b, c));
+    let normal: Point3D<f32> = matrix.transform_normal(p);
+    let tangent: Point3D<f32> = matrix.transform_vector(tangent);
+    assert!(linear_algebra::dot_product(normal, tangent).abs() < 1e-6f32);
+    let slanted: Point3D<f32> = matrix.transform_vector(p);
+    assert!(linear_algebra::dot_product(slanted, tangent).abs() > 1f32);
+}
+
+#[test]
+fn mirroring_swaps_the_winding() {
+    let original: Mesh = primitives::icosphere(1f32, 1);
+    let mirror: Mat4 = Mat4::scaling(point(-1f32, 2f32, 1f32));
+    assert!(mirror.linear_determinant() < 0f32);
+    let mut mirrored: Mesh = primitives::icosphere(1f32, 1);
+    mirrored.transform(&mirror);
+
+    // Still enclosing a positive volume, twice the original, and facing out.
+    assert!((volume(&mirrored) - 2f32 * volume(&original)).abs() < 1e-4f32);
+    assert!(normals_face_out(&mirrored));
+    for (triangle, before) in mirrored.triangles.iter().zip(&original.triangles) {
+        let [a, b, c] = before.vertices;
+        assert_eq!(triangle.vertices, [a, c, b]);
+        let [a, b, c] = before.normals;
+        assert_eq!(triangle.normals, [a, c, b]);
+        let [a, b, c] = before.textures;
+        assert_eq!(triangle.textures, [a, c, b]);
+    }
+
+    // A rotation keeps it.
+    let mut rotated: Mesh = primitives::icosphere(1f32, 1);
+    rotated.transform(&Mat4::rotation(point(0f32, 0f32, 1f32), 2f32));
+    for (triangle, before) in rotated.triangles.iter().zip(&original.triangles) {
+        assert_eq!(triangle.vertices, before.vertices);
+    }
+    assert!(normals_face_out(&rotated));
+}
+
+#[test]
+fn merge_rebases_every_index() {
+    let mut first: Mesh = primitives::icosphere(1f32, 0);
+    first.materials.push(Material::new("red"));
+    first.triangles[3].material = 1;
+    let second: Mesh = primitives::cube(2f32, 1);
+    let merged: Mesh = Mesh::merge(&[first.clone(), second.clone()]);
+
+    let positions: usize = first.v_positions.xs.len();
+    let normals: usize = first.v_normals.xs.len();
+    let textures: usize = first.v_textures.us.len();
+    assert_eq!(
+        merged.v_positions.xs.len(),
+        positions + second.v_positions.xs.len()
+    );
+    assert_eq!(
+        merged.v_normals.xs.len(),
+        normals + second.v_normals.xs.len()
+    );
+    assert_eq!(
+        merged.v_textures.us.len(),
+        textures + second.v_textures.us.len()
+    );
+    assert_eq!(merged.materials.len(), 3);
+    assert_eq!(
+        merged.triangles.len(),
+        first.triangles.len() + second.triangles.len()
+    );
+
+    // Every corner still reads the same attributes as in its own mesh.
+    for (mesh, triangles) in [
+        (&first, &merged.triangles[..first.triangles.len()]),
+        (&second, &merged.triangles[first.triangles.len()..]),
+    ] {
+        for (triangle, own) in triangles.iter().zip(&mesh.triangles) {
+            for k in 0..3 {
+                assert_close(
+                    merged.v_positions.get_at(triangle.vertices[k]),
+                    mesh.v_positions.get_at(own.vertices[k]),
+                    1e-6f32,
+                );
+                assert_close(
+                    merged.v_normals.get_at(triangle.normals[k]),
+                    mesh.v_normals.get_at(own.normals[k]),
+                    1e-6f32,
+                );
+                assert_eq!(
+                    merged.v_textures.get_at(triangle.textures[k]),
+                    mesh.v_textures.get_at(own.textures[k])
+                );
+            }
+            assert_eq!(
+                merged.materials[triangle.material].name,
+                mesh.materials[own.material].name
+            );
+        }
+    }
+    let last: &Triangle = merged.triangles.last().unwrap();
+    let own: &Triangle = second.triangles.last().unwrap();
+    assert_eq!(last.vertices, own.vertices.map(|v| v + positions));
+    assert_eq!(last.normals, own.normals.map(|n| n + normals));
+    assert_eq!(last.textures, own.textures.map(|t| t + textures));
+    assert_eq!(last.material, 2);
+}